@@ -82,16 +82,16 @@ impl BwaAligner {
             return Ok(None);
         }
 
-        let position = GenomicCoordinate::new(0, 1000, 1000 + read.sequence.len() as u64, true)?;
+        let position = GenomicCoordinate::new(0, 1000, 1000 + read.sequence().len() as u64, true)?;
 
         let aligned = AlignedRead {
             id: Uuid::new_v4(),
             qname: read.id.clone(),
             position,
             mapq: avg_quality as u8,
-            cigar: format!("{}M", read.sequence.len()),
-            sequence: read.sequence.clone(),
-            quality: read.quality.clone(),
+            cigar: format!("{}M", read.sequence().len()),
+            sequence: read.sequence().to_string(),
+            quality: read.quality().to_vec(),
             flags: 0,
             nm: Some(0),
         };
@@ -103,7 +103,7 @@ impl BwaAligner {
     fn calculate_alignment_score(&self, read: &FastqRecord) -> i32 {
         // Simplified scoring based on quality and length
         let avg_qual = read.average_quality();
-        let length = read.sequence.len() as i32;
+        let length = read.sequence().len() as i32;
         ((avg_qual * length as f64) / 40.0) as i32
     }
 }
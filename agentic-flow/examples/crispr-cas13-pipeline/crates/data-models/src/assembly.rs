@@ -0,0 +1,90 @@
+//! Summary statistics for assembled contigs
+
+use serde::{Deserialize, Serialize};
+
+/// Length statistics of a set of contigs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AssemblyStats {
+    /// Number of contigs
+    pub count: usize,
+    /// Sum of contig lengths
+    pub total_length: usize,
+    /// Shortest contig length
+    pub min: usize,
+    /// Longest contig length
+    pub max: usize,
+    /// Mean contig length
+    pub mean: f64,
+    /// Length of the shortest contig among the longest ones covering half
+    /// the assembly
+    pub n50: usize,
+    /// As [`AssemblyStats::n50`], covering 90% of the assembly
+    pub n90: usize,
+}
+
+/// Compute length statistics from contig lengths
+///
+/// Nx is found by sorting lengths in decreasing order and summing them until
+/// the running total reaches x% of the total length; the length that gets it
+/// there is Nx. An empty input, or one of only zero-length contigs, gives
+/// all-zero statistics rather than an error.
+pub fn assembly_stats(lengths: &[usize]) -> AssemblyStats {
+    let total_length: usize = lengths.iter().sum();
+    if total_length == 0 {
+        return AssemblyStats {
+            count: lengths.len(),
+            ..Default::default()
+        };
+    }
+
+    let mut sorted = lengths.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+
+    AssemblyStats {
+        count: sorted.len(),
+        total_length,
+        min: sorted[sorted.len() - 1],
+        max: sorted[0],
+        mean: total_length as f64 / sorted.len() as f64,
+        n50: nx(&sorted, total_length, 50),
+        n90: nx(&sorted, total_length, 90),
+    }
+}
+
+/// Get Nx from lengths sorted in decreasing order
+fn nx(sorted: &[usize], total_length: usize, percent: usize) -> usize {
+    let mut cumulative = 0;
+    for &length in sorted {
+        cumulative += length;
+        if cumulative * 100 >= total_length * percent {
+            return length;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assembly_stats() {
+        // Sorted: 80, 70, 50, 40, 30, 20, 10 (total 300)
+        // Cumulative: 80, 150 (>= 150 -> N50 = 70), 200, 240, 270 (>= 270 -> N90 = 30)
+        let stats = assembly_stats(&[20, 80, 10, 50, 70, 30, 40]);
+
+        assert_eq!(stats.count, 7);
+        assert_eq!(stats.total_length, 300);
+        assert_eq!((stats.min, stats.max), (10, 80));
+        assert!((stats.mean - 300.0 / 7.0).abs() < 1e-12);
+        assert_eq!(stats.n50, 70);
+        assert_eq!(stats.n90, 30);
+    }
+
+    #[test]
+    fn test_empty_assembly() {
+        assert_eq!(assembly_stats(&[]), AssemblyStats::default());
+        assert_eq!(assembly_stats(&[0, 0]).count, 2);
+        assert_eq!(assembly_stats(&[0, 0]).n50, 0);
+    }
+}
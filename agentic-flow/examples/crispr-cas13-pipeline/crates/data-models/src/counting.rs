@@ -0,0 +1,278 @@
+//! Alignment-free guide counting for screen readouts
+//!
+//! [`GuideCounter`] assigns each read to the guide whose spacer it starts
+//! with. Matching is tried in order of cost: an exact hash lookup, then
+//! Hamming distance within `max_mismatches`, then, only when enabled with
+//! [`GuideCounter::with_indel_tolerance`], a banded edit distance that also
+//! absorbs insertions and deletions. Reads whose best match ties between
+//! guides are left unassigned.
+
+use crate::error::{DataModelError, Result};
+use crate::guides::{normalize, GuideRna};
+use crate::sequencing::FastqRecord;
+use crate::targets::mismatch_count;
+use std::collections::HashMap;
+
+/// Per-guide read counts from spacer matching
+#[derive(Debug, Clone)]
+pub struct GuideCounter {
+    ids: Vec<String>,
+    spacers: Vec<Vec<u8>>,
+    exact: HashMap<Vec<u8>, usize>,
+    /// Distinct spacer lengths, for the exact lookup
+    lengths: Vec<usize>,
+    max_mismatches: usize,
+    max_edits: Option<usize>,
+    counts: Vec<u64>,
+    unmatched: u64,
+}
+
+impl GuideCounter {
+    /// Build a counter over a guide library
+    ///
+    /// Spacers are compared case-insensitively with `U` read as `T`. Guides
+    /// must have non-empty, unique spacers.
+    pub fn new(guides: &[GuideRna], max_mismatches: usize) -> Result<Self> {
+        let spacers: Vec<Vec<u8>> = guides.iter().map(|g| normalize(&g.spacer)).collect();
+        let mut exact = HashMap::new();
+        for (i, spacer) in spacers.iter().enumerate() {
+            if spacer.is_empty() {
+                return Err(DataModelError::ValidationError(format!(
+                    "guide '{}' has an empty spacer",
+                    guides[i].id
+                )));
+            }
+            if let Some(previous) = exact.insert(spacer.clone(), i) {
+                return Err(DataModelError::ValidationError(format!(
+                    "guides '{}' and '{}' share a spacer",
+                    guides[previous].id, guides[i].id
+                )));
+            }
+        }
+
+        let mut lengths: Vec<usize> = spacers.iter().map(Vec::len).collect();
+        lengths.sort_unstable();
+        lengths.dedup();
+
+        Ok(Self {
+            ids: guides.iter().map(|g| g.id.clone()).collect(),
+            counts: vec![0; spacers.len()],
+            spacers,
+            exact,
+            lengths,
+            max_mismatches,
+            max_edits: None,
+            unmatched: 0,
+        })
+    }
+
+    /// Fall back to an edit-distance match allowing up to `max_edits`
+    /// substitutions, insertions and deletions when Hamming matching fails
+    pub fn with_indel_tolerance(mut self, max_edits: usize) -> Self {
+        self.max_edits = Some(max_edits);
+        self
+    }
+
+    /// Find the guide a read sequence starts with, by index into the library
+    pub fn match_sequence(&self, sequence: &str) -> Option<usize> {
+        let read = normalize(sequence);
+
+        for &len in &self.lengths {
+            if let Some(&guide) = read.get(..len).and_then(|prefix| self.exact.get(prefix)) {
+                return Some(guide);
+            }
+        }
+
+        let hamming = best_unique(self.spacers.iter().map(|spacer| {
+            read.get(..spacer.len())
+                .map(|prefix| mismatch_count(spacer, prefix))
+                .filter(|&d| d <= self.max_mismatches)
+        }));
+        if hamming.is_some() {
+            return hamming;
+        }
+
+        let max_edits = self.max_edits?;
+        best_unique(
+            self.spacers
+                .iter()
+                .map(|spacer| prefix_edit_distance(spacer, &read, max_edits)),
+        )
+    }
+
+    /// Count a read, returning the index of the guide it was assigned to
+    pub fn add_read(&mut self, read: &FastqRecord) -> Option<usize> {
+        let guide = self.match_sequence(read.sequence());
+        match guide {
+            Some(i) => self.counts[i] += 1,
+            None => self.unmatched += 1,
+        }
+        guide
+    }
+
+    /// Count every read
+    pub fn add_reads<'a, I>(&mut self, reads: I)
+    where
+        I: IntoIterator<Item = &'a FastqRecord>,
+    {
+        for read in reads {
+            self.add_read(read);
+        }
+    }
+
+    /// Get the number of reads assigned to a guide, or `None` for an unknown id
+    pub fn count(&self, guide_id: &str) -> Option<u64> {
+        let i = self.ids.iter().position(|id| id == guide_id)?;
+        Some(self.counts[i])
+    }
+
+    /// Get `(guide id, count)` pairs in library order
+    pub fn counts(&self) -> impl Iterator<Item = (&str, u64)> + '_ {
+        self.ids
+            .iter()
+            .map(String::as_str)
+            .zip(self.counts.iter().copied())
+    }
+
+    /// Get the number of reads assigned to no guide
+    pub fn unmatched(&self) -> u64 {
+        self.unmatched
+    }
+}
+
+/// Get the index of the unique smallest distance, if there is one
+fn best_unique<I>(distances: I) -> Option<usize>
+where
+    I: IntoIterator<Item = Option<usize>>,
+{
+    let mut best: Option<(usize, usize)> = None;
+    let mut tied = false;
+    for (i, distance) in distances.into_iter().enumerate() {
+        let Some(distance) = distance else { continue };
+        match best {
+            Some((_, d)) if distance > d => {}
+            Some((_, d)) if distance == d => tied = true,
+            _ => {
+                best = Some((i, distance));
+                tied = false;
+            }
+        }
+    }
+    best.filter(|_| !tied).map(|(i, _)| i)
+}
+
+/// Get the smallest edit distance between `spacer` and any prefix of `read`,
+/// if it is at most `band`
+///
+/// Only alignments staying within `band` of the diagonal are explored, so the
+/// cost is O(spacer length × band).
+fn prefix_edit_distance(spacer: &[u8], read: &[u8], band: usize) -> Option<usize> {
+    let width = read.len().min(spacer.len() + band);
+    let far = band + 1;
+    let mut previous: Vec<usize> = (0..=width).map(|j| j.min(far)).collect();
+    let mut current = vec![far; width + 1];
+
+    for (i, &base) in spacer.iter().enumerate() {
+        let row = i + 1;
+        current.fill(far);
+        if row <= band {
+            current[0] = row;
+        }
+        let from = row.saturating_sub(band).max(1);
+        let to = (row + band).min(width);
+        for j in from..=to {
+            let substitution = previous[j - 1] + usize::from(read[j - 1] != base);
+            current[j] = substitution
+                .min(previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(far);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    let from = spacer.len().saturating_sub(band);
+    previous
+        .get(from..)?
+        .iter()
+        .copied()
+        .min()
+        .filter(|&d| d <= band)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACER: &str = "ACGUACGGUUCAGCUAGCUA";
+
+    fn read(sequence: &str) -> FastqRecord {
+        FastqRecord::new(
+            "r".to_string(),
+            sequence.to_string(),
+            vec![b'I'; sequence.len()],
+        )
+        .unwrap()
+    }
+
+    fn library() -> Vec<GuideRna> {
+        vec![
+            GuideRna::new("g1".to_string(), SPACER.to_string()),
+            GuideRna::new("g2".to_string(), "GGGCCCAAATTTGGGCCCAA".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_exact_and_hamming_counts() {
+        let mut counter = GuideCounter::new(&library(), 1).unwrap();
+        counter.add_reads(&[
+            read("ACGTACGGTTCAGCTAGCTAGGTT"),
+            read("ACGTACGGTTCAGCTAGCTTGGTT"),
+            read("GGGCCCAAATTTGGGCCCAA"),
+            read("TTTTTTTTTTTTTTTTTTTTTT"),
+        ]);
+
+        assert_eq!(counter.count("g1"), Some(2));
+        assert_eq!(counter.count("g2"), Some(1));
+        assert_eq!(counter.count("missing"), None);
+        assert_eq!(counter.unmatched(), 1);
+        assert_eq!(
+            counter.counts().collect::<Vec<_>>(),
+            vec![("g1", 2), ("g2", 1)]
+        );
+    }
+
+    #[test]
+    fn test_single_insertion_needs_indel_mode() {
+        // An extra T after the eighth base shifts the rest of the spacer
+        let inserted = read("ACGTACGGTTTCAGCTAGCTAGGTT");
+
+        let mut exact = GuideCounter::new(&library(), 1).unwrap();
+        assert_eq!(exact.add_read(&inserted), None);
+        assert_eq!(exact.count("g1"), Some(0));
+
+        let mut tolerant = GuideCounter::new(&library(), 1)
+            .unwrap()
+            .with_indel_tolerance(1);
+        assert_eq!(tolerant.add_read(&inserted), Some(0));
+        assert_eq!(tolerant.count("g1"), Some(1));
+
+        let deleted = read("ACGTACGGTCAGCTAGCTAGGTT");
+        assert_eq!(tolerant.match_sequence(deleted.sequence()), Some(0));
+    }
+
+    #[test]
+    fn test_prefix_edit_distance() {
+        assert_eq!(prefix_edit_distance(b"ACGT", b"ACGTTT", 1), Some(0));
+        assert_eq!(prefix_edit_distance(b"ACGT", b"AGGT", 1), Some(1));
+        assert_eq!(prefix_edit_distance(b"ACGT", b"ACCGT", 1), Some(1));
+        assert_eq!(prefix_edit_distance(b"ACGT", b"AGT", 1), Some(1));
+        assert_eq!(prefix_edit_distance(b"ACGT", b"TTTT", 2), None);
+    }
+
+    #[test]
+    fn test_duplicate_spacers_rejected() {
+        let mut guides = library();
+        guides.push(GuideRna::new("g3".to_string(), SPACER.to_lowercase()));
+        assert!(GuideCounter::new(&guides, 0).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! Per-position read coverage over a transcript
+
+use serde::{Deserialize, Serialize};
+
+/// Strand-specific read depth at every position of a transcript
+///
+/// Alignments are given as a 0-based start, a length and a strand
+/// (`true` = forward). Bases past the end of the transcript are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverageTrack {
+    forward: Vec<u32>,
+    reverse: Vec<u32>,
+}
+
+impl CoverageTrack {
+    /// Create an empty track for a transcript of `len` bases
+    pub fn new(len: usize) -> Self {
+        Self {
+            forward: vec![0; len],
+            reverse: vec![0; len],
+        }
+    }
+
+    /// Build a track from `(start, len, strand)` alignments
+    pub fn from_alignments<I>(len: usize, alignments: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize, bool)>,
+    {
+        let mut track = Self::new(len);
+        for (start, aligned_len, strand) in alignments {
+            track.add(start, aligned_len, strand);
+        }
+        track
+    }
+
+    /// Add one alignment, clamped to the transcript
+    pub fn add(&mut self, start: usize, len: usize, strand: bool) {
+        let depth = if strand {
+            &mut self.forward
+        } else {
+            &mut self.reverse
+        };
+        let end = start.saturating_add(len).min(depth.len());
+        for count in depth.iter_mut().take(end).skip(start) {
+            *count = count.saturating_add(1);
+        }
+    }
+
+    /// Get the transcript length
+    pub fn len(&self) -> usize {
+        self.forward.len()
+    }
+
+    /// Check if the transcript is empty
+    pub fn is_empty(&self) -> bool {
+        self.forward.is_empty()
+    }
+
+    /// Get the forward-strand depth at each position
+    pub fn forward(&self) -> &[u32] {
+        &self.forward
+    }
+
+    /// Get the reverse-strand depth at each position
+    pub fn reverse(&self) -> &[u32] {
+        &self.reverse
+    }
+
+    /// Get the depth on both strands at a position, or 0 past the end
+    pub fn total(&self, pos: usize) -> u32 {
+        match (self.forward.get(pos), self.reverse.get(pos)) {
+            (Some(f), Some(r)) => f.saturating_add(*r),
+            _ => 0,
+        }
+    }
+
+    /// Get the depth on both strands at each position
+    pub fn totals(&self) -> Vec<u32> {
+        (0..self.len()).map(|pos| self.total(pos)).collect()
+    }
+
+    /// Get the highest depth on both strands combined, or 0 when empty
+    pub fn max(&self) -> u32 {
+        (0..self.len())
+            .map(|pos| self.total(pos))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strand_coverage() {
+        let track = CoverageTrack::from_alignments(
+            8,
+            [(0, 4, true), (2, 4, true), (3, 3, false), (6, 5, false)],
+        );
+
+        assert_eq!(track.len(), 8);
+        assert_eq!(track.forward(), [1, 1, 2, 2, 1, 1, 0, 0]);
+        assert_eq!(track.reverse(), [0, 0, 0, 1, 1, 1, 1, 1]);
+        assert_eq!(track.totals(), vec![1, 1, 2, 3, 2, 2, 1, 1]);
+        assert_eq!(track.total(3), 3);
+        assert_eq!(track.total(8), 0);
+        assert_eq!(track.max(), 3);
+    }
+
+    #[test]
+    fn test_alignment_past_end() {
+        let mut track = CoverageTrack::new(3);
+        track.add(10, 5, true);
+        track.add(1, usize::MAX, false);
+
+        assert_eq!(track.forward(), [0, 0, 0]);
+        assert_eq!(track.reverse(), [0, 1, 1]);
+        assert_eq!(CoverageTrack::new(0).max(), 0);
+    }
+}
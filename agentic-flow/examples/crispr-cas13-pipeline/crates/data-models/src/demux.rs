@@ -0,0 +1,513 @@
+//! Sample demultiplexing by index barcode
+//!
+//! [`BarcodeMatcher`] precomputes every sequence within `max_mismatches`
+//! substitutions of each sample barcode, so matching an observed index is a
+//! single hash lookup. When an observed index is equally close to two or more
+//! barcodes the match is reported as ambiguous instead of guessing.
+//!
+//! [`TenXReader`] handles single-cell libraries, where the cell barcode and
+//! UMI are read from fixed positions of R1 instead of an index read.
+
+use crate::error::{DataModelError, Result};
+use crate::reads::template_name;
+use crate::sequencing::FastqRecord;
+use crate::targets::mismatch_count;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Bases substituted when enumerating barcode neighbours
+const BASES: [u8; 5] = [b'A', b'C', b'G', b'T', b'N'];
+
+/// Outcome of matching an observed index against the barcode set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BarcodeMatch {
+    /// Unique closest barcode
+    Matched { sample: String, distance: usize },
+    /// Two or more barcodes tie at the minimum distance
+    Ambiguous {
+        samples: Vec<String>,
+        distance: usize,
+    },
+    /// No barcode within the mismatch budget
+    Unmatched,
+}
+
+/// Reads routed to samples by their index barcode
+#[derive(Debug, Clone, Default)]
+pub struct DemuxResult {
+    /// Reads assigned to each sample
+    pub assigned: HashMap<String, Vec<FastqRecord>>,
+    /// Reads whose index tied between several samples
+    pub ambiguous: Vec<FastqRecord>,
+    /// Reads whose index matched no sample
+    pub unmatched: Vec<FastqRecord>,
+}
+
+/// Closest samples found so far for a neighbour sequence
+#[derive(Debug, Clone)]
+struct Hit {
+    distance: usize,
+    samples: Vec<usize>,
+}
+
+/// Precomputed barcode lookup tolerant of index mismatches
+#[derive(Debug, Clone)]
+pub struct BarcodeMatcher {
+    samples: Vec<String>,
+    barcode_len: usize,
+    max_mismatches: usize,
+    min_barcode_distance: usize,
+    lookup: HashMap<Vec<u8>, Hit>,
+}
+
+impl BarcodeMatcher {
+    /// Build a matcher from `(sample, barcode)` pairs
+    ///
+    /// All barcodes must have the same length and be unique.
+    pub fn new(barcodes: &[(&str, &str)], max_mismatches: usize) -> Result<Self> {
+        let barcode_len = barcodes.first().map(|(_, b)| b.len()).unwrap_or(0);
+        let normalized: Vec<Vec<u8>> = barcodes
+            .iter()
+            .map(|(_, b)| b.to_ascii_uppercase().into_bytes())
+            .collect();
+
+        if let Some((sample, barcode)) = barcodes.iter().find(|(_, b)| b.len() != barcode_len) {
+            return Err(DataModelError::ValidationError(format!(
+                "barcode '{}' for sample '{}' has length {}, expected {}",
+                barcode,
+                sample,
+                barcode.len(),
+                barcode_len
+            )));
+        }
+
+        let mut min_barcode_distance = usize::MAX;
+        for (i, a) in normalized.iter().enumerate() {
+            for b in &normalized[i + 1..] {
+                let distance = mismatch_count(a, b);
+                if distance == 0 {
+                    return Err(DataModelError::ValidationError(format!(
+                        "duplicate barcode '{}'",
+                        String::from_utf8_lossy(a)
+                    )));
+                }
+                min_barcode_distance = min_barcode_distance.min(distance);
+            }
+        }
+
+        let mut lookup: HashMap<Vec<u8>, Hit> = HashMap::new();
+        for (sample, barcode) in normalized.iter().enumerate() {
+            let mut neighbours = Vec::new();
+            collect_neighbours(
+                barcode,
+                0,
+                max_mismatches,
+                &mut barcode.clone(),
+                &mut neighbours,
+            );
+            for neighbour in neighbours {
+                let distance = mismatch_count(barcode, &neighbour);
+                let hit = lookup.entry(neighbour).or_insert(Hit {
+                    distance,
+                    samples: Vec::new(),
+                });
+                if distance < hit.distance {
+                    hit.distance = distance;
+                    hit.samples.clear();
+                }
+                if distance == hit.distance && !hit.samples.contains(&sample) {
+                    hit.samples.push(sample);
+                }
+            }
+        }
+
+        Ok(Self {
+            samples: barcodes.iter().map(|(s, _)| s.to_string()).collect(),
+            barcode_len,
+            max_mismatches,
+            min_barcode_distance,
+            lookup,
+        })
+    }
+
+    /// Get the mismatch budget used when matching
+    pub fn max_mismatches(&self) -> usize {
+        self.max_mismatches
+    }
+
+    /// Get the smallest Hamming distance between any two barcodes
+    ///
+    /// Returns `usize::MAX` for fewer than two barcodes.
+    pub fn min_barcode_distance(&self) -> usize {
+        self.min_barcode_distance
+    }
+
+    /// Check if ties are impossible under the current mismatch budget
+    ///
+    /// An observed index can only be equidistant to two barcodes when they are
+    /// at most `2 * max_mismatches` apart.
+    pub fn is_collision_free(&self) -> bool {
+        self.min_barcode_distance > 2 * self.max_mismatches
+    }
+
+    /// Match an observed index sequence
+    pub fn match_index(&self, index: &str) -> BarcodeMatch {
+        if index.len() != self.barcode_len {
+            return BarcodeMatch::Unmatched;
+        }
+
+        match self.lookup.get(index.to_ascii_uppercase().as_bytes()) {
+            Some(hit) if hit.samples.len() == 1 => BarcodeMatch::Matched {
+                sample: self.samples[hit.samples[0]].clone(),
+                distance: hit.distance,
+            },
+            Some(hit) => BarcodeMatch::Ambiguous {
+                samples: hit
+                    .samples
+                    .iter()
+                    .map(|&s| self.samples[s].clone())
+                    .collect(),
+                distance: hit.distance,
+            },
+            None => BarcodeMatch::Unmatched,
+        }
+    }
+
+    /// Route `(index, read)` pairs into per-sample, ambiguous and unmatched buckets
+    pub fn demultiplex<I>(&self, reads: I) -> DemuxResult
+    where
+        I: IntoIterator<Item = (String, FastqRecord)>,
+    {
+        let mut result = DemuxResult::default();
+        for (index, read) in reads {
+            match self.match_index(&index) {
+                BarcodeMatch::Matched { sample, .. } => {
+                    result.assigned.entry(sample).or_default().push(read)
+                }
+                BarcodeMatch::Ambiguous { .. } => result.ambiguous.push(read),
+                BarcodeMatch::Unmatched => result.unmatched.push(read),
+            }
+        }
+        result
+    }
+}
+
+/// Fields of an Illumina (CASAVA 1.8+) read header
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadHeaderInfo {
+    /// Instrument id
+    pub instrument: String,
+    /// Run number on the instrument
+    pub run: u32,
+    /// Flowcell id
+    pub flowcell: String,
+    /// Flowcell lane
+    pub lane: u32,
+    /// Tile within the lane
+    pub tile: u32,
+    /// X coordinate of the cluster within the tile
+    pub x: u32,
+    /// Y coordinate of the cluster within the tile
+    pub y: u32,
+    /// Member of the pair (1 or 2), or index read number
+    pub read: u8,
+    /// Whether the read failed the chastity filter (`Y`)
+    pub filtered: bool,
+    /// Control bits, 0 when none are set
+    pub control: u32,
+    /// Index sequence, `i7+i5` for dual indexes; may be empty or a sample
+    /// number depending on the instrument software
+    pub index: String,
+}
+
+/// Parse an Illumina read header
+///
+/// Expects the CASAVA 1.8 layout,
+/// `@instrument:run:flowcell:lane:tile:x:y read:filtered:control:index`, with
+/// or without the leading `@`. Returns `None` for headers in any other shape,
+/// including older `/1`-suffixed ones.
+pub fn parse_illumina_header(header: &str) -> Option<ReadHeaderInfo> {
+    let header = header.strip_prefix('@').unwrap_or(header);
+    let (id, description) = header.split_once(' ')?;
+
+    let id: Vec<&str> = id.split(':').collect();
+    let [instrument, run, flowcell, lane, tile, x, y] = id.as_slice() else {
+        return None;
+    };
+    let description: Vec<&str> = description.trim().split(':').collect();
+    let [read, filtered, control, index] = description.as_slice() else {
+        return None;
+    };
+    let filtered = match *filtered {
+        "Y" => true,
+        "N" => false,
+        _ => return None,
+    };
+    if instrument.is_empty() || flowcell.is_empty() {
+        return None;
+    }
+
+    Some(ReadHeaderInfo {
+        instrument: instrument.to_string(),
+        run: run.parse().ok()?,
+        flowcell: flowcell.to_string(),
+        lane: lane.parse().ok()?,
+        tile: tile.parse().ok()?,
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+        read: read.parse().ok()?,
+        filtered,
+        control: control.parse().ok()?,
+        index: index.to_string(),
+    })
+}
+
+/// Cell barcode, UMI and cDNA read extracted from a single-cell read pair
+pub type TenXRead = (String, String, FastqRecord);
+
+/// Extractor for 10x Chromium-style single-cell read pairs
+///
+/// R1 carries the cell barcode and the UMI at fixed offsets and R2 carries
+/// the cDNA. Pairs whose R1 is too short to hold both, or whose mates have
+/// different ids, are counted as malformed and skipped rather than failing
+/// the whole run.
+#[derive(Debug, Clone)]
+pub struct TenXReader {
+    barcode_offset: usize,
+    barcode_len: usize,
+    umi_offset: usize,
+    umi_len: usize,
+    malformed: u64,
+}
+
+impl TenXReader {
+    /// Create a reader for a barcode and a UMI at the given R1 offsets
+    ///
+    /// Both lengths must be non-zero and the two ranges must not overlap.
+    pub fn new(
+        barcode_offset: usize,
+        barcode_len: usize,
+        umi_offset: usize,
+        umi_len: usize,
+    ) -> Result<Self> {
+        if barcode_len == 0 || umi_len == 0 {
+            return Err(DataModelError::ValidationError(
+                "cell barcode and UMI lengths must be non-zero".to_string(),
+            ));
+        }
+        if barcode_offset < umi_offset + umi_len && umi_offset < barcode_offset + barcode_len {
+            return Err(DataModelError::ValidationError(format!(
+                "cell barcode at {}..{} overlaps UMI at {}..{}",
+                barcode_offset,
+                barcode_offset + barcode_len,
+                umi_offset,
+                umi_offset + umi_len
+            )));
+        }
+
+        Ok(Self {
+            barcode_offset,
+            barcode_len,
+            umi_offset,
+            umi_len,
+            malformed: 0,
+        })
+    }
+
+    /// Create a reader for the Chromium v3 layout: a 16 nt barcode followed
+    /// by a 12 nt UMI
+    pub fn chromium_v3() -> Self {
+        Self::new(0, 16, 16, 12).expect("valid Chromium v3 layout")
+    }
+
+    /// Get the shortest R1 that holds both the barcode and the UMI
+    pub fn min_r1_len(&self) -> usize {
+        (self.barcode_offset + self.barcode_len).max(self.umi_offset + self.umi_len)
+    }
+
+    /// Get the number of read pairs rejected so far
+    pub fn malformed_count(&self) -> u64 {
+        self.malformed
+    }
+
+    /// Extract the upper-cased cell barcode and UMI from `r1` and pair them
+    /// with `r2`
+    ///
+    /// Mate ids are compared without a trailing `/1` or `/2`. Returns `None`
+    /// and increments [`TenXReader::malformed_count`] for a malformed pair.
+    pub fn extract(&mut self, r1: &FastqRecord, r2: FastqRecord) -> Option<TenXRead> {
+        let sequence = r1.sequence();
+        if sequence.len() < self.min_r1_len() || template_name(&r1.id) != template_name(&r2.id) {
+            self.malformed += 1;
+            return None;
+        }
+
+        let slice = |offset: usize, len: usize| sequence[offset..offset + len].to_ascii_uppercase();
+        Some((
+            slice(self.barcode_offset, self.barcode_len),
+            slice(self.umi_offset, self.umi_len),
+            r2,
+        ))
+    }
+
+    /// Extract every well-formed pair, counting the rest as malformed
+    pub fn extract_pairs<'a, I>(&'a mut self, pairs: I) -> impl Iterator<Item = TenXRead> + 'a
+    where
+        I: IntoIterator<Item = (FastqRecord, FastqRecord)>,
+        I::IntoIter: 'a,
+    {
+        pairs
+            .into_iter()
+            .filter_map(move |(r1, r2)| self.extract(&r1, r2))
+    }
+}
+
+/// Enumerate all sequences within `budget` substitutions of `original`
+fn collect_neighbours(
+    original: &[u8],
+    from: usize,
+    budget: usize,
+    current: &mut Vec<u8>,
+    out: &mut Vec<Vec<u8>>,
+) {
+    out.push(current.clone());
+    if budget == 0 {
+        return;
+    }
+    for pos in from..original.len() {
+        for &base in BASES.iter().filter(|&&b| b != original[pos]) {
+            current[pos] = base;
+            collect_neighbours(original, pos + 1, budget - 1, current, out);
+        }
+        current[pos] = original[pos];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(id: &str) -> FastqRecord {
+        FastqRecord::new(id.to_string(), "ACGT".to_string(), vec![b'I'; 4]).unwrap()
+    }
+
+    #[test]
+    fn test_tenx_extracts_barcode_and_umi() {
+        let mut reader = TenXReader::new(0, 6, 6, 4).unwrap();
+        assert_eq!(reader.min_r1_len(), 10);
+
+        let r1 = FastqRecord::new(
+            "c1/1".to_string(),
+            "acgtacGGCCTT".to_string(),
+            vec![b'I'; 12],
+        )
+        .unwrap();
+        let (barcode, umi, cdna) = reader.extract(&r1, read("c1/2")).unwrap();
+        assert_eq!((barcode.as_str(), umi.as_str()), ("ACGTAC", "GGCC"));
+        assert_eq!(cdna.id, "c1/2");
+        assert_eq!(reader.malformed_count(), 0);
+    }
+
+    #[test]
+    fn test_tenx_rejects_malformed_pairs() {
+        let mut reader = TenXReader::chromium_v3();
+        let short_r1 = FastqRecord::new("c1".to_string(), "A".repeat(27), vec![b'I'; 27]).unwrap();
+        let full_r1 = FastqRecord::new("c2".to_string(), "A".repeat(28), vec![b'I'; 28]).unwrap();
+        let pairs = vec![
+            (short_r1, read("c1")),
+            (full_r1.clone(), read("other")),
+            (full_r1, read("c2")),
+        ];
+
+        let extracted: Vec<TenXRead> = reader.extract_pairs(pairs).collect();
+        assert_eq!(extracted.len(), 1);
+        assert_eq!(extracted[0].2.id, "c2");
+        assert_eq!(reader.malformed_count(), 2);
+
+        assert!(TenXReader::new(0, 16, 10, 12).is_err());
+        assert!(TenXReader::new(0, 16, 16, 0).is_err());
+    }
+
+    #[test]
+    fn test_unambiguous_single_mismatch() {
+        let matcher = BarcodeMatcher::new(&[("s1", "ACGTACGT"), ("s2", "TTTTGGGG")], 1).unwrap();
+
+        assert_eq!(
+            matcher.match_index("ACGTACGA"),
+            BarcodeMatch::Matched {
+                sample: "s1".to_string(),
+                distance: 1
+            }
+        );
+        assert_eq!(matcher.match_index("CCCCCCCC"), BarcodeMatch::Unmatched);
+        assert!(matcher.is_collision_free());
+    }
+
+    #[test]
+    fn test_equidistant_barcodes_are_ambiguous() {
+        let matcher = BarcodeMatcher::new(&[("s1", "AAAA"), ("s2", "AATT")], 1).unwrap();
+        assert_eq!(matcher.min_barcode_distance(), 2);
+        assert!(!matcher.is_collision_free());
+
+        match matcher.match_index("AAAT") {
+            BarcodeMatch::Ambiguous { samples, distance } => {
+                assert_eq!(distance, 1);
+                assert_eq!(samples, vec!["s1".to_string(), "s2".to_string()]);
+            }
+            other => panic!("expected ambiguous match, got {:?}", other),
+        }
+
+        let result = matcher.demultiplex(vec![
+            ("AAAA".to_string(), read("r1")),
+            ("AAAT".to_string(), read("r2")),
+            ("GGGG".to_string(), read("r3")),
+        ]);
+        assert_eq!(result.assigned["s1"].len(), 1);
+        assert_eq!(result.ambiguous[0].id, "r2");
+        assert_eq!(result.unmatched[0].id, "r3");
+    }
+
+    #[test]
+    fn test_invalid_barcode_sets() {
+        assert!(BarcodeMatcher::new(&[("s1", "AAAA"), ("s2", "AAA")], 1).is_err());
+        assert!(BarcodeMatcher::new(&[("s1", "AAAA"), ("s2", "aaaa")], 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_illumina_header() {
+        let info =
+            parse_illumina_header("@EAS139:136:FC706VJ:2:2104:15343:197393 1:Y:18:ATCACG+TTAGGC")
+                .unwrap();
+
+        assert_eq!(info.instrument, "EAS139");
+        assert_eq!(info.run, 136);
+        assert_eq!(info.flowcell, "FC706VJ");
+        assert_eq!((info.lane, info.tile), (2, 2104));
+        assert_eq!((info.x, info.y), (15343, 197393));
+        assert_eq!(info.read, 1);
+        assert!(info.filtered);
+        assert_eq!(info.control, 18);
+        assert_eq!(info.index, "ATCACG+TTAGGC");
+
+        let unprefixed = parse_illumina_header("M001:7:000-ABC:1:1101:10:20 2:N:0:1").unwrap();
+        assert_eq!((unprefixed.read, unprefixed.filtered), (2, false));
+        assert_eq!(unprefixed.index, "1");
+    }
+
+    #[test]
+    fn test_parse_non_illumina_header() {
+        assert_eq!(
+            parse_illumina_header("@HWUSI-EAS100R:6:73:941:1973#0/1"),
+            None
+        );
+        assert_eq!(parse_illumina_header("@read_42 sample=A"), None);
+        assert_eq!(
+            parse_illumina_header("@EAS139:136:FC706VJ:2:2104:15343:197393 1:X:18:ATCACG"),
+            None
+        );
+        assert_eq!(
+            parse_illumina_header("@EAS139:136:FC706VJ:2:tile:15343:197393 1:N:0:ATCACG"),
+            None
+        );
+    }
+}
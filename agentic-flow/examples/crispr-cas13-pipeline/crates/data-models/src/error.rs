@@ -28,4 +28,123 @@ pub enum DataModelError {
 
     #[error("Parse error: {0}")]
     ParseError(String),
+
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+impl From<std::num::ParseIntError> for DataModelError {
+    fn from(err: std::num::ParseIntError) -> Self {
+        DataModelError::ParseError(err.to_string())
+    }
+}
+
+impl From<std::num::ParseFloatError> for DataModelError {
+    fn from(err: std::num::ParseFloatError) -> Self {
+        DataModelError::ParseError(err.to_string())
+    }
+}
+
+impl From<std::str::Utf8Error> for DataModelError {
+    fn from(err: std::str::Utf8Error) -> Self {
+        DataModelError::ParseError(err.to_string())
+    }
+}
+
+impl From<std::string::FromUtf8Error> for DataModelError {
+    fn from(err: std::string::FromUtf8Error) -> Self {
+        DataModelError::ParseError(err.to_string())
+    }
+}
+
+/// How multi-record parsers react to a malformed record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorMode {
+    /// Stop at the first malformed record
+    #[default]
+    FailFast,
+    /// Skip malformed records and collect their errors in a [`ParseReport`]
+    CollectAll,
+}
+
+/// Errors collected while parsing a multi-record file
+///
+/// Each entry pairs the 1-based line number where the offending record starts
+/// with the error it produced.
+#[derive(Debug, Default)]
+pub struct ParseReport {
+    errors: Vec<(usize, DataModelError)>,
+}
+
+impl ParseReport {
+    /// Create an empty report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an error for the record starting at `line_number`
+    pub fn push(&mut self, line_number: usize, error: DataModelError) {
+        self.errors.push((line_number, error));
+    }
+
+    /// Get the collected `(line_number, error)` entries in file order
+    pub fn errors(&self) -> &[(usize, DataModelError)] {
+        &self.errors
+    }
+
+    /// Get the line numbers of all malformed records
+    pub fn line_numbers(&self) -> Vec<usize> {
+        self.errors.iter().map(|(line, _)| *line).collect()
+    }
+
+    /// Get the number of collected errors
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Check if no errors were collected
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Succeed only if no errors were collected
+    ///
+    /// Otherwise all errors are joined into a single
+    /// [`DataModelError::ParseError`].
+    pub fn into_result(self) -> Result<()> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        let messages: Vec<String> = self
+            .errors
+            .iter()
+            .map(|(line, err)| format!("line {}: {}", line, err))
+            .collect();
+        Err(DataModelError::ParseError(messages.join("; ")))
+    }
+
+    /// Handle a malformed record according to `mode`
+    ///
+    /// Returns the error with its line number for [`ErrorMode::FailFast`],
+    /// otherwise records it and returns `Ok`.
+    pub(crate) fn handle(
+        &mut self,
+        mode: ErrorMode,
+        line_number: usize,
+        error: DataModelError,
+    ) -> Result<()> {
+        match mode {
+            ErrorMode::FailFast => Err(DataModelError::ParseError(format!(
+                "line {}: {}",
+                line_number, error
+            ))),
+            ErrorMode::CollectAll => {
+                self.push(line_number, error);
+                Ok(())
+            }
+        }
+    }
 }
@@ -1,8 +1,12 @@
 //! Gene expression analysis data models
 
-use crate::error::DataModelError;
+use crate::error::{DataModelError, Result};
+use crate::tables::{TableWriter, TsvWriter};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
 use uuid::Uuid;
 
 /// Represents gene expression data for a single sample
@@ -72,6 +76,949 @@ pub struct SampleMetadata {
     pub custom: HashMap<String, String>,
 }
 
+/// Gene identifier used to label expression matrix rows
+pub type GeneId = String;
+
+/// Descriptive attributes of a gene from an annotation table such as a GTF
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeneAnnotation {
+    /// Gene symbol, e.g. `TP53`
+    pub symbol: Option<String>,
+    /// Gene biotype, e.g. `protein_coding`
+    pub biotype: Option<String>,
+    /// Chromosome or contig name
+    pub chromosome: Option<String>,
+}
+
+/// Annotation columns written by [`ExpressionMatrix::write_annotated_tsv`]
+pub const GENE_ANNOTATION_COLUMNS: [&str; 3] = ["symbol", "biotype", "chromosome"];
+
+/// Dense gene × sample expression matrix
+///
+/// Values are stored row-major, one row per gene and one column per sample.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpressionMatrix {
+    /// Gene identifiers, one per row
+    genes: Vec<GeneId>,
+    /// Sample names, one per column
+    samples: Vec<String>,
+    /// Values in row-major order
+    values: Vec<f64>,
+    /// Per-gene annotations keyed by column name, one map per row, or empty
+    /// when the matrix carries no annotations
+    #[serde(default)]
+    annotations: Vec<HashMap<String, String>>,
+}
+
+/// Layout of a delimited count matrix file
+///
+/// The header row names the gene id column, then `metadata_columns` gene
+/// annotation columns, then one column per sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CountMatrixFormat {
+    /// Field separator; `None` uses a tab if the header contains one and a
+    /// comma otherwise
+    pub delimiter: Option<char>,
+    /// Number of annotation columns between the gene id and the samples
+    pub metadata_columns: usize,
+}
+
+impl CountMatrixFormat {
+    /// featureCounts output: tab-separated with Chr, Start, End, Strand and
+    /// Length columns before the samples
+    pub fn feature_counts() -> Self {
+        Self {
+            delimiter: Some('\t'),
+            metadata_columns: 5,
+        }
+    }
+}
+
+/// How technical replicates are combined into one column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CollapseMethod {
+    /// Sum replicate values
+    Sum,
+    /// Average replicate values
+    Mean,
+}
+
+/// Correlation measure used to compare samples across genes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CorrelationMethod {
+    /// Pearson correlation of the values
+    #[default]
+    Pearson,
+    /// Spearman rank correlation: Pearson correlation of the values' ranks,
+    /// with tied values sharing their average rank
+    Spearman,
+}
+
+impl ExpressionMatrix {
+    /// Create a matrix from gene ids, sample names and row-major values
+    pub fn new(genes: Vec<GeneId>, samples: Vec<String>, values: Vec<f64>) -> Result<Self> {
+        if values.len() != genes.len() * samples.len() {
+            return Err(DataModelError::ValidationError(format!(
+                "expected {} values for {} genes x {} samples, found {}",
+                genes.len() * samples.len(),
+                genes.len(),
+                samples.len(),
+                values.len()
+            )));
+        }
+        Ok(Self {
+            genes,
+            samples,
+            values,
+            annotations: Vec::new(),
+        })
+    }
+
+    /// Read a delimited count matrix with one row per gene
+    ///
+    /// Leading `#` comment lines (such as the featureCounts command line) and
+    /// blank lines are skipped. The first remaining line is the header.
+    /// Annotation columns described by `format` are kept per gene and
+    /// available from [`ExpressionMatrix::annotation`].
+    pub fn read_counts<R: BufRead>(reader: R, format: &CountMatrixFormat) -> Result<Self> {
+        let mut lines = reader.lines().enumerate();
+
+        let (header, delimiter) = loop {
+            match lines.next() {
+                Some((_, line)) => {
+                    let line = line?;
+                    if line.trim().is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+                    let delimiter =
+                        format
+                            .delimiter
+                            .unwrap_or(if line.contains('\t') { '\t' } else { ',' });
+                    let header: Vec<String> = line
+                        .split(delimiter)
+                        .map(|h| h.trim().to_string())
+                        .collect();
+                    break (header, delimiter);
+                }
+                None => return Err(DataModelError::MissingField("header".to_string())),
+            }
+        };
+
+        let first_sample = 1 + format.metadata_columns;
+        if header.len() <= first_sample {
+            return Err(DataModelError::ValidationError(format!(
+                "header has {} columns, expected a gene id, {} metadata column(s) and at least one sample",
+                header.len(),
+                format.metadata_columns
+            )));
+        }
+
+        let mut genes = Vec::new();
+        let mut values = Vec::new();
+        let mut annotations = Vec::new();
+        for (idx, line) in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let line_number = idx + 1;
+            let fields: Vec<&str> = line.split(delimiter).map(str::trim).collect();
+            if fields.len() != header.len() {
+                return Err(DataModelError::ParseError(format!(
+                    "line {}: expected {} fields, found {}",
+                    line_number,
+                    header.len(),
+                    fields.len()
+                )));
+            }
+
+            for field in &fields[first_sample..] {
+                values.push(field.parse::<f64>().map_err(|_| {
+                    DataModelError::ParseError(format!(
+                        "line {}: count '{}' is not a number",
+                        line_number, field
+                    ))
+                })?);
+            }
+            genes.push(fields[0].to_string());
+            if format.metadata_columns > 0 {
+                annotations.push(
+                    header[1..first_sample]
+                        .iter()
+                        .cloned()
+                        .zip(fields[1..first_sample].iter().map(|f| f.to_string()))
+                        .collect(),
+                );
+            }
+        }
+
+        let mut matrix = Self::new(genes, header[first_sample..].to_vec(), values)?;
+        matrix.annotations = annotations;
+        Ok(matrix)
+    }
+
+    /// Build a count matrix from expression samples
+    ///
+    /// Rows are the sorted union of genes across samples; genes missing from a
+    /// sample are counted as zero.
+    pub fn from_samples(samples: &[ExpressionSample]) -> Self {
+        let mut genes: Vec<GeneId> = samples
+            .iter()
+            .flat_map(|s| s.counts.keys().cloned())
+            .collect();
+        genes.sort();
+        genes.dedup();
+
+        let mut values = Vec::with_capacity(genes.len() * samples.len());
+        for gene in &genes {
+            for sample in samples {
+                values.push(sample.counts.get(gene).copied().unwrap_or(0) as f64);
+            }
+        }
+
+        Self {
+            genes,
+            samples: samples.iter().map(|s| s.name.clone()).collect(),
+            values,
+            annotations: Vec::new(),
+        }
+    }
+
+    /// Get gene identifiers in row order
+    pub fn genes(&self) -> &[GeneId] {
+        &self.genes
+    }
+
+    /// Get sample names in column order
+    pub fn samples(&self) -> &[String] {
+        &self.samples
+    }
+
+    /// Get number of genes (rows)
+    pub fn n_genes(&self) -> usize {
+        self.genes.len()
+    }
+
+    /// Get number of samples (columns)
+    pub fn n_samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Get the row index of a gene
+    pub fn gene_index(&self, gene_id: &str) -> Option<usize> {
+        self.genes.iter().position(|g| g == gene_id)
+    }
+
+    /// Get the column index of a sample
+    pub fn sample_index(&self, sample: &str) -> Option<usize> {
+        self.samples.iter().position(|s| s == sample)
+    }
+
+    /// Get an annotation of a gene row, such as featureCounts' `Length`
+    pub fn annotation(&self, gene: usize, column: &str) -> Option<&str> {
+        self.annotations
+            .get(gene)
+            .and_then(|a| a.get(column))
+            .map(String::as_str)
+    }
+
+    /// Get the value at a gene row and sample column
+    pub fn get(&self, gene: usize, sample: usize) -> Option<f64> {
+        if gene < self.n_genes() && sample < self.n_samples() {
+            Some(self.values[gene * self.n_samples() + sample])
+        } else {
+            None
+        }
+    }
+
+    /// Get all values for a gene row
+    ///
+    /// # Panics
+    ///
+    /// Panics if `gene` is out of range; use [`get`](Self::get) for a
+    /// checked lookup.
+    pub fn row(&self, gene: usize) -> &[f64] {
+        let n = self.n_samples();
+        &self.values[gene * n..(gene + 1) * n]
+    }
+
+    /// Get all values for a sample column
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample` is out of range.
+    pub fn column(&self, sample: usize) -> Vec<f64> {
+        (0..self.n_genes())
+            .map(|g| self.values[g * self.n_samples() + sample])
+            .collect()
+    }
+
+    /// Collapse technical replicate columns into one column per group
+    ///
+    /// Output columns are named after the groups and ordered by group name.
+    /// Columns not listed in any group are dropped.
+    pub fn collapse_replicates(
+        &self,
+        groups: &HashMap<String, Vec<usize>>,
+        method: CollapseMethod,
+    ) -> Result<ExpressionMatrix> {
+        let mut names: Vec<&String> = groups.keys().collect();
+        names.sort();
+
+        let mut owners: Vec<Option<&String>> = vec![None; self.n_samples()];
+        for name in &names {
+            let columns = &groups[*name];
+            if columns.is_empty() {
+                return Err(DataModelError::ValidationError(format!(
+                    "replicate group '{}' has no columns",
+                    name
+                )));
+            }
+            for &col in columns {
+                if col >= self.n_samples() {
+                    return Err(DataModelError::ValidationError(format!(
+                        "column {} in group '{}' is out of range ({} samples)",
+                        col,
+                        name,
+                        self.n_samples()
+                    )));
+                }
+                match owners[col].replace(*name) {
+                    Some(owner) if owner == *name => {
+                        return Err(DataModelError::ValidationError(format!(
+                            "column {} is listed more than once in group '{}'",
+                            col, name
+                        )));
+                    }
+                    Some(owner) => {
+                        return Err(DataModelError::ValidationError(format!(
+                            "column {} appears in both replicate groups '{}' and '{}'",
+                            col, owner, name
+                        )));
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        let mut values = Vec::with_capacity(self.n_genes() * names.len());
+        for gene in 0..self.n_genes() {
+            let row = self.row(gene);
+            for name in &names {
+                let columns = &groups[*name];
+                let sum: f64 = columns.iter().map(|&c| row[c]).sum();
+                values.push(match method {
+                    CollapseMethod::Sum => sum,
+                    CollapseMethod::Mean => sum / columns.len() as f64,
+                });
+            }
+        }
+
+        let mut collapsed = ExpressionMatrix::new(
+            self.genes.clone(),
+            names.into_iter().cloned().collect(),
+            values,
+        )?;
+        collapsed.annotations = self.annotations.clone();
+        Ok(collapsed)
+    }
+
+    /// Estimate per-sample size factors with DESeq2's median-of-ratios method
+    ///
+    /// Each gene's reference is the geometric mean of its counts across
+    /// samples; genes with a zero count in any sample are left out. A sample's
+    /// size factor is the median, over the remaining genes, of its count
+    /// divided by the gene's reference. Fails with
+    /// [`DataModelError::ValidationError`] if no gene is nonzero everywhere.
+    pub fn median_of_ratios_factors(&self) -> Result<Vec<f64>> {
+        let n_samples = self.n_samples();
+        let mut log_ratios: Vec<Vec<f64>> = vec![Vec::new(); n_samples];
+
+        for g in 0..self.n_genes() {
+            let row = self.row(g);
+            if n_samples == 0 || row.iter().any(|&v| v.is_nan() || v <= 0.0) {
+                continue;
+            }
+            let logs: Vec<f64> = row.iter().map(|v| v.ln()).collect();
+            let log_geo_mean = logs.iter().sum::<f64>() / n_samples as f64;
+            for (ratios, log) in log_ratios.iter_mut().zip(&logs) {
+                ratios.push(log - log_geo_mean);
+            }
+        }
+
+        if log_ratios.iter().all(Vec::is_empty) {
+            return Err(DataModelError::ValidationError(
+                "no gene has a nonzero count in every sample".to_string(),
+            ));
+        }
+        Ok(log_ratios
+            .iter_mut()
+            .map(|ratios| median(ratios).exp())
+            .collect())
+    }
+
+    /// Append this matrix's samples to an incremental matrix file
+    ///
+    /// Each call writes one segment: a header with the gene order and sample
+    /// names, followed by the values. Appending to an existing file fails if
+    /// its first segment lists different genes, or a different order, or if
+    /// a sample name is repeated or already in the file, so the file stays
+    /// readable by [`ExpressionMatrix::read_appended`]. Annotations are not
+    /// stored.
+    pub fn append_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+
+        let mut seen = HashSet::new();
+        if file.metadata()?.len() > 0 {
+            let mut reader = BufReader::new(&file);
+            let mut segment = 0;
+            while let Some((genes, samples)) = read_segment_header(&mut reader)? {
+                segment += 1;
+                if segment == 1 {
+                    check_gene_order(&genes, &self.genes, 1)?;
+                }
+                skip_values(&mut reader, genes.len(), samples.len())?;
+                seen.extend(samples);
+            }
+        }
+        for sample in &self.samples {
+            if !seen.insert(sample.clone()) {
+                return Err(DataModelError::ValidationError(format!(
+                    "sample '{}' is already in the file or repeated in the segment",
+                    sample
+                )));
+            }
+        }
+
+        let mut writer = BufWriter::new(&mut file);
+        writer.write_all(SEGMENT_MAGIC)?;
+        write_strings(&mut writer, &self.genes)?;
+        write_strings(&mut writer, &self.samples)?;
+        for value in &self.values {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read an incremental matrix file written by
+    /// [`ExpressionMatrix::append_to_file`]
+    ///
+    /// The samples of all segments are concatenated in the order they were
+    /// appended. Every segment must list the same genes in the same order, and
+    /// sample names must be unique across segments.
+    pub fn read_appended<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut segments: Vec<ExpressionMatrix> = Vec::new();
+        while let Some(segment) = read_segment(&mut reader)? {
+            if let Some(first) = segments.first() {
+                check_gene_order(&first.genes, &segment.genes, segments.len() + 1)?;
+            }
+            segments.push(segment);
+        }
+
+        let genes = segments
+            .first()
+            .map(|s| s.genes.clone())
+            .unwrap_or_default();
+        let mut seen = HashSet::new();
+        let mut samples = Vec::new();
+        for sample in segments.iter().flat_map(|s| &s.samples) {
+            if !seen.insert(sample) {
+                return Err(DataModelError::ValidationError(format!(
+                    "sample '{}' appears in more than one segment",
+                    sample
+                )));
+            }
+            samples.push(sample.clone());
+        }
+
+        let mut values = Vec::with_capacity(genes.len() * samples.len());
+        for g in 0..genes.len() {
+            for segment in &segments {
+                values.extend_from_slice(segment.row(g));
+            }
+        }
+        Self::new(genes, samples, values)
+    }
+
+    /// Merge matrices whose gene sets overlap but need not be identical
+    ///
+    /// Rows are the union of gene ids in order of first appearance and
+    /// columns are the samples of each matrix in turn. Cells for a gene that
+    /// a matrix does not contain are set to `fill`, for example 0 for counts
+    /// or `f64::NAN` to mark them as missing. A gene's annotations are taken
+    /// from the first matrix that annotates it. Sample names must be unique
+    /// across all matrices.
+    pub fn merge_outer(matrices: &[ExpressionMatrix], fill: f64) -> Result<Self> {
+        let mut seen = HashSet::new();
+        let mut samples = Vec::new();
+        for sample in matrices.iter().flat_map(|m| &m.samples) {
+            if !seen.insert(sample) {
+                return Err(DataModelError::ValidationError(format!(
+                    "sample '{}' appears in more than one matrix",
+                    sample
+                )));
+            }
+            samples.push(sample.clone());
+        }
+
+        let mut genes = Vec::new();
+        let mut rows: HashMap<&str, usize> = HashMap::new();
+        for gene in matrices.iter().flat_map(|m| &m.genes) {
+            if !rows.contains_key(gene.as_str()) {
+                rows.insert(gene, genes.len());
+                genes.push(gene.clone());
+            }
+        }
+
+        let mut values = vec![fill; genes.len() * samples.len()];
+        let mut annotations = vec![None; genes.len()];
+        let mut offset = 0;
+        for matrix in matrices {
+            for (g, gene) in matrix.genes.iter().enumerate() {
+                let row = rows[gene.as_str()];
+                let start = row * samples.len() + offset;
+                values[start..start + matrix.n_samples()].copy_from_slice(matrix.row(g));
+                if let Some(annotation) = matrix.annotations.get(g) {
+                    annotations[row].get_or_insert_with(|| annotation.clone());
+                }
+            }
+            offset += matrix.n_samples();
+        }
+
+        let mut merged = Self::new(genes, samples, values)?;
+        if annotations.iter().any(Option::is_some) {
+            merged.annotations = annotations
+                .into_iter()
+                .map(Option::unwrap_or_default)
+                .collect();
+        }
+        Ok(merged)
+    }
+
+    /// Calculate the coefficient of variation of each gene across samples
+    ///
+    /// CV is the sample standard deviation divided by the mean. Genes with a
+    /// zero mean (including all-zero rows) are assigned a CV of 0 so they rank
+    /// as non-variable rather than being dropped.
+    pub fn coefficient_of_variation(&self) -> HashMap<GeneId, f64> {
+        self.genes
+            .iter()
+            .enumerate()
+            .map(|(g, gene)| (gene.clone(), coefficient_of_variation(self.row(g))))
+            .collect()
+    }
+
+    /// Get the `n` genes with the highest coefficient of variation
+    ///
+    /// Ties are broken by gene id so the selection is deterministic.
+    pub fn top_variable_genes(&self, n: usize) -> Vec<GeneId> {
+        let mut ranked: Vec<(GeneId, f64)> = self.coefficient_of_variation().into_iter().collect();
+        ranked.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        ranked.into_iter().take(n).map(|(gene, _)| gene).collect()
+    }
+
+    /// Correlate every pair of samples across genes
+    ///
+    /// Returns a symmetric `n_samples` x `n_samples` matrix indexed like
+    /// [`ExpressionMatrix::samples`]. Correlation is undefined for a sample
+    /// whose values are all equal (including an empty matrix); such samples
+    /// are given a correlation of 0 with every sample, themselves included,
+    /// so they sort as unrelated instead of poisoning the matrix with NaN.
+    pub fn sample_correlation(&self, method: CorrelationMethod) -> Vec<Vec<f64>> {
+        let columns: Vec<Vec<f64>> = (0..self.n_samples())
+            .map(|s| match method {
+                CorrelationMethod::Pearson => self.column(s),
+                CorrelationMethod::Spearman => ranks(&self.column(s)),
+            })
+            .collect();
+
+        let n = columns.len();
+        let mut matrix = vec![vec![0.0; n]; n];
+        for i in 0..n {
+            for j in i..n {
+                let r = pearson(&columns[i], &columns[j]);
+                matrix[i][j] = r;
+                matrix[j][i] = r;
+            }
+        }
+        matrix
+    }
+
+    /// Attach symbol, biotype and chromosome annotations to gene rows
+    ///
+    /// Each field that is set is stored under its name in
+    /// [`GENE_ANNOTATION_COLUMNS`], replacing any previous value and readable
+    /// through [`ExpressionMatrix::annotation`]. Genes missing from
+    /// `annotations` are left as they are.
+    pub fn annotate(&mut self, annotations: &HashMap<GeneId, GeneAnnotation>) {
+        self.annotations.resize_with(self.genes.len(), HashMap::new);
+        for (gene, row) in self.genes.iter().zip(&mut self.annotations) {
+            let Some(annotation) = annotations.get(gene) else {
+                continue;
+            };
+            let fields = [
+                &annotation.symbol,
+                &annotation.biotype,
+                &annotation.chromosome,
+            ];
+            for (column, value) in GENE_ANNOTATION_COLUMNS.iter().zip(fields) {
+                if let Some(value) = value {
+                    row.insert(column.to_string(), value.clone());
+                }
+            }
+        }
+    }
+
+    /// Write the matrix as a TSV table with gene annotation columns
+    ///
+    /// See [`ExpressionMatrix::write_annotated`] for the layout.
+    pub fn write_annotated_tsv<W: Write>(&self, writer: &mut W) -> Result<()> {
+        self.write_annotated(&mut TsvWriter::new(writer))
+    }
+
+    /// Write the matrix as a table with gene annotation columns
+    ///
+    /// The header is `gene_id`, the [`GENE_ANNOTATION_COLUMNS`], then one
+    /// column per sample. Missing annotations are written as empty fields.
+    pub fn write_annotated(&self, table: &mut dyn TableWriter) -> Result<()> {
+        let mut header = vec!["gene_id"];
+        header.extend(GENE_ANNOTATION_COLUMNS);
+        header.extend(self.samples.iter().map(String::as_str));
+        table.write_header(&header)?;
+
+        for (g, gene) in self.genes.iter().enumerate() {
+            let mut fields = vec![gene.clone()];
+            for column in GENE_ANNOTATION_COLUMNS {
+                fields.push(self.annotation(g, column).unwrap_or("").to_string());
+            }
+            fields.extend(self.row(g).iter().map(f64::to_string));
+            table.write_row(&fields)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read access and normalization shared by dense and sparse expression
+/// matrices
+///
+/// [`ExpressionMatrix`] stores every cell; [`SparseExpressionMatrix`] stores
+/// only non-zero ones. See [`crate::sparse`] for when to use each.
+///
+/// [`SparseExpressionMatrix`]: crate::sparse::SparseExpressionMatrix
+pub trait ExpressionData {
+    /// Get gene identifiers in row order
+    fn genes(&self) -> &[GeneId];
+
+    /// Get sample names in column order
+    fn samples(&self) -> &[String];
+
+    /// Get the value at a gene row and sample column
+    fn get(&self, gene: usize, sample: usize) -> Option<f64>;
+
+    /// Get the sum of each sample column
+    fn column_sums(&self) -> Vec<f64>;
+
+    /// Get a copy with every value in column `s` multiplied by `factors[s]`
+    ///
+    /// Fails with [`DataModelError::ValidationError`] unless there is one
+    /// factor per sample.
+    fn scale_columns(&self, factors: &[f64]) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Get number of genes (rows)
+    fn n_genes(&self) -> usize {
+        self.genes().len()
+    }
+
+    /// Get number of samples (columns)
+    fn n_samples(&self) -> usize {
+        self.samples().len()
+    }
+
+    /// Get the row index of a gene
+    fn gene_index(&self, gene_id: &str) -> Option<usize> {
+        self.genes().iter().position(|g| g == gene_id)
+    }
+
+    /// Get the column index of a sample
+    fn sample_index(&self, sample: &str) -> Option<usize> {
+        self.samples().iter().position(|s| s == sample)
+    }
+
+    /// Normalize each sample to counts per million
+    ///
+    /// Samples with no counts are left at zero.
+    fn cpm(&self) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let factors: Vec<f64> = self
+            .column_sums()
+            .into_iter()
+            .map(|sum| if sum > 0.0 { 1e6 / sum } else { 0.0 })
+            .collect();
+        self.scale_columns(&factors)
+    }
+}
+
+/// Check that there is one scaling factor per sample
+pub(crate) fn check_factors(factors: &[f64], n_samples: usize) -> Result<()> {
+    if factors.len() != n_samples {
+        return Err(DataModelError::ValidationError(format!(
+            "expected {} scaling factors, found {}",
+            n_samples,
+            factors.len()
+        )));
+    }
+    Ok(())
+}
+
+impl ExpressionData for ExpressionMatrix {
+    fn genes(&self) -> &[GeneId] {
+        ExpressionMatrix::genes(self)
+    }
+
+    fn samples(&self) -> &[String] {
+        ExpressionMatrix::samples(self)
+    }
+
+    fn get(&self, gene: usize, sample: usize) -> Option<f64> {
+        ExpressionMatrix::get(self, gene, sample)
+    }
+
+    fn column_sums(&self) -> Vec<f64> {
+        let mut sums = vec![0.0; self.samples.len()];
+        for g in 0..self.genes.len() {
+            for (sum, value) in sums.iter_mut().zip(self.row(g)) {
+                *sum += value;
+            }
+        }
+        sums
+    }
+
+    fn scale_columns(&self, factors: &[f64]) -> Result<Self> {
+        check_factors(factors, self.samples.len())?;
+        let mut scaled = self.clone();
+        if !factors.is_empty() {
+            for row in scaled.values.chunks_mut(factors.len()) {
+                for (value, factor) in row.iter_mut().zip(factors) {
+                    *value *= factor;
+                }
+            }
+        }
+        Ok(scaled)
+    }
+}
+
+/// Marker starting every segment of an incremental matrix file
+const SEGMENT_MAGIC: &[u8; 4] = b"EXM1";
+
+/// Longest gene id or sample name accepted when reading a segment
+const MAX_SEGMENT_STRING_LEN: usize = 64 * 1024;
+
+/// Write a `u32` count followed by length-prefixed UTF-8 strings
+fn write_strings<W: Write>(writer: &mut W, strings: &[String]) -> Result<()> {
+    writer.write_all(&len_u32(strings.len())?.to_le_bytes())?;
+    for string in strings {
+        writer.write_all(&len_u32(string.len())?.to_le_bytes())?;
+        writer.write_all(string.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn len_u32(len: usize) -> Result<u32> {
+    u32::try_from(len)
+        .map_err(|_| DataModelError::ValidationError(format!("length {} exceeds u32", len)))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Read a `u32` count followed by length-prefixed UTF-8 strings
+///
+/// Buffers grow with the bytes actually read rather than the lengths in the
+/// file, so a corrupt header fails with an error instead of allocating
+/// gigabytes.
+fn read_strings<R: Read>(reader: &mut R) -> Result<Vec<String>> {
+    let count = read_u32(reader)?;
+    let mut strings = Vec::new();
+    for _ in 0..count {
+        let len = read_u32(reader)? as usize;
+        if len > MAX_SEGMENT_STRING_LEN {
+            return Err(DataModelError::ParseError(format!(
+                "segment string of {} bytes exceeds the {} byte limit",
+                len, MAX_SEGMENT_STRING_LEN
+            )));
+        }
+        let mut bytes = Vec::new();
+        if reader.take(len as u64).read_to_end(&mut bytes)? < len {
+            return Err(truncated_segment());
+        }
+        strings.push(String::from_utf8(bytes)?);
+    }
+    Ok(strings)
+}
+
+fn truncated_segment() -> DataModelError {
+    DataModelError::ParseError("incremental matrix segment is truncated".to_string())
+}
+
+/// Get the number of values in a segment of `genes` x `samples`
+fn segment_values(genes: usize, samples: usize) -> Result<usize> {
+    genes
+        .checked_mul(samples)
+        .filter(|&n| n.checked_mul(8).is_some())
+        .ok_or_else(|| {
+            DataModelError::ParseError(format!(
+                "segment of {} genes x {} samples is too large",
+                genes, samples
+            ))
+        })
+}
+
+/// Read the magic, genes and samples of the next segment of an incremental
+/// matrix file, or `None` at the end
+fn read_segment_header<R: BufRead>(reader: &mut R) -> Result<Option<(Vec<GeneId>, Vec<String>)>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != SEGMENT_MAGIC {
+        return Err(DataModelError::ParseError(
+            "not an incremental expression matrix segment".to_string(),
+        ));
+    }
+
+    let genes = read_strings(reader)?;
+    let samples = read_strings(reader)?;
+    Ok(Some((genes, samples)))
+}
+
+/// Skip the values following a segment header
+fn skip_values<R: Read>(reader: &mut R, genes: usize, samples: usize) -> Result<()> {
+    let bytes = segment_values(genes, samples)? as u64 * 8;
+    if std::io::copy(&mut reader.take(bytes), &mut std::io::sink())? < bytes {
+        return Err(truncated_segment());
+    }
+    Ok(())
+}
+
+/// Read the next segment of an incremental matrix file, or `None` at the end
+fn read_segment<R: BufRead>(reader: &mut R) -> Result<Option<ExpressionMatrix>> {
+    let Some((genes, samples)) = read_segment_header(reader)? else {
+        return Ok(None);
+    };
+    let count = segment_values(genes.len(), samples.len())?;
+
+    // Grown as values arrive; see read_strings
+    let mut values = Vec::new();
+    let mut bytes = [0u8; 8];
+    for _ in 0..count {
+        reader
+            .read_exact(&mut bytes)
+            .map_err(|_| truncated_segment())?;
+        values.push(f64::from_le_bytes(bytes));
+    }
+    ExpressionMatrix::new(genes, samples, values).map(Some)
+}
+
+/// Check that a segment lists the same genes, in the same order, as the first
+fn check_gene_order(expected: &[GeneId], found: &[GeneId], segment: usize) -> Result<()> {
+    if expected == found {
+        return Ok(());
+    }
+    let detail = match expected.iter().zip(found).position(|(a, b)| a != b) {
+        Some(row) => format!(
+            "row {} is '{}', expected '{}'",
+            row, found[row], expected[row]
+        ),
+        None => format!("{} genes, expected {}", found.len(), expected.len()),
+    };
+    Err(DataModelError::ValidationError(format!(
+        "gene order mismatch in segment {}: {}",
+        segment, detail
+    )))
+}
+
+/// Median of a non-empty slice, averaging the middle pair for even lengths
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len() % 2 == 1 {
+        values[mid]
+    } else {
+        (values[mid - 1] + values[mid]) / 2.0
+    }
+}
+
+/// Pearson correlation of two equal-length slices, or 0 if either is constant
+fn pearson(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len() as f64;
+    let mean_a = a.iter().sum::<f64>() / n;
+    let mean_b = b.iter().sum::<f64>() / n;
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        let (dx, dy) = (x - mean_a, y - mean_b);
+        cov += dx * dy;
+        var_a += dx * dx;
+        var_b += dy * dy;
+    }
+    if var_a == 0.0 || var_b == 0.0 {
+        return 0.0;
+    }
+    (cov / (var_a * var_b).sqrt()).clamp(-1.0, 1.0)
+}
+
+/// 1-based ranks of the values, with ties given their average rank
+fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+/// Sample standard deviation over mean, or 0 for a zero mean or fewer than two values
+fn coefficient_of_variation(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    if mean == 0.0 {
+        return 0.0;
+    }
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    variance.sqrt() / mean
+}
+
 /// Differential expression analysis result for a single gene
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DifferentialExpression {
@@ -168,6 +1115,44 @@ impl DifferentialExpressionAnalysis {
     }
 }
 
+/// Smallest p-value used for the `-log10(p)` column of [`write_volcano_tsv`]
+///
+/// Tests can underflow to exactly zero; clamping keeps those genes plottable
+/// at the top of the volcano instead of at infinity.
+pub const VOLCANO_PVALUE_EPSILON: f64 = 1e-300;
+
+/// Write differential expression results as a volcano-plot TSV table
+///
+/// See [`write_volcano`] for the layout.
+pub fn write_volcano_tsv<W: Write>(
+    results: &[DifferentialExpression],
+    writer: &mut W,
+) -> Result<()> {
+    write_volcano(results, &mut TsvWriter::new(writer))
+}
+
+/// Write differential expression results as a volcano-plot table
+///
+/// Each row lists the gene id, log2 fold change, p-value and `-log10(p)`.
+/// P-values below [`VOLCANO_PVALUE_EPSILON`] are clamped to it before the
+/// log; the p-value column keeps the original value.
+pub fn write_volcano(
+    results: &[DifferentialExpression],
+    table: &mut dyn TableWriter,
+) -> Result<()> {
+    table.write_header(&["gene_id", "log2_fold_change", "pvalue", "neg_log10_pvalue"])?;
+    for result in results {
+        let neg_log10 = -result.pvalue.max(VOLCANO_PVALUE_EPSILON).log10();
+        table.write_row(&[
+            result.gene_id.clone(),
+            result.log2_fold_change.to_string(),
+            result.pvalue.to_string(),
+            format!("{:.4}", neg_log10),
+        ])?;
+    }
+    Ok(())
+}
+
 /// Parameters for differential expression analysis
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalysisParameters {
@@ -264,6 +1249,136 @@ impl PathwayAnalysis {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tables::CsvWriter;
+
+    /// Split CSV lines into fields, undoing RFC 4180 quoting
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        text.lines()
+            .map(|line| {
+                let mut fields = vec![String::new()];
+                let (mut quoted, mut chars) = (false, line.chars().peekable());
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' if quoted && chars.peek() == Some(&'"') => {
+                            chars.next();
+                            fields.last_mut().unwrap().push('"');
+                        }
+                        '"' => quoted = !quoted,
+                        ',' if !quoted => fields.push(String::new()),
+                        c => fields.last_mut().unwrap().push(c),
+                    }
+                }
+                fields
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_export_csv_matches_tsv() {
+        let mut matrix = ExpressionMatrix::new(
+            vec!["ENSG01".to_string(), "ENSG02".to_string()],
+            vec!["day 1, rep \"A\"".to_string(), "day 2".to_string()],
+            vec![1.0, 2.5, 3.0, 4.0],
+        )
+        .unwrap();
+        let annotations = HashMap::from([(
+            "ENSG01".to_string(),
+            GeneAnnotation {
+                symbol: Some("TP53".to_string()),
+                biotype: Some("protein_coding".to_string()),
+                chromosome: Some("chr17".to_string()),
+            },
+        )]);
+        matrix.annotate(&annotations);
+
+        let mut csv = CsvWriter::new(Vec::new());
+        matrix.write_annotated(&mut csv).unwrap();
+        let csv = String::from_utf8(csv.into_inner()).unwrap();
+        let mut tsv = Vec::new();
+        matrix.write_annotated_tsv(&mut tsv).unwrap();
+        let tsv = String::from_utf8(tsv).unwrap();
+
+        let from_tsv: Vec<Vec<String>> = tsv
+            .lines()
+            .map(|line| line.split('\t').map(str::to_string).collect())
+            .collect();
+        let from_csv = parse_csv(&csv);
+        assert_eq!(from_csv, from_tsv);
+        assert_eq!(from_csv.len(), 3);
+        assert_eq!(from_csv[0][4], "day 1, rep \"A\"");
+        assert_eq!(
+            from_csv[1],
+            ["ENSG01", "TP53", "protein_coding", "chr17", "1", "2.5"]
+        );
+    }
+
+    #[test]
+    fn test_annotate_and_export() {
+        let mut matrix = ExpressionMatrix::new(
+            vec!["ENSG01".to_string(), "ENSG02".to_string()],
+            vec!["s1".to_string(), "s2".to_string()],
+            vec![1.0, 2.5, 3.0, 4.0],
+        )
+        .unwrap();
+        let annotations = HashMap::from([(
+            "ENSG01".to_string(),
+            GeneAnnotation {
+                symbol: Some("TP53".to_string()),
+                biotype: Some("protein_coding".to_string()),
+                chromosome: None,
+            },
+        )]);
+        matrix.annotate(&annotations);
+
+        assert_eq!(matrix.annotation(0, "symbol"), Some("TP53"));
+        assert_eq!(matrix.annotation(1, "symbol"), None);
+
+        let mut out = Vec::new();
+        matrix.write_annotated_tsv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "gene_id\tsymbol\tbiotype\tchromosome\ts1\ts2\n\
+             ENSG01\tTP53\tprotein_coding\t\t1\t2.5\n\
+             ENSG02\t\t\t\t3\t4\n"
+        );
+    }
+
+    #[test]
+    fn test_sample_correlation() {
+        // s1 and s2 nearly match, s3 is reversed and s4 is constant
+        let matrix = ExpressionMatrix::new(
+            vec!["g1", "g2", "g3", "g4"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            vec!["s1", "s2", "s3", "s4"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            vec![
+                1.0, 1.1, 40.0, 5.0, //
+                5.0, 5.2, 30.0, 5.0, //
+                10.0, 9.8, 20.0, 5.0, //
+                40.0, 41.0, 1.0, 5.0,
+            ],
+        )
+        .unwrap();
+
+        for method in [CorrelationMethod::Pearson, CorrelationMethod::Spearman] {
+            let corr = matrix.sample_correlation(method);
+            assert_eq!(corr.len(), 4);
+            let best = (1..4).max_by(|&a, &b| corr[0][a].total_cmp(&corr[0][b]));
+            assert_eq!(best, Some(1));
+            assert!(corr[0][1] > 0.99);
+            assert!(corr[0][2] < 0.0);
+            assert_eq!(corr[1][0], corr[0][1]);
+            assert_eq!((corr[3][0], corr[3][3]), (0.0, 0.0));
+        }
+
+        let spearman = matrix.sample_correlation(CorrelationMethod::Spearman);
+        assert!((spearman[0][1] - 1.0).abs() < 1e-12);
+        assert!((spearman[0][2] + 1.0).abs() < 1e-12);
+    }
 
     #[test]
     fn test_expression_sample() {
@@ -293,6 +1408,30 @@ mod tests {
         assert!(!de.is_downregulated(0.05, 1.0));
     }
 
+    #[test]
+    fn test_write_volcano_tsv() {
+        let result = |gene_id: &str, log2_fold_change: f64, pvalue: f64| DifferentialExpression {
+            gene_id: gene_id.to_string(),
+            gene_name: None,
+            log2_fold_change,
+            log2_fold_change_se: 0.1,
+            base_mean: 50.0,
+            wald_statistic: 0.0,
+            pvalue,
+            padj: pvalue,
+        };
+        let results = [result("GENE1", 1.5, 0.001), result("GENE2", -2.0, 0.0)];
+
+        let mut out = Vec::new();
+        write_volcano_tsv(&results, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "gene_id\tlog2_fold_change\tpvalue\tneg_log10_pvalue\n\
+             GENE1\t1.5\t0.001\t3.0000\n\
+             GENE2\t-2\t0\t300.0000\n"
+        );
+    }
+
     #[test]
     fn test_pathway_enrichment() {
         let pathway = PathwayEnrichment {
@@ -310,4 +1449,326 @@ mod tests {
         assert!(pathway.is_significant(0.05));
         assert_eq!(pathway.gene_ratio(), 0.15);
     }
+
+    fn replicate_matrix() -> ExpressionMatrix {
+        ExpressionMatrix::new(
+            vec!["GENE1".to_string(), "GENE2".to_string()],
+            vec![
+                "ctrl_r1".to_string(),
+                "ctrl_r2".to_string(),
+                "trt_r1".to_string(),
+                "trt_r2".to_string(),
+            ],
+            vec![10.0, 20.0, 30.0, 50.0, 1.0, 3.0, 5.0, 7.0],
+        )
+        .unwrap()
+    }
+
+    fn replicate_groups() -> HashMap<String, Vec<usize>> {
+        HashMap::from([
+            ("control".to_string(), vec![0, 1]),
+            ("treated".to_string(), vec![2, 3]),
+        ])
+    }
+
+    #[test]
+    fn test_collapse_replicates_sum() {
+        let collapsed = replicate_matrix()
+            .collapse_replicates(&replicate_groups(), CollapseMethod::Sum)
+            .unwrap();
+
+        assert_eq!(collapsed.samples(), ["control", "treated"]);
+        assert_eq!(collapsed.row(0), [30.0, 80.0]);
+        assert_eq!(collapsed.row(1), [4.0, 12.0]);
+    }
+
+    #[test]
+    fn test_collapse_replicates_mean() {
+        let collapsed = replicate_matrix()
+            .collapse_replicates(&replicate_groups(), CollapseMethod::Mean)
+            .unwrap();
+
+        assert_eq!(collapsed.row(0), [15.0, 40.0]);
+        assert_eq!(collapsed.row(1), [2.0, 6.0]);
+    }
+
+    #[test]
+    fn test_collapse_replicates_invalid_groups() {
+        let matrix = replicate_matrix();
+
+        let overlapping =
+            HashMap::from([("a".to_string(), vec![0, 1]), ("b".to_string(), vec![1, 2])]);
+        let err = matrix
+            .collapse_replicates(&overlapping, CollapseMethod::Sum)
+            .unwrap_err();
+        assert!(err.to_string().contains("groups 'a' and 'b'"));
+
+        let repeated = HashMap::from([("a".to_string(), vec![0, 1, 0])]);
+        let err = matrix
+            .collapse_replicates(&repeated, CollapseMethod::Sum)
+            .unwrap_err();
+        assert!(err.to_string().contains("more than once in group 'a'"));
+
+        let out_of_range = HashMap::from([("a".to_string(), vec![0, 4])]);
+        assert!(matrix
+            .collapse_replicates(&out_of_range, CollapseMethod::Sum)
+            .is_err());
+    }
+
+    #[test]
+    fn test_merge_outer() {
+        let strings = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        let first = ExpressionMatrix::new(
+            strings(&["A", "B", "C"]),
+            strings(&["s1", "s2"]),
+            vec![
+                1.0, 2.0, // A
+                3.0, 4.0, // B
+                5.0, 6.0, // C
+            ],
+        )
+        .unwrap();
+        let second = ExpressionMatrix::new(
+            strings(&["C", "D", "A"]),
+            strings(&["s3"]),
+            vec![
+                7.0, // C
+                8.0, // D
+                9.0, // A
+            ],
+        )
+        .unwrap();
+
+        let merged = ExpressionMatrix::merge_outer(&[first.clone(), second], 0.0).unwrap();
+        assert_eq!(merged.genes(), &["A", "B", "C", "D"]);
+        assert_eq!(merged.samples(), &["s1", "s2", "s3"]);
+        assert_eq!(merged.row(0), &[1.0, 2.0, 9.0]);
+        assert_eq!(merged.row(1), &[3.0, 4.0, 0.0]);
+        assert_eq!(merged.row(2), &[5.0, 6.0, 7.0]);
+        assert_eq!(merged.row(3), &[0.0, 0.0, 8.0]);
+
+        let only_d = ExpressionMatrix::new(strings(&["D"]), strings(&["s4"]), vec![10.0]).unwrap();
+        let merged = ExpressionMatrix::merge_outer(&[first.clone(), only_d], f64::NAN).unwrap();
+        assert_eq!(merged.genes(), &["A", "B", "C", "D"]);
+        assert!(merged.row(0)[2].is_nan());
+        assert!(merged.row(3)[..2].iter().all(|v| v.is_nan()));
+        assert_eq!(merged.get(3, 2), Some(10.0));
+
+        let duplicate =
+            ExpressionMatrix::new(strings(&["A"]), strings(&["s2"]), vec![1.0]).unwrap();
+        assert!(ExpressionMatrix::merge_outer(&[first, duplicate], 0.0).is_err());
+    }
+
+    #[test]
+    fn test_top_variable_genes() {
+        let matrix = ExpressionMatrix::new(
+            vec![
+                "FLAT".to_string(),
+                "VARIABLE".to_string(),
+                "ZERO".to_string(),
+            ],
+            vec!["s1".to_string(), "s2".to_string(), "s3".to_string()],
+            vec![
+                50.0, 50.0, 50.0, // flat
+                1.0, 100.0, 10.0, // highly variable
+                0.0, 0.0, 0.0, // never detected
+            ],
+        )
+        .unwrap();
+
+        let cv = matrix.coefficient_of_variation();
+        assert_eq!(cv["FLAT"], 0.0);
+        assert_eq!(cv["ZERO"], 0.0);
+        assert!(cv["VARIABLE"] > 1.0);
+
+        assert_eq!(matrix.top_variable_genes(1), vec!["VARIABLE".to_string()]);
+        assert_eq!(
+            matrix.top_variable_genes(3),
+            vec![
+                "VARIABLE".to_string(),
+                "FLAT".to_string(),
+                "ZERO".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_feature_counts() {
+        let content =
+            "# Program:featureCounts v2.0.1; Command:\"featureCounts\" \"-a\" \"genes.gtf\"\n\
+            Geneid\tChr\tStart\tEnd\tStrand\tLength\tctrl.bam\ttrt.bam\n\
+            GENE1\tchr1\t100\t500\t+\t401\t10\t20\n\
+            GENE2\tchr2;chr2\t7;50\t20;90\t-;-\t55\t0\t3\n";
+
+        let matrix =
+            ExpressionMatrix::read_counts(content.as_bytes(), &CountMatrixFormat::feature_counts())
+                .unwrap();
+
+        assert_eq!(matrix.genes(), ["GENE1", "GENE2"]);
+        assert_eq!(matrix.samples(), ["ctrl.bam", "trt.bam"]);
+        assert_eq!(matrix.row(0), [10.0, 20.0]);
+        assert_eq!(matrix.row(1), [0.0, 3.0]);
+        assert_eq!(matrix.annotation(0, "Chr"), Some("chr1"));
+        assert_eq!(matrix.annotation(1, "Length"), Some("55"));
+        assert_eq!(matrix.annotation(1, "ctrl.bam"), None);
+    }
+
+    #[test]
+    fn test_read_plain_matrix() {
+        let content = "# exported counts\n# second comment\ngene_id,s1,s2,s3\nA,1,2,3\nB,4,5,6\n";
+
+        let matrix =
+            ExpressionMatrix::read_counts(content.as_bytes(), &CountMatrixFormat::default())
+                .unwrap();
+
+        assert_eq!(matrix.genes(), ["A", "B"]);
+        assert_eq!(matrix.samples(), ["s1", "s2", "s3"]);
+        assert_eq!(matrix.get(1, 2), Some(6.0));
+        assert_eq!(matrix.annotation(0, "gene_id"), None);
+
+        let ragged = "gene_id,s1\nA,1,2\n";
+        let err = ExpressionMatrix::read_counts(ragged.as_bytes(), &CountMatrixFormat::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn test_median_of_ratios_factors() {
+        // D has a zero count, so only A, B and C set the reference
+        let matrix = ExpressionMatrix::new(
+            vec!["A".into(), "B".into(), "C".into(), "D".into()],
+            vec!["s1".into(), "s2".into()],
+            vec![10.0, 20.0, 20.0, 40.0, 30.0, 30.0, 0.0, 5.0],
+        )
+        .unwrap();
+
+        // Geometric means are √200, √800 and 30, so s1's ratios are
+        // 1/√2, 1/√2 and 1, and s2's are √2, √2 and 1
+        let factors = matrix.median_of_ratios_factors().unwrap();
+        assert_eq!(factors.len(), 2);
+        assert!((factors[0] - 1.0 / 2f64.sqrt()).abs() < 1e-12);
+        assert!((factors[1] - 2f64.sqrt()).abs() < 1e-12);
+
+        let sparse = ExpressionMatrix::new(
+            vec!["A".into(), "B".into()],
+            vec!["s1".into(), "s2".into()],
+            vec![0.0, 3.0, 4.0, 0.0],
+        )
+        .unwrap();
+        assert!(matches!(
+            sparse.median_of_ratios_factors(),
+            Err(DataModelError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_append_and_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("screen.exm");
+        let genes = vec!["A".to_string(), "B".to_string()];
+
+        let first = ExpressionMatrix::new(
+            genes.clone(),
+            vec!["s1".to_string(), "s2".to_string()],
+            vec![1.0, 2.0, 3.0, 4.0],
+        )
+        .unwrap();
+        let second =
+            ExpressionMatrix::new(genes.clone(), vec!["s3".to_string()], vec![5.0, 6.0]).unwrap();
+        first.append_to_file(&path).unwrap();
+        second.append_to_file(&path).unwrap();
+
+        let combined = ExpressionMatrix::read_appended(&path).unwrap();
+        assert_eq!(combined.genes(), ["A", "B"]);
+        assert_eq!(combined.samples(), ["s1", "s2", "s3"]);
+        assert_eq!(combined.row(0), [1.0, 2.0, 5.0]);
+        assert_eq!(combined.row(1), [3.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn test_append_rejects_repeated_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("screen.exm");
+        let genes = vec!["A".to_string(), "B".to_string()];
+        let batch = |samples: &[&str]| {
+            ExpressionMatrix::new(
+                genes.clone(),
+                samples.iter().map(|s| s.to_string()).collect(),
+                vec![1.0; 2 * samples.len()],
+            )
+            .unwrap()
+        };
+
+        batch(&["s1", "s2"]).append_to_file(&path).unwrap();
+        batch(&["s3"]).append_to_file(&path).unwrap();
+        let err = batch(&["s4", "s2"]).append_to_file(&path).unwrap_err();
+        assert!(err.to_string().contains("sample 's2'"));
+        assert!(batch(&["s5", "s5"]).append_to_file(&path).is_err());
+
+        // Rejected batches left the file readable
+        let combined = ExpressionMatrix::read_appended(&path).unwrap();
+        assert_eq!(combined.samples(), ["s1", "s2", "s3"]);
+    }
+
+    #[test]
+    fn test_read_corrupt_segment_lengths() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // A gene id claiming to be 4 GB long
+        let path = dir.path().join("long_string.exm");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(SEGMENT_MAGIC).unwrap();
+        file.write_all(&1u32.to_le_bytes()).unwrap();
+        file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        drop(file);
+        assert!(ExpressionMatrix::read_appended(&path).is_err());
+
+        // Many genes and samples but no values
+        let path = dir.path().join("huge_matrix.exm");
+        let names = |prefix: &str| -> Vec<String> {
+            (0..50_000).map(|i| format!("{}{}", prefix, i)).collect()
+        };
+        let mut file = File::create(&path).unwrap();
+        file.write_all(SEGMENT_MAGIC).unwrap();
+        write_strings(&mut file, &names("g")).unwrap();
+        write_strings(&mut file, &names("s")).unwrap();
+        drop(file);
+        let err = ExpressionMatrix::read_appended(&path).unwrap_err();
+        assert!(err.to_string().contains("truncated"));
+        let extra = ExpressionMatrix::new(names("g"), vec!["t".to_string()], vec![0.0; 50_000]);
+        assert!(extra.unwrap().append_to_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_append_gene_order_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("screen.exm");
+        let matrix = |genes: [&str; 2], sample: &str| {
+            ExpressionMatrix::new(
+                genes.iter().map(|g| g.to_string()).collect(),
+                vec![sample.to_string()],
+                vec![1.0, 2.0],
+            )
+            .unwrap()
+        };
+
+        matrix(["A", "B"], "s1").append_to_file(&path).unwrap();
+        let err = matrix(["B", "A"], "s2").append_to_file(&path).unwrap_err();
+        assert!(err.to_string().contains("gene order mismatch"));
+
+        // A segment written by another tool is still caught on read
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(SEGMENT_MAGIC).unwrap();
+        write_strings(&mut file, &["B".to_string(), "A".to_string()]).unwrap();
+        write_strings(&mut file, &["s2".to_string()]).unwrap();
+        for value in [1.0f64, 2.0] {
+            file.write_all(&value.to_le_bytes()).unwrap();
+        }
+        drop(file);
+
+        let err = ExpressionMatrix::read_appended(&path).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("segment 2: row 0 is 'B', expected 'A'"));
+    }
 }
@@ -0,0 +1,504 @@
+//! Readers for FASTQ, FASTA and GFF3 files
+//!
+//! Every reader takes an [`ErrorMode`]. In [`ErrorMode::FailFast`] the first
+//! malformed record aborts parsing with its line number in the error. In
+//! [`ErrorMode::CollectAll`] malformed records are skipped and reported in the
+//! returned [`ParseReport`], so a whole file can be checked in one pass. I/O
+//! errors always abort.
+
+use crate::error::{DataModelError, ErrorMode, ParseReport, Result};
+use crate::intervals::GffInterval;
+use crate::sequencing::{FastaRecord, FastqRecord};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+/// A single feature line from a GFF3 file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GffFeature {
+    /// Sequence (chromosome/contig) the feature lies on
+    pub seqid: String,
+    /// Program or database that produced the feature
+    pub source: String,
+    /// Feature type, e.g. `exon` or `CDS`
+    pub feature_type: String,
+    /// 1-based start position (inclusive)
+    pub start: u64,
+    /// 1-based end position (inclusive)
+    pub end: u64,
+    /// Feature score
+    pub score: Option<f64>,
+    /// Strand (`Some(true)` = forward, `Some(false)` = reverse)
+    pub strand: Option<bool>,
+    /// CDS reading phase (0-2)
+    pub phase: Option<u8>,
+    /// Column 9 `key=value` attributes
+    pub attributes: HashMap<String, String>,
+}
+
+impl GffFeature {
+    /// Get an attribute value by key
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).map(String::as_str)
+    }
+
+    /// Get the feature's 1-based closed interval
+    pub fn interval(&self) -> Result<GffInterval> {
+        GffInterval::new(self.start, self.end)
+    }
+
+    /// Format the feature as a BED line named by its `ID` attribute
+    pub fn to_bed_line(&self) -> Result<String> {
+        let bed = self.interval()?.to_bed();
+        Ok(bed.to_bed_line(&self.seqid, self.attribute("ID")))
+    }
+}
+
+/// Read four-line FASTQ records
+///
+/// Records are read in fixed groups of four lines, so a malformed record never
+/// shifts the framing of the records after it.
+pub fn read_fastq<R: BufRead>(
+    reader: R,
+    mode: ErrorMode,
+) -> Result<(Vec<FastqRecord>, ParseReport)> {
+    let mut records = Vec::new();
+    let mut report = ParseReport::new();
+    let mut lines = reader.lines().enumerate();
+
+    while let Some((idx, header)) = lines.next() {
+        let header = header?;
+        if header.trim().is_empty() {
+            continue;
+        }
+        let mut group = vec![header];
+        for _ in 0..3 {
+            match lines.next() {
+                Some((_, line)) => group.push(line?),
+                None => break,
+            }
+        }
+
+        match parse_fastq_record(&group) {
+            Ok(record) => records.push(record),
+            Err(err) => report.handle(mode, idx + 1, err)?,
+        }
+    }
+
+    Ok((records, report))
+}
+
+/// Split a FASTQ stream into `num_shards` streams, round-robin by record
+///
+/// `make_writer(i)` is called once per shard, in order, before any record is
+/// read. Records are copied verbatim as whole four-line groups, so shards
+/// concatenate back to the input minus blank lines between records. Only
+/// framing is checked: each group must have four lines, a header starting
+/// with `@` and a separator starting with `+`; anything else fails with a
+/// [`DataModelError::ParseError`] naming the header line. Returns the number
+/// of records written to each shard.
+pub fn shard_fastq<R: BufRead>(
+    reader: R,
+    num_shards: usize,
+    mut make_writer: impl FnMut(usize) -> Box<dyn Write>,
+) -> Result<Vec<u64>> {
+    if num_shards == 0 {
+        return Err(DataModelError::ValidationError(
+            "cannot split FASTQ into 0 shards".to_string(),
+        ));
+    }
+    let mut writers: Vec<Box<dyn Write>> = (0..num_shards).map(&mut make_writer).collect();
+    let mut counts = vec![0u64; num_shards];
+    let mut lines = reader.lines().enumerate();
+    let mut shard = 0;
+
+    while let Some((idx, header)) = lines.next() {
+        let header = header?;
+        if header.trim().is_empty() {
+            continue;
+        }
+        let mut group = vec![header];
+        for _ in 0..3 {
+            match lines.next() {
+                Some((_, line)) => group.push(line?),
+                None => break,
+            }
+        }
+
+        let framing = match group.as_slice() {
+            [header, _, separator, _] if header.starts_with('@') && separator.starts_with('+') => {
+                Ok(())
+            }
+            [_, _, _, _] => Err("FASTQ record must start with '@' and have a '+' separator"),
+            _ => Err("truncated FASTQ record"),
+        };
+        if let Err(message) = framing {
+            return Err(DataModelError::ParseError(format!(
+                "line {}: {}",
+                idx + 1,
+                message
+            )));
+        }
+
+        for line in &group {
+            writeln!(writers[shard], "{}", line)?;
+        }
+        counts[shard] += 1;
+        shard = (shard + 1) % num_shards;
+    }
+
+    for writer in &mut writers {
+        writer.flush()?;
+    }
+    Ok(counts)
+}
+
+/// Parse one FASTQ record from its (up to) four lines
+fn parse_fastq_record(lines: &[String]) -> Result<FastqRecord> {
+    let [header, sequence, separator, quality] = lines else {
+        return Err(DataModelError::ParseError(
+            "truncated FASTQ record".to_string(),
+        ));
+    };
+
+    let header = header.strip_prefix('@').ok_or_else(|| {
+        DataModelError::ParseError("FASTQ header must start with '@'".to_string())
+    })?;
+    if !separator.starts_with('+') {
+        return Err(DataModelError::ParseError(
+            "FASTQ separator line must start with '+'".to_string(),
+        ));
+    }
+
+    let (id, description) = split_header(header)?;
+    let mut record = FastqRecord::new(
+        id,
+        sequence.trim().to_string(),
+        quality.trim().as_bytes().to_vec(),
+    )?;
+    record.description = description;
+    Ok(record)
+}
+
+/// Read FASTA records, joining wrapped sequence lines
+///
+/// Each record is reported at its header line.
+pub fn read_fasta<R: BufRead>(
+    reader: R,
+    mode: ErrorMode,
+) -> Result<(Vec<FastaRecord>, ParseReport)> {
+    let mut records = Vec::new();
+    let mut report = ParseReport::new();
+    // Header line number and record being built; `None` after a bad header
+    let mut current: Option<(usize, Option<FastaRecord>)> = None;
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        let line_number = idx + 1;
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('>') {
+            if let Some(record) = current.take() {
+                finish_fasta_record(record, &mut records, &mut report, mode)?;
+            }
+            match split_header(header) {
+                Ok((id, description)) => {
+                    current = Some((
+                        line_number,
+                        Some(FastaRecord {
+                            id,
+                            description,
+                            sequence: String::new(),
+                        }),
+                    ))
+                }
+                Err(err) => {
+                    report.handle(mode, line_number, err)?;
+                    current = Some((line_number, None));
+                }
+            }
+            continue;
+        }
+
+        match &mut current {
+            Some((_, Some(record))) => {
+                if let Some(c) = line
+                    .chars()
+                    .find(|c| !c.is_ascii_alphabetic() && *c != '-' && *c != '*')
+                {
+                    let err = DataModelError::InvalidSequence(format!(
+                        "invalid character '{}' in sequence '{}'",
+                        c, record.id
+                    ));
+                    report.handle(mode, line_number, err)?;
+                    current = Some((line_number, None));
+                } else {
+                    record.sequence.push_str(line);
+                }
+            }
+            // Continuation lines of a record already reported as malformed
+            Some((_, None)) => {}
+            None => {
+                let err = DataModelError::ParseError(
+                    "sequence data before first FASTA header".to_string(),
+                );
+                report.handle(mode, line_number, err)?;
+                current = Some((line_number, None));
+            }
+        }
+    }
+
+    if let Some(record) = current {
+        finish_fasta_record(record, &mut records, &mut report, mode)?;
+    }
+
+    Ok((records, report))
+}
+
+/// Store a completed FASTA record, rejecting records without sequence
+fn finish_fasta_record(
+    (line_number, record): (usize, Option<FastaRecord>),
+    records: &mut Vec<FastaRecord>,
+    report: &mut ParseReport,
+    mode: ErrorMode,
+) -> Result<()> {
+    match record {
+        Some(record) if record.sequence.is_empty() => report.handle(
+            mode,
+            line_number,
+            DataModelError::MissingField(format!("sequence for '{}'", record.id)),
+        ),
+        Some(record) => {
+            records.push(record);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Read GFF3 feature lines, skipping `#` directives and comments
+///
+/// Reading stops at a `##FASTA` directive.
+pub fn read_gff<R: BufRead>(reader: R, mode: ErrorMode) -> Result<(Vec<GffFeature>, ParseReport)> {
+    let mut features = Vec::new();
+    let mut report = ParseReport::new();
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.starts_with("##FASTA") {
+            break;
+        }
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match parse_gff_line(&line) {
+            Ok(feature) => features.push(feature),
+            Err(err) => report.handle(mode, idx + 1, err)?,
+        }
+    }
+
+    Ok((features, report))
+}
+
+/// Parse one tab-separated GFF3 feature line
+fn parse_gff_line(line: &str) -> Result<GffFeature> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != 9 {
+        return Err(DataModelError::ParseError(format!(
+            "expected 9 tab-separated GFF columns, found {}",
+            fields.len()
+        )));
+    }
+
+    let parse_pos = |field: &str, name: &str| {
+        field.parse::<u64>().map_err(|_| {
+            DataModelError::InvalidCoordinate(format!("{} '{}' is not a position", name, field))
+        })
+    };
+    let start = parse_pos(fields[3], "start")?;
+    let end = parse_pos(fields[4], "end")?;
+    if start == 0 || start > end {
+        return Err(DataModelError::InvalidRange { start, end });
+    }
+
+    let score =
+        match fields[5] {
+            "." => None,
+            s => Some(s.parse::<f64>().map_err(|_| {
+                DataModelError::ParseError(format!("score '{}' is not a number", s))
+            })?),
+        };
+    let strand = match fields[6] {
+        "+" => Some(true),
+        "-" => Some(false),
+        "." | "?" => None,
+        s => {
+            return Err(DataModelError::ParseError(format!(
+                "invalid strand '{}'",
+                s
+            )));
+        }
+    };
+    let phase = match fields[7] {
+        "." => None,
+        "0" => Some(0),
+        "1" => Some(1),
+        "2" => Some(2),
+        s => {
+            return Err(DataModelError::ParseError(format!("invalid phase '{}'", s)));
+        }
+    };
+
+    let mut attributes = HashMap::new();
+    for attribute in fields[8].split(';').filter(|a| !a.trim().is_empty()) {
+        let (key, value) = attribute.split_once('=').ok_or_else(|| {
+            DataModelError::ParseError(format!("attribute '{}' is not key=value", attribute))
+        })?;
+        attributes.insert(key.trim().to_string(), value.trim().to_string());
+    }
+
+    Ok(GffFeature {
+        seqid: fields[0].to_string(),
+        source: fields[1].to_string(),
+        feature_type: fields[2].to_string(),
+        start,
+        end,
+        score,
+        strand,
+        phase,
+        attributes,
+    })
+}
+
+/// Split a header into id and optional description
+fn split_header(header: &str) -> Result<(String, Option<String>)> {
+    let header = header.trim();
+    match header.split_once(char::is_whitespace) {
+        Some((id, description)) => Ok((id.to_string(), Some(description.trim().to_string()))),
+        None if !header.is_empty() => Ok((header.to_string(), None)),
+        None => Err(DataModelError::MissingField("record id".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const BAD_FASTQ: &str = "@r1 first\nACGT\n+\nIIII\n\
+                             @r2\nACGT\n+\nIII\n\
+                             r3\nACGT\n+\nIIII\n\
+                             @r4\nACGT\n+\nIIII\n\
+                             @r5\nACXT\n+\nIIII\n";
+
+    #[test]
+    fn test_fastq_collects_all_errors() {
+        let (records, report) = read_fastq(Cursor::new(BAD_FASTQ), ErrorMode::CollectAll).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].description.as_deref(), Some("first"));
+        assert_eq!(records[1].id, "r4");
+        assert_eq!(report.line_numbers(), vec![5, 9, 17]);
+
+        let err = report.into_result().unwrap_err().to_string();
+        assert!(err.contains("line 5:") && err.contains("line 9:") && err.contains("line 17:"));
+    }
+
+    #[test]
+    fn test_fastq_fail_fast() {
+        let err = read_fastq(Cursor::new(BAD_FASTQ), ErrorMode::FailFast).unwrap_err();
+        assert!(err.to_string().contains("line 5:"));
+
+        let (records, report) =
+            read_fastq(Cursor::new("@r1\nACGT\n+\nIIII\n"), ErrorMode::FailFast).unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(report.into_result().is_ok());
+    }
+
+    #[test]
+    fn test_shard_fastq() {
+        let content = "@r1\nACGT\n+\nIIII\n\
+                       @r2\nGGCC\n+r2\nIIII\n\
+                       @r3\nACG\n+\nIII\n";
+        let dir = tempfile::tempdir().unwrap();
+        let path = |i: usize| dir.path().join(format!("shard{}.fastq", i));
+
+        let counts = shard_fastq(Cursor::new(content), 2, |i| {
+            Box::new(std::fs::File::create(path(i)).unwrap())
+        })
+        .unwrap();
+        assert_eq!(counts, vec![2, 1]);
+
+        let shards: Vec<String> = (0..2)
+            .map(|i| std::fs::read_to_string(path(i)).unwrap())
+            .collect();
+        assert_eq!(shards[0], "@r1\nACGT\n+\nIIII\n@r3\nACG\n+\nIII\n");
+        assert_eq!(shards[1], "@r2\nGGCC\n+r2\nIIII\n");
+
+        let mut ids: Vec<String> = shards
+            .iter()
+            .flat_map(|shard| {
+                let (records, _) = read_fastq(Cursor::new(shard), ErrorMode::CollectAll).unwrap();
+                records.into_iter().map(|r| r.id)
+            })
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["r1", "r2", "r3"]);
+    }
+
+    #[test]
+    fn test_shard_fastq_rejects_broken_framing() {
+        let sink = |_| Box::new(std::io::sink()) as Box<dyn Write>;
+        let truncated = "@r1\nACGT\n+\nIIII\n@r2\nACGT\n";
+        let err = shard_fastq(Cursor::new(truncated), 2, sink).unwrap_err();
+        assert!(err.to_string().contains("line 5:"));
+
+        assert!(shard_fastq(Cursor::new("r1\nACGT\n+\nIIII\n"), 2, sink).is_err());
+        assert!(shard_fastq(Cursor::new(""), 0, sink).is_err());
+    }
+
+    #[test]
+    fn test_fasta_collects_all_errors() {
+        let content = "ACGT\n>tx1 gene=A\nACGT\nACGT\n>\nACGT\n>tx2\nAC1T\n>tx3\n>tx4\nGGGG\n";
+        let (records, report) = read_fasta(Cursor::new(content), ErrorMode::CollectAll).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].sequence, "ACGTACGT");
+        assert_eq!(records[0].description.as_deref(), Some("gene=A"));
+        assert_eq!(records[1].id, "tx4");
+        assert_eq!(report.line_numbers(), vec![1, 5, 8, 9]);
+    }
+
+    #[test]
+    fn test_gff_collects_all_errors() {
+        let content = "##gff-version 3\n\
+                       chr1\tsrc\texon\t100\t200\t.\t+\t.\tID=exon1;Parent=tx1\n\
+                       chr1\tsrc\texon\t300\t250\t.\t+\t.\tID=exon2\n\
+                       chr1\tsrc\tCDS\t100\t200\t.\t+\t3\tID=cds1\n\
+                       chr1\tsrc\texon\n\
+                       chr1\tsrc\tCDS\t120\t180\t0.5\t-\t0\tID=cds2\n";
+        let (features, report) = read_gff(Cursor::new(content), ErrorMode::CollectAll).unwrap();
+
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0].attribute("Parent"), Some("tx1"));
+        assert_eq!(features[1].strand, Some(false));
+        assert_eq!(features[1].phase, Some(0));
+        assert_eq!(report.line_numbers(), vec![3, 4, 5]);
+        assert!(read_gff(Cursor::new(content), ErrorMode::FailFast).is_err());
+    }
+
+    #[test]
+    fn test_gff_feature_to_bed() {
+        let content = "chr1\tsrc\texon\t100\t200\t.\t+\t.\tID=exon1\n";
+        let (features, _) = read_gff(Cursor::new(content), ErrorMode::FailFast).unwrap();
+
+        let bed = features[0].interval().unwrap().to_bed();
+        assert_eq!((bed.start(), bed.end()), (99, 200));
+        assert_eq!(bed.to_gff(), features[0].interval().unwrap());
+        assert_eq!(features[0].to_bed_line().unwrap(), "chr1\t99\t200\texon1");
+    }
+}
@@ -0,0 +1,1161 @@
+//! Guide RNA design models
+//!
+//! Spacers are written in target-sense orientation, i.e. as the sequence of
+//! the transcript region they bind, so a target site is found by comparing the
+//! spacer directly against transcript windows. `U` and `T` are equivalent.
+
+use crate::error::{DataModelError, Result};
+use crate::intervals::BedInterval;
+use crate::reads::shannon_entropy;
+use crate::scoring::{DesignThresholds, GuideDesignReport, ScoringContext};
+use crate::sequencing::CompositionProfile;
+use crate::tables::{TableWriter, TsvWriter};
+use crate::targets::{complement, reverse_complement};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::ops::RangeInclusive;
+
+/// Cas13 ortholog a guide is designed for
+///
+/// Orthologs differ in the spacer lengths they load efficiently and in the
+/// spacer GC content associated with good knockdown; each variant carries
+/// built-in defaults for both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CasVariant {
+    /// Leptotrichia wadei Cas13a
+    LwaCas13a,
+    /// Leptotrichia buccalis Cas13a
+    LbuCas13a,
+    /// Prevotella sp. P5-125 Cas13b
+    PspCas13b,
+    /// Ruminococcus flavefaciens XPD3002 Cas13d (CasRx)
+    RfxCas13d,
+}
+
+impl CasVariant {
+    /// Get the recommended spacer GC fraction range
+    pub fn gc_range(&self) -> RangeInclusive<f64> {
+        match self {
+            CasVariant::LwaCas13a | CasVariant::LbuCas13a => 0.30..=0.70,
+            CasVariant::PspCas13b => 0.35..=0.65,
+            CasVariant::RfxCas13d => 0.40..=0.60,
+        }
+    }
+
+    /// Get the supported spacer length range in nucleotides
+    pub fn spacer_len_range(&self) -> RangeInclusive<usize> {
+        match self {
+            CasVariant::LwaCas13a | CasVariant::LbuCas13a => 20..=28,
+            CasVariant::PspCas13b => 20..=30,
+            CasVariant::RfxCas13d => 22..=30,
+        }
+    }
+}
+
+/// A designed guide RNA
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuideRna {
+    /// Guide identifier
+    pub id: String,
+    /// Spacer sequence in target-sense orientation
+    pub spacer: String,
+}
+
+impl GuideRna {
+    /// Create a new guide
+    pub fn new(id: String, spacer: String) -> Self {
+        Self { id, spacer }
+    }
+
+    /// Get the GC fraction of the spacer (0.0 to 1.0)
+    pub fn gc_content(&self) -> f64 {
+        CompositionProfile::from_sequence(&self.spacer).gc_fraction()
+    }
+
+    /// Validate the spacer against the constraints of a Cas variant
+    ///
+    /// The spacer must consist of A, C, G, T/U only and fall within the
+    /// variant's length and GC ranges.
+    pub fn validate(&self, variant: CasVariant) -> Result<()> {
+        if let Some(c) = self
+            .spacer
+            .chars()
+            .find(|c| !matches!(c.to_ascii_uppercase(), 'A' | 'C' | 'G' | 'T' | 'U'))
+        {
+            return Err(DataModelError::InvalidSequence(format!(
+                "guide '{}' has invalid base '{}' in spacer",
+                self.id, c
+            )));
+        }
+
+        let len_range = variant.spacer_len_range();
+        if !len_range.contains(&self.spacer.len()) {
+            return Err(DataModelError::ValidationError(format!(
+                "guide '{}' spacer length {} outside {}-{} nt for {:?}",
+                self.id,
+                self.spacer.len(),
+                len_range.start(),
+                len_range.end(),
+                variant
+            )));
+        }
+
+        let gc = self.gc_content();
+        let gc_range = variant.gc_range();
+        if !gc_range.contains(&gc) {
+            return Err(DataModelError::ValidationError(format!(
+                "guide '{}' GC content {:.2} outside {:.2}-{:.2} for {:?}",
+                self.id,
+                gc,
+                gc_range.start(),
+                gc_range.end(),
+                variant
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Computed design score of a guide
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuideScore {
+    /// Combined score (higher is better)
+    pub score: f64,
+    /// Number of predicted off-target sites
+    pub off_target_count: usize,
+}
+
+/// Heuristic sequence-based guide scorer
+///
+/// Guides failing [`GuideRna::validate`] for the scorer's variant score 0.
+/// Otherwise the score is the product of a GC term, 1.0 at the centre of the
+/// variant's GC range falling to 0.5 at its edges, and a complexity term, the
+/// spacer's Shannon entropy divided by its 2-bit maximum. When
+/// `penalize_self_folding` is set the score is further multiplied by a folding
+/// term, 1.0 for a spacer without a hairpin falling to 0.5 for a perfect one
+/// (see [`self_fold_penalty`]). Scores lie in `0.0..=1.0`. Off-target counts
+/// are not computed and are reported as 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuideScorer {
+    /// Cas ortholog whose constraints are applied
+    pub variant: CasVariant,
+    /// Whether spacers that fold into hairpins are penalized
+    #[serde(default)]
+    pub penalize_self_folding: bool,
+}
+
+impl GuideScorer {
+    /// Create a scorer for a Cas variant
+    pub fn new(variant: CasVariant) -> Self {
+        Self {
+            variant,
+            penalize_self_folding: false,
+        }
+    }
+
+    /// Penalize spacers that fold into hairpins
+    pub fn with_self_fold_penalty(mut self) -> Self {
+        self.penalize_self_folding = true;
+        self
+    }
+
+    /// Score a single guide
+    pub fn score(&self, guide: &GuideRna) -> GuideScore {
+        let score = if guide.validate(self.variant).is_err() {
+            0.0
+        } else {
+            let range = self.variant.gc_range();
+            let mid = (range.start() + range.end()) / 2.0;
+            let half_width = (range.end() - range.start()) / 2.0;
+            let gc_term = 1.0 - 0.5 * (guide.gc_content() - mid).abs() / half_width;
+            let complexity_term = (shannon_entropy(&guide.spacer) / 2.0).min(1.0);
+            let fold_term = if self.penalize_self_folding {
+                1.0 - 0.5 * self_fold_penalty(&guide.spacer)
+            } else {
+                1.0
+            };
+            gc_term * complexity_term * fold_term
+        };
+
+        GuideScore {
+            score,
+            off_target_count: 0,
+        }
+    }
+
+    /// Score a guide using whatever the context provides
+    ///
+    /// Starts from [`GuideScorer::score`] and multiplies in a factor per
+    /// available component, so missing components leave the score unchanged:
+    ///
+    /// - with a transcript and a PFS model, the [`PfsModel::term`] of the base
+    ///   after the guide's exact target site, if the site is found;
+    /// - with an on-target model, its [`OnTargetModel::predict`]ion;
+    /// - with an off-target index, the [`SeedModel`] specificity of every
+    ///   candidate site. One perfect match is taken to be the intended target
+    ///   and skipped; the remaining sites are reported as
+    ///   `off_target_count`.
+    ///
+    /// Guides scoring 0 without context are not looked up further.
+    ///
+    /// [`PfsModel::term`]: crate::scoring::PfsModel::term
+    /// [`OnTargetModel::predict`]: crate::scoring::OnTargetModel::predict
+    /// [`SeedModel`]: crate::offtarget::SeedModel
+    pub fn score_with_context(&self, guide: &GuideRna, context: &ScoringContext) -> GuideScore {
+        let mut result = self.score(guide);
+        if result.score == 0.0 {
+            return result;
+        }
+
+        if let Some(term) = pfs_term(guide, context) {
+            result.score *= term;
+        }
+
+        if let Some(model) = context.on_target_model {
+            result.score *= model.predict(&guide.spacer);
+        }
+
+        if let Some((count, specificity)) = off_target_terms(guide, context) {
+            result.off_target_count = count;
+            result.score *= specificity;
+        }
+
+        result
+    }
+
+    /// Report every quality metric of each guide with a pass/fail verdict
+    ///
+    /// The report's `score` is [`GuideScorer::score_with_context`]; the other
+    /// metrics are the terms it combines, reported unweighted. Context
+    /// metrics are computed for every guide, including ones scoring 0.
+    pub fn design_report(
+        &self,
+        guides: &[GuideRna],
+        context: &ScoringContext,
+        thresholds: &DesignThresholds,
+    ) -> Vec<GuideDesignReport> {
+        let gc_range = thresholds
+            .gc_range
+            .clone()
+            .unwrap_or_else(|| self.variant.gc_range());
+
+        guides
+            .iter()
+            .map(|guide| {
+                let gc = guide.gc_content();
+                let complexity = shannon_entropy(&guide.spacer);
+                let self_fold = self_fold_penalty(&guide.spacer);
+                let on_target = context.on_target_model.map(|m| m.predict(&guide.spacer));
+                let off_target_count = off_target_terms(guide, context).map(|(count, _)| count);
+                let pfs_term = pfs_term(guide, context);
+
+                let checks = [
+                    ("gc", gc_range.contains(&gc)),
+                    ("complexity", complexity >= thresholds.min_complexity),
+                    ("self_fold", self_fold <= thresholds.max_self_fold),
+                    (
+                        "on_target",
+                        !matches!(on_target, Some(p) if p < thresholds.min_on_target),
+                    ),
+                    (
+                        "off_target_count",
+                        !matches!(off_target_count, Some(n) if n > thresholds.max_off_targets),
+                    ),
+                    (
+                        "pfs_term",
+                        !matches!(pfs_term, Some(t) if t < thresholds.min_pfs_term),
+                    ),
+                ];
+                let failures: Vec<String> = checks
+                    .iter()
+                    .filter(|(_, ok)| !ok)
+                    .map(|(metric, _)| metric.to_string())
+                    .collect();
+
+                GuideDesignReport {
+                    guide_id: guide.id.clone(),
+                    spacer: guide.spacer.clone(),
+                    gc,
+                    complexity,
+                    self_fold,
+                    on_target,
+                    off_target_count,
+                    pfs_term,
+                    score: self.score_with_context(guide, context).score,
+                    passed: failures.is_empty(),
+                    failures,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Get the PFS factor of a guide's exact target site, if the context has a
+/// transcript and a PFS model and the site is found
+fn pfs_term(guide: &GuideRna, context: &ScoringContext) -> Option<f64> {
+    let (transcript, pfs) = (context.transcript?, context.pfs_model?);
+    let site = find_target_site(transcript, &guide.spacer, &TargetSearchConfig::default())?;
+    Some(pfs.term(transcript.as_bytes().get(site.end).copied()))
+}
+
+/// Count a guide's off-target sites and multiply their specificities, if
+/// the context has an off-target index
+///
+/// One perfect match is taken to be the intended target and skipped.
+fn off_target_terms(guide: &GuideRna, context: &ScoringContext) -> Option<(usize, f64)> {
+    let index = context.off_target_index?;
+    let seed_model = context.seed_model();
+    let mut skipped_target = false;
+    let (mut count, mut specificity) = (0, 1.0);
+    for site in index.score_candidates(&guide.spacer, &seed_model) {
+        if site.mismatches == 0 && !skipped_target {
+            skipped_target = true;
+            continue;
+        }
+        count += 1;
+        specificity *= site.specificity;
+    }
+    Some((count, specificity))
+}
+
+/// Get the GC fraction of every `window`-base stretch of a spacer
+///
+/// Entry `i` covers bases `i..i + window`. Fails with
+/// [`DataModelError::ValidationError`] if `window` is 0 or longer than the
+/// spacer.
+pub fn local_gc_profile(spacer: &str, window: usize) -> Result<Vec<f64>> {
+    let seq = normalize(spacer);
+    if window == 0 || window > seq.len() {
+        return Err(DataModelError::ValidationError(format!(
+            "GC window of {} bases does not fit a {} nt spacer",
+            window,
+            seq.len()
+        )));
+    }
+    Ok(seq
+        .windows(window)
+        .map(|w| gc_count(w) as f64 / window as f64)
+        .collect())
+}
+
+/// Get the largest difference between a window's GC fraction and the whole
+/// spacer's, from [`local_gc_profile`]
+///
+/// A high value flags a spacer whose overall GC looks acceptable but hides a
+/// GC-rich or AT-rich stretch.
+pub fn max_gc_window_deviation(spacer: &str, window: usize) -> Result<f64> {
+    let profile = local_gc_profile(spacer, window)?;
+    let seq = normalize(spacer);
+    let overall = gc_count(&seq) as f64 / seq.len() as f64;
+    Ok(profile
+        .iter()
+        .map(|gc| (gc - overall).abs())
+        .fold(0.0, f64::max))
+}
+
+/// Count the G and C bases of a normalized sequence
+fn gc_count(seq: &[u8]) -> usize {
+    seq.iter().filter(|&&b| matches!(b, b'G' | b'C')).count()
+}
+
+/// Fewest unpaired bases closing a hairpin loop
+pub const MIN_HAIRPIN_LOOP: usize = 3;
+
+/// Fewest base pairs a stem needs to count towards [`self_fold_penalty`]
+pub const MIN_STEM_LEN: usize = 4;
+
+/// Estimate how strongly a spacer folds back on itself
+///
+/// Finds the most stable hairpin stem: a run of at least [`MIN_STEM_LEN`]
+/// consecutive Watson-Crick pairs between the spacer and its own reverse
+/// complement, closing a loop of at least [`MIN_HAIRPIN_LOOP`] bases. Stability
+/// is counted in hydrogen bonds (3 per G-C pair, 2 per A-U pair) and divided by
+/// the bonds of the longest stem the spacer could form if it were all G-C, so
+/// the penalty lies in `0.0..=1.0` and is 0 when no stem is found.
+pub fn self_fold_penalty(spacer: &str) -> f64 {
+    let seq = normalize(spacer);
+    let n = seq.len();
+    if n < 2 * MIN_STEM_LEN + MIN_HAIRPIN_LOOP {
+        return 0.0;
+    }
+
+    let mut best = 0u32;
+    for i in 0..n {
+        for j in i + 2 * MIN_STEM_LEN + MIN_HAIRPIN_LOOP - 1..n {
+            let max_len = (j - i - MIN_HAIRPIN_LOOP).div_ceil(2);
+            let (len, bonds) = stem(&seq, i, &seq, j, max_len);
+            if len >= MIN_STEM_LEN {
+                best = best.max(bonds);
+            }
+        }
+    }
+
+    let max_bonds = 3 * (n - MIN_HAIRPIN_LOOP) / 2;
+    f64::from(best) / max_bonds as f64
+}
+
+/// Find guide pairs whose spacers could hybridize to each other
+///
+/// Two spacers cross-hybridize when a stretch of one is the reverse
+/// complement of a stretch of the other, the same Watson-Crick pairing
+/// [`self_fold_penalty`] looks for within one spacer. Returns the index pairs
+/// `(i, j)`, `i < j`, whose longest such stretch is more than
+/// `max_cross_complementarity` bases.
+pub fn check_pool_compatibility(
+    guides: &[GuideRna],
+    max_cross_complementarity: usize,
+) -> Vec<(usize, usize)> {
+    let spacers: Vec<Vec<u8>> = guides.iter().map(|g| normalize(&g.spacer)).collect();
+    let mut flagged = Vec::new();
+    for (i, a) in spacers.iter().enumerate() {
+        for (j, b) in spacers.iter().enumerate().skip(i + 1) {
+            if longest_duplex(a, b) > max_cross_complementarity {
+                flagged.push((i, j));
+            }
+        }
+    }
+    flagged
+}
+
+/// Find guides that must be dropped from a library
+///
+/// A guide is flagged when its spacer equals a `blocklist` entry, or when it
+/// contains a `restriction_sites` entry or that site's reverse complement, so
+/// sites are caught on either strand of the cloned oligo. Comparisons ignore
+/// case and treat `U` as `T`. Returns the flagged guide indices in ascending
+/// order.
+pub fn filter_blocklisted(
+    guides: &[GuideRna],
+    blocklist: &[&str],
+    restriction_sites: &[&str],
+) -> Vec<usize> {
+    let blocklist: Vec<Vec<u8>> = blocklist.iter().map(|s| normalize(s)).collect();
+    let sites: Vec<Vec<u8>> = restriction_sites
+        .iter()
+        .filter(|site| !site.is_empty())
+        .flat_map(|site| [normalize(site), normalize(&reverse_complement(site))])
+        .collect();
+
+    guides
+        .iter()
+        .enumerate()
+        .filter(|(_, guide)| {
+            let spacer = normalize(&guide.spacer);
+            blocklist.contains(&spacer)
+                || sites
+                    .iter()
+                    .any(|site| spacer.windows(site.len()).any(|w| w == site.as_slice()))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Get the length of the longest stretch of `a` pairing antiparallel with `b`
+fn longest_duplex(a: &[u8], b: &[u8]) -> usize {
+    let mut best = 0;
+    for i in 0..a.len() {
+        for j in 0..b.len() {
+            let max_len = (a.len() - i).min(j + 1);
+            if max_len > best {
+                best = best.max(stem(a, i, b, j, max_len).0);
+            }
+        }
+    }
+    best
+}
+
+/// Pair `a[i..]` with `b[..=j]` read backwards for as long as the bases are
+/// Watson-Crick complements, up to `max_len` pairs
+///
+/// Returns the number of pairs and their hydrogen bonds (3 per G-C pair, 2
+/// per A-T pair).
+fn stem(a: &[u8], i: usize, b: &[u8], j: usize, max_len: usize) -> (usize, u32) {
+    let mut len = 0;
+    let mut bonds = 0;
+    while len < max_len {
+        let (x, y) = (a[i + len], b[j - len]);
+        if !is_unambiguous(x) || complement(x) != y {
+            break;
+        }
+        bonds += if matches!(x, b'G' | b'C') { 3 } else { 2 };
+        len += 1;
+    }
+    (len, bonds)
+}
+
+/// Tile guides across a transcript, keeping them apart by `min_spacing`
+///
+/// Every `guide_len` window without ambiguous bases is scored as a candidate
+/// spacer. Candidates are then taken greedily from the highest score down
+/// (earliest first on ties), skipping any that would overlap or lie within
+/// `min_spacing` bases of one already chosen. Zero-scoring candidates are
+/// never chosen. Guides are returned in transcript order with ids
+/// `tile_<start>`, where `start` is the 0-based window start.
+pub fn plan_tiling(
+    transcript: &str,
+    guide_len: usize,
+    min_spacing: usize,
+    scorer: &GuideScorer,
+) -> Vec<GuideRna> {
+    if guide_len == 0 || guide_len > transcript.len() {
+        return Vec::new();
+    }
+
+    let mut candidates: Vec<(usize, f64)> = (0..=transcript.len() - guide_len)
+        .filter_map(|start| {
+            let window = transcript.get(start..start + guide_len)?;
+            let guide = GuideRna::new(String::new(), window.to_string());
+            let score = scorer.score(&guide).score;
+            (score > 0.0).then_some((start, score))
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut chosen: Vec<usize> = Vec::new();
+    for (start, _) in candidates {
+        let clear = chosen
+            .iter()
+            .all(|&other| start.abs_diff(other) >= guide_len + min_spacing);
+        if clear {
+            chosen.push(start);
+        }
+    }
+    chosen.sort_unstable();
+
+    chosen
+        .into_iter()
+        .map(|start| {
+            GuideRna::new(
+                format!("tile_{}", start),
+                transcript[start..start + guide_len].to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Write a designed guide library as a TSV table
+///
+/// See [`write_guide_library`] for the layout.
+pub fn write_guide_library_tsv<W: Write>(
+    guides: &[GuideRna],
+    scores: &[GuideScore],
+    writer: &mut W,
+) -> Result<()> {
+    write_guide_library(guides, scores, &mut TsvWriter::new(writer))
+}
+
+/// Write a designed guide library as a table
+///
+/// `scores[i]` annotates `guides[i]`. Each row lists the guide id, spacer, GC
+/// fraction, off-target count, score and rank, where rank 1 is the highest
+/// score and ties keep library order.
+pub fn write_guide_library(
+    guides: &[GuideRna],
+    scores: &[GuideScore],
+    table: &mut dyn TableWriter,
+) -> Result<()> {
+    if guides.len() != scores.len() {
+        return Err(DataModelError::ValidationError(format!(
+            "{} guides but {} scores",
+            guides.len(),
+            scores.len()
+        )));
+    }
+
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[b].score.total_cmp(&scores[a].score));
+    let mut ranks = vec![0; scores.len()];
+    for (rank, &idx) in order.iter().enumerate() {
+        ranks[idx] = rank + 1;
+    }
+
+    table.write_header(&[
+        "guide_id",
+        "spacer",
+        "gc",
+        "off_target_count",
+        "score",
+        "rank",
+    ])?;
+    for ((guide, score), rank) in guides.iter().zip(scores).zip(ranks) {
+        table.write_row(&[
+            guide.id.clone(),
+            guide.spacer.clone(),
+            format!("{:.3}", guide.gc_content()),
+            score.off_target_count.to_string(),
+            format!("{:.4}", score.score),
+            rank.to_string(),
+        ])?;
+    }
+    Ok(())
+}
+
+/// Options for locating a guide's target site in a transcript
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetSearchConfig {
+    /// Maximum mismatches between spacer and target window
+    pub max_mismatches: usize,
+    /// Maximum ambiguous bases (`N` or IUPAC codes) allowed in the window;
+    /// `None` accepts any number
+    pub max_ambiguous: Option<usize>,
+}
+
+impl Default for TargetSearchConfig {
+    fn default() -> Self {
+        Self {
+            max_mismatches: 0,
+            max_ambiguous: Some(2),
+        }
+    }
+}
+
+/// Read a guide library from `guide_id<TAB>spacer` lines
+///
+/// Blank lines and lines starting with `#` are skipped and fields are trimmed.
+/// Spacers must consist of A, C, G, T/U only and guide ids must be unique;
+/// errors name the offending 1-based line.
+pub fn read_guide_library<R: BufRead>(reader: R) -> Result<Vec<GuideRna>> {
+    let mut guides = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = idx + 1;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+        let [id, spacer] = fields[..] else {
+            return Err(DataModelError::ParseError(format!(
+                "line {}: expected guide_id and spacer, found {} field(s)",
+                line_number,
+                fields.len()
+            )));
+        };
+        if id.is_empty() || spacer.is_empty() {
+            return Err(DataModelError::ParseError(format!(
+                "line {}: empty guide_id or spacer",
+                line_number
+            )));
+        }
+        if let Some(c) = spacer
+            .chars()
+            .find(|c| !matches!(c.to_ascii_uppercase(), 'A' | 'C' | 'G' | 'T' | 'U'))
+        {
+            return Err(DataModelError::InvalidSequence(format!(
+                "line {}: guide '{}' has invalid base '{}' in spacer",
+                line_number, id, c
+            )));
+        }
+        if let Some(first) = seen.insert(id.to_string(), line_number) {
+            return Err(DataModelError::ValidationError(format!(
+                "line {}: duplicate guide_id '{}', first seen on line {}",
+                line_number, id, first
+            )));
+        }
+
+        guides.push(GuideRna::new(id.to_string(), spacer.to_string()));
+    }
+    Ok(guides)
+}
+
+/// A transcript window matched by a guide spacer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetSite {
+    /// 0-based start of the window in the transcript
+    pub start: usize,
+    /// 0-based end of the window (exclusive)
+    pub end: usize,
+    /// Mismatches at unambiguous positions
+    pub mismatches: usize,
+    /// Window-relative positions holding ambiguous bases
+    pub ambiguous_positions: Vec<usize>,
+}
+
+impl TargetSite {
+    /// Get the number of ambiguous bases within the window
+    pub fn ambiguous_count(&self) -> usize {
+        self.ambiguous_positions.len()
+    }
+
+    /// Get the length of the window
+    pub fn length(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Get the window as a 0-based half-open interval
+    pub fn interval(&self) -> Result<BedInterval> {
+        BedInterval::new(self.start as u64, self.end as u64)
+    }
+}
+
+/// Group guides whose target sites lie close together on the transcript
+///
+/// `sites[i]` is the target site of `guides[i]`; entries past the shorter of
+/// the two slices are ignored. Guides are linked when their sites start at
+/// most `min_distance` bases apart, and linked guides share a cluster even if
+/// the chain spans further. Clusters are ordered by transcript position and
+/// hold guide indices in ascending order, so callers can keep the best guide
+/// of each.
+pub fn cluster_guides_by_target(
+    guides: &[GuideRna],
+    sites: &[TargetSite],
+    min_distance: usize,
+) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..guides.len().min(sites.len())).collect();
+    order.sort_by_key(|&i| (sites[i].start, i));
+
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    let mut previous_start = None;
+    for i in order {
+        let start = sites[i].start;
+        match (previous_start, clusters.last_mut()) {
+            (Some(previous), Some(cluster)) if start - previous <= min_distance => cluster.push(i),
+            _ => clusters.push(vec![i]),
+        }
+        previous_start = Some(start);
+    }
+    for cluster in &mut clusters {
+        cluster.sort_unstable();
+    }
+    clusters
+}
+
+/// Find the best-matching target site of a spacer in a transcript
+///
+/// Ambiguous transcript bases are reported on the site rather than counted as
+/// mismatches, so a masked region is never silently scored as a perfect match.
+/// Windows with more ambiguous bases than `config.max_ambiguous` are rejected.
+/// The best site has the fewest mismatches, then the fewest ambiguous bases,
+/// then the earliest start.
+pub fn find_target_site(
+    transcript: &str,
+    spacer: &str,
+    config: &TargetSearchConfig,
+) -> Option<TargetSite> {
+    let transcript = normalize(transcript);
+    let spacer = normalize(spacer);
+    if spacer.is_empty() || spacer.len() > transcript.len() {
+        return None;
+    }
+
+    let mut best: Option<TargetSite> = None;
+    for start in 0..=transcript.len() - spacer.len() {
+        let window = &transcript[start..start + spacer.len()];
+        let mut mismatches = 0;
+        let mut ambiguous_positions = Vec::new();
+
+        for (pos, (&t, &s)) in window.iter().zip(&spacer).enumerate() {
+            if !is_unambiguous(t) {
+                ambiguous_positions.push(pos);
+            } else if t != s {
+                mismatches += 1;
+            }
+        }
+
+        if mismatches > config.max_mismatches {
+            continue;
+        }
+        if config
+            .max_ambiguous
+            .is_some_and(|max| ambiguous_positions.len() > max)
+        {
+            continue;
+        }
+
+        let better = match &best {
+            Some(b) => {
+                (mismatches, ambiguous_positions.len()) < (b.mismatches, b.ambiguous_count())
+            }
+            None => true,
+        };
+        if better {
+            best = Some(TargetSite {
+                start,
+                end: start + spacer.len(),
+                mismatches,
+                ambiguous_positions,
+            });
+        }
+    }
+
+    best
+}
+
+/// Upper-case a sequence and convert RNA `U` to `T`
+pub(crate) fn normalize(sequence: &str) -> Vec<u8> {
+    sequence
+        .bytes()
+        .map(|b| match b.to_ascii_uppercase() {
+            b'U' => b'T',
+            other => other,
+        })
+        .collect()
+}
+
+/// Check if a normalized base is one of A, C, G, T
+fn is_unambiguous(base: u8) -> bool {
+    matches!(base, b'A' | b'C' | b'G' | b'T')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::offtarget::OffTargetIndex;
+    use crate::scoring::{OnTargetModel, PfsModel};
+
+    #[test]
+    fn test_filter_blocklisted() {
+        let guide = |id: &str, spacer: &str| GuideRna::new(id.to_string(), spacer.to_string());
+        let guides = vec![
+            guide("clean", "AUGGCAUUCGAUCCAGAUCA"),
+            // BsmBI site CGTCTC as its reverse complement GAGACG
+            guide("bsmbi", "AUUGAGACGUUCAAGCUGAU"),
+            guide("blocked", "GCAUGCAUGCAUGCAUGCAU"),
+            guide("clean2", "UUCAGGAUCCAUGGAUUACA"),
+        ];
+
+        let flagged = filter_blocklisted(&guides, &["gcatgcatgcatgcatgcat"], &["CGTCTC"]);
+        assert_eq!(flagged, vec![1, 2]);
+        assert!(filter_blocklisted(&guides, &[], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_local_gc_profile() {
+        let spacer = "AUAUAUGCGCGCAUAUAUAU";
+        let profile = local_gc_profile(spacer, 6).unwrap();
+
+        assert_eq!(profile.len(), 15);
+        assert_eq!((profile[0], profile[1]), (0.0, 1.0 / 6.0));
+        assert_eq!(profile[6], 1.0);
+
+        // Overall GC is 0.3, the GC-rich window sits 0.7 above it
+        let deviation = max_gc_window_deviation(spacer, 6).unwrap();
+        assert!((deviation - 0.7).abs() < 1e-9);
+        assert_eq!(max_gc_window_deviation("ACGUACGUACGU", 4).unwrap(), 0.0);
+
+        assert!(local_gc_profile(spacer, 21).is_err());
+        assert!(local_gc_profile(spacer, 0).is_err());
+    }
+
+    #[test]
+    fn test_cluster_guides_by_target() {
+        let guides: Vec<GuideRna> = ["g1", "g2", "g3"]
+            .iter()
+            .map(|id| GuideRna::new(id.to_string(), "ACGUACGUACGUACGUACGU".to_string()))
+            .collect();
+        let site = |start: usize| TargetSite {
+            start,
+            end: start + 20,
+            mismatches: 0,
+            ambiguous_positions: Vec::new(),
+        };
+        let sites = [site(500), site(100), site(104)];
+
+        assert_eq!(
+            cluster_guides_by_target(&guides, &sites, 10),
+            vec![vec![1, 2], vec![0]]
+        );
+        assert_eq!(cluster_guides_by_target(&guides, &sites, 3).len(), 3);
+        assert_eq!(
+            cluster_guides_by_target(&guides, &sites, 400),
+            vec![vec![0, 1, 2]]
+        );
+    }
+
+    const SPACER: &str = "ACGUACGGUUCAGCUAGCUA";
+
+    #[test]
+    fn test_validate_gc_range_per_variant() {
+        // 16 of 24 bases are G/C
+        let guide = GuideRna::new("g1".to_string(), "GCGCAUGCGCAUGCGCAUGCAUGC".to_string());
+        assert!((guide.gc_content() - 16.0 / 24.0).abs() < 1e-9);
+
+        assert!(guide.validate(CasVariant::LwaCas13a).is_ok());
+
+        let err = guide.validate(CasVariant::RfxCas13d).unwrap_err();
+        assert!(matches!(err, DataModelError::ValidationError(_)));
+        assert!(err.to_string().contains("GC content"));
+    }
+
+    #[test]
+    fn test_validate_length_and_alphabet() {
+        let short = GuideRna::new("g1".to_string(), "ACGUACGUACGUACGUACGUA".to_string());
+        assert!(short.validate(CasVariant::LwaCas13a).is_ok());
+        assert!(short.validate(CasVariant::RfxCas13d).is_err());
+
+        let masked = GuideRna::new("g2".to_string(), "ACGUACGUACGNACGUACGUACGU".to_string());
+        assert!(matches!(
+            masked.validate(CasVariant::LwaCas13a),
+            Err(DataModelError::InvalidSequence(_))
+        ));
+    }
+
+    #[test]
+    fn test_write_guide_library_tsv() {
+        let guides = vec![
+            GuideRna::new("g1".to_string(), "ACGUACGU".to_string()),
+            GuideRna::new("g2".to_string(), "GGGCAAAU".to_string()),
+        ];
+        let scores = vec![
+            GuideScore {
+                score: 0.5,
+                off_target_count: 3,
+            },
+            GuideScore {
+                score: 0.875,
+                off_target_count: 0,
+            },
+        ];
+
+        let mut out = Vec::new();
+        write_guide_library_tsv(&guides, &scores, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "guide_id\tspacer\tgc\toff_target_count\tscore\trank\n\
+             g1\tACGUACGU\t0.500\t3\t0.5000\t2\n\
+             g2\tGGGCAAAU\t0.500\t0\t0.8750\t1\n"
+        );
+
+        let err = write_guide_library_tsv(&guides, &scores[..1], &mut Vec::new()).unwrap_err();
+        assert!(matches!(err, DataModelError::ValidationError(_)));
+    }
+
+    #[test]
+    fn test_read_guide_library() {
+        let content =
+            "# CD19 tiling library\ng1\tACGUACGUACGUACGUACGUACGU\n\ng2\tggcaaatttggcaaatttggcaaa\n";
+        let guides = read_guide_library(content.as_bytes()).unwrap();
+
+        assert_eq!(
+            guides,
+            vec![
+                GuideRna::new("g1".to_string(), "ACGUACGUACGUACGUACGUACGU".to_string()),
+                GuideRna::new("g2".to_string(), "ggcaaatttggcaaatttggcaaa".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_guide_library_errors() {
+        let duplicate = "g1\tACGUACGU\n# comment\ng1\tGGGCAAAU\n";
+        let err = read_guide_library(duplicate.as_bytes()).unwrap_err();
+        assert!(matches!(err, DataModelError::ValidationError(_)));
+        assert!(err.to_string().contains("line 3"));
+
+        let invalid = "g1\tACGUACGU\ng2\tACGXACGU\n";
+        let err = read_guide_library(invalid.as_bytes()).unwrap_err();
+        assert!(matches!(err, DataModelError::InvalidSequence(_)));
+        assert!(err.to_string().contains("line 2"));
+
+        let err = read_guide_library("g1 ACGUACGU\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, DataModelError::ParseError(_)));
+    }
+
+    #[test]
+    fn test_scorer_prefers_balanced_gc() {
+        let scorer = GuideScorer::new(CasVariant::LwaCas13a);
+        let balanced = GuideRna::new("b".to_string(), "ACGTTGCAAGCTTCGAGCTAGGCT".to_string());
+        let skewed = GuideRna::new("s".to_string(), "GCGCATGCGCATGCGCATGCATGC".to_string());
+        let poly_a = GuideRna::new("a".to_string(), "A".repeat(24));
+
+        assert!(scorer.score(&balanced).score > scorer.score(&skewed).score);
+        assert_eq!(scorer.score(&poly_a).score, 0.0);
+    }
+
+    #[test]
+    fn test_self_fold_penalty() {
+        // GACUGCAA, an 8-base loop, then its reverse complement UUGCAGUC
+        let hairpin = "GACUGCAAAUAAUAAUUUGCAGUC";
+        let open = "ACACACACACACACACACACACAC";
+
+        assert!(self_fold_penalty(hairpin) > 0.5);
+        assert_eq!(self_fold_penalty(open), 0.0);
+        // A three-pair stem is too short to count
+        assert_eq!(self_fold_penalty("AAGCGAAAACGCAA"), 0.0);
+        // Perfect G-C stem with the smallest loop
+        assert_eq!(self_fold_penalty("GGGGCCCAAAGGGCCCC"), 1.0);
+    }
+
+    #[test]
+    fn test_score_with_partial_context() {
+        let scorer = GuideScorer::new(CasVariant::LwaCas13a);
+        let guide = GuideRna::new("g".to_string(), "ACGTTGCAAGCTTCGAGCTAGGCT".to_string());
+        let base = scorer.score(&guide);
+
+        // Nothing set: identical to the context-free score
+        assert_eq!(
+            scorer.score_with_context(&guide, &ScoringContext::new()),
+            base
+        );
+
+        // A PFS model without a transcript has nothing to look at
+        let pfs = PfsModel::default();
+        let no_transcript = ScoringContext::new().with_pfs_model(&pfs);
+        assert_eq!(scorer.score_with_context(&guide, &no_transcript), base);
+
+        // Transcript and PFS set, off-target index and models missing
+        let transcript = "UUACGUUGCAAGCUUCGAGCUAGGCUGAA";
+        let context = ScoringContext::new()
+            .with_transcript(transcript)
+            .with_pfs_model(&pfs);
+        let scored = scorer.score_with_context(&guide, &context);
+        assert_eq!(scored.score, base.score * 0.5);
+        assert_eq!(scored.off_target_count, 0);
+    }
+
+    #[test]
+    fn test_design_report_verdict() {
+        let guides = vec![
+            GuideRna::new("balanced".to_string(), "AUGCCAGUUCAGAUCCGAUA".to_string()),
+            GuideRna::new("at_rich".to_string(), "AUUAGAUUACAUUAAUGAUA".to_string()),
+        ];
+        let scorer = GuideScorer::new(CasVariant::LwaCas13a);
+        let reports = scorer.design_report(&guides, &ScoringContext::new(), &Default::default());
+
+        assert_eq!(reports.len(), 2);
+        assert!(reports[0].passed);
+        assert!((reports[1].gc - 0.15).abs() < 1e-9);
+        assert_eq!(reports[1].failures, ["gc"]);
+        assert!(!reports[1].passed);
+        assert_eq!(reports[1].score, 0.0);
+        for report in &reports {
+            assert_eq!(
+                (report.on_target, report.off_target_count, report.pfs_term),
+                (None, None, None)
+            );
+        }
+
+        // A model predicting 0.5 for every spacer meets the default minimum
+        let model = OnTargetModel {
+            intercept: 0.0,
+            weights: Vec::new(),
+        };
+        let context = ScoringContext::new().with_on_target_model(&model);
+        let reports = scorer.design_report(&guides[..1], &context, &Default::default());
+        assert_eq!(reports[0].on_target, Some(0.5));
+        assert!(reports[0].passed);
+    }
+
+    #[test]
+    fn test_score_with_off_target_index() {
+        let scorer = GuideScorer::new(CasVariant::LwaCas13a);
+        let spacer = "ACGTTGCAAGCTTCGAGCTAGGCT";
+        let guide = GuideRna::new("g".to_string(), spacer.to_string());
+        // The target itself, and a copy with one distal mismatch
+        let reference = [
+            ("target", format!("AA{}AA", spacer)),
+            ("paralog", "ACGTTGCAAGCTTCGAGCTAGGCA".to_string()),
+        ];
+        let index = OffTargetIndex::build(&reference, 8, 8).unwrap();
+
+        let context = ScoringContext::new().with_off_target_index(&index);
+        let scored = scorer.score_with_context(&guide, &context);
+        assert_eq!(scored.off_target_count, 1);
+        assert!(scored.score < scorer.score(&guide).score);
+    }
+
+    #[test]
+    fn test_pool_compatibility() {
+        let guides = [
+            GuideRna::new("a".to_string(), SPACER.to_string()),
+            // Ends with the reverse complement of the first 10 bases of `a`
+            GuideRna::new("b".to_string(), "CCCCACCCACAACCGUACGU".to_string()),
+            GuideRna::new("c".to_string(), "CACACACACACACACACACA".to_string()),
+        ];
+
+        assert_eq!(check_pool_compatibility(&guides, 8), vec![(0, 1)]);
+        assert_eq!(check_pool_compatibility(&guides, 10), Vec::new());
+        assert_eq!(check_pool_compatibility(&guides[..1], 0), Vec::new());
+    }
+
+    #[test]
+    fn test_scorer_self_fold_penalty() {
+        let plain = GuideScorer::new(CasVariant::LwaCas13a);
+        let folding = plain.with_self_fold_penalty();
+        let hairpin = GuideRna::new("h".to_string(), "GACUGCAAAUAAUAAUUUGCAGUC".to_string());
+        let open = GuideRna::new("o".to_string(), "ACGTTGCAAGCTTCGAGCTAGGCT".to_string());
+
+        assert!(folding.score(&hairpin).score < plain.score(&hairpin).score);
+        assert!(folding.score(&open).score > folding.score(&hairpin).score);
+        assert!(plain.score(&hairpin).score > 0.0);
+    }
+
+    #[test]
+    fn test_plan_tiling_respects_spacing() {
+        let transcript = format!(
+            "{}{}{}{}",
+            "ACGTTGCAAGCTTCGAGCTAGGCT",
+            "A".repeat(12),
+            "GCGCATGCGCATGCACATGCATGC",
+            "TTGACCAGTACGATCGATGCAAGT"
+        );
+        let scorer = GuideScorer::new(CasVariant::LwaCas13a);
+        let guides = plan_tiling(&transcript, 24, 4, &scorer);
+
+        assert!(!guides.is_empty());
+        let starts: Vec<usize> = guides
+            .iter()
+            .map(|g| g.id.trim_start_matches("tile_").parse().unwrap())
+            .collect();
+        for pair in starts.windows(2) {
+            assert!(pair[1] - pair[0] >= 24 + 4, "starts {:?}", starts);
+        }
+
+        // The best-scoring window anywhere is always selected
+        let best_start = (0..=transcript.len() - 24)
+            .max_by(|&a, &b| {
+                let score = |s: usize| {
+                    let guide = GuideRna::new(String::new(), transcript[s..s + 24].to_string());
+                    scorer.score(&guide).score
+                };
+                score(a).total_cmp(&score(b)).then(b.cmp(&a))
+            })
+            .unwrap();
+        assert!(starts.contains(&best_start));
+        for guide in &guides {
+            assert!(scorer.score(guide).score > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_clean_site() {
+        let transcript = "GGGGACGTACGGTTCAGCTAGCTAGGGG";
+        let site = find_target_site(transcript, SPACER, &TargetSearchConfig::default()).unwrap();
+
+        assert_eq!(site.start, 4);
+        assert_eq!(site.end, 24);
+        assert_eq!(site.mismatches, 0);
+        assert_eq!(site.ambiguous_count(), 0);
+
+        let gff = site.interval().unwrap().to_gff();
+        assert_eq!((gff.start(), gff.end()), (5, 24));
+    }
+
+    #[test]
+    fn test_site_with_ambiguous_bases_under_limit() {
+        let transcript = "GGGGACGTNCGGTTCAGCTNGCTAGGGG";
+        let site = find_target_site(transcript, SPACER, &TargetSearchConfig::default()).unwrap();
+
+        assert_eq!(site.start, 4);
+        assert_eq!(site.mismatches, 0);
+        assert_eq!(site.ambiguous_positions, vec![4, 15]);
+    }
+
+    #[test]
+    fn test_site_over_ambiguous_limit_rejected() {
+        let transcript = "GGGGACGTNCGGTNCAGCTNGCTAGGGG";
+        let config = TargetSearchConfig::default();
+        assert!(find_target_site(transcript, SPACER, &config).is_none());
+
+        let permissive = TargetSearchConfig {
+            max_ambiguous: None,
+            ..config
+        };
+        let site = find_target_site(transcript, SPACER, &permissive).unwrap();
+        assert_eq!(site.ambiguous_count(), 3);
+    }
+}
@@ -0,0 +1,161 @@
+//! Intervals tagged with their coordinate system
+//!
+//! GFF3 positions are 1-based and closed (`start..=end`), while BED and the
+//! rest of this crate use 0-based half-open positions (`start..end`). Mixing
+//! the two silently shifts features by one base, so every conversion goes
+//! through [`GffInterval::to_bed`] and [`BedInterval::to_gff`]:
+//!
+//! | GFF3 (1-based, closed) | BED (0-based, half-open) |
+//! |------------------------|--------------------------|
+//! | `start`                | `start - 1`              |
+//! | `end`                  | `end`                    |
+//!
+//! Both types reject empty intervals, so the conversions are exact inverses.
+
+use crate::error::{DataModelError, Result};
+use serde::{Deserialize, Serialize};
+
+/// A 1-based interval including both ends, as used by GFF3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GffInterval {
+    start: u64,
+    end: u64,
+}
+
+impl GffInterval {
+    /// Create an interval covering positions `start..=end`
+    ///
+    /// Fails with [`DataModelError::InvalidRange`] when `start` is 0 or after
+    /// `end`.
+    pub fn new(start: u64, end: u64) -> Result<Self> {
+        if start == 0 || start > end {
+            return Err(DataModelError::InvalidRange { start, end });
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Get the first position (1-based, inclusive)
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Get the last position (1-based, inclusive)
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Get the number of bases covered
+    pub fn length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Convert to the equivalent 0-based half-open interval
+    pub fn to_bed(&self) -> BedInterval {
+        BedInterval {
+            start: self.start - 1,
+            end: self.end,
+        }
+    }
+}
+
+impl From<BedInterval> for GffInterval {
+    fn from(interval: BedInterval) -> Self {
+        interval.to_gff()
+    }
+}
+
+/// A 0-based interval excluding its end, as used by BED
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BedInterval {
+    start: u64,
+    end: u64,
+}
+
+impl BedInterval {
+    /// Create an interval covering positions `start..end`
+    ///
+    /// Fails with [`DataModelError::InvalidRange`] when the interval is empty.
+    pub fn new(start: u64, end: u64) -> Result<Self> {
+        if start >= end {
+            return Err(DataModelError::InvalidRange { start, end });
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Get the first position (0-based, inclusive)
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Get the position after the last one (0-based, exclusive)
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Get the number of bases covered
+    pub fn length(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Convert to the equivalent 1-based closed interval
+    pub fn to_gff(&self) -> GffInterval {
+        GffInterval {
+            start: self.start + 1,
+            end: self.end,
+        }
+    }
+
+    /// Format as a tab-separated BED line with an optional name column
+    pub fn to_bed_line(&self, chrom: &str, name: Option<&str>) -> String {
+        match name {
+            Some(name) => format!("{}\t{}\t{}\t{}", chrom, self.start, self.end, name),
+            None => format!("{}\t{}\t{}", chrom, self.start, self.end),
+        }
+    }
+}
+
+impl From<GffInterval> for BedInterval {
+    fn from(interval: GffInterval) -> Self {
+        interval.to_bed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gff_to_bed_round_trip() {
+        let gff = GffInterval::new(100, 250).unwrap();
+        let bed = gff.to_bed();
+
+        assert_eq!((bed.start(), bed.end()), (99, 250));
+        assert_eq!(bed.length(), gff.length());
+        assert_eq!(bed.to_gff(), gff);
+        assert_eq!((bed.to_gff().start(), bed.to_gff().end()), (100, 250));
+
+        let single = GffInterval::new(1, 1).unwrap();
+        assert_eq!((single.to_bed().start(), single.to_bed().end()), (0, 1));
+        assert_eq!(BedInterval::from(single).to_gff(), single);
+    }
+
+    #[test]
+    fn test_rejects_invalid_intervals() {
+        assert!(matches!(
+            GffInterval::new(0, 10),
+            Err(DataModelError::InvalidRange { start: 0, end: 10 })
+        ));
+        assert!(GffInterval::new(11, 10).is_err());
+        assert!(BedInterval::new(10, 10).is_err());
+    }
+
+    #[test]
+    fn test_bed_line() {
+        let bed = BedInterval::new(99, 250).unwrap();
+        assert_eq!(
+            bed.to_bed_line("chr1", Some("exon1")),
+            "chr1\t99\t250\texon1"
+        );
+        assert_eq!(bed.to_bed_line("chr1", None), "chr1\t99\t250");
+    }
+}
@@ -3,11 +3,29 @@
 //! Shared data structures for the CRISPR-Cas13 bioinformatics pipeline.
 //! This crate provides common types used across all pipeline components.
 
+pub mod assembly;
+pub mod counting;
+pub mod coverage;
+pub mod demux;
 pub mod error;
 pub mod expression;
+pub mod formats;
+pub mod guides;
+pub mod intervals;
 pub mod metadata;
+pub mod offtarget;
+pub mod prelude;
+pub mod primers;
+pub mod qc;
+pub mod reads;
+pub mod sample_sheet;
+pub mod scoring;
 pub mod sequencing;
+pub mod sparse;
+pub mod tables;
 pub mod targets;
+pub mod transcripts;
+pub mod translation;
 
 pub use error::{DataModelError, Result};
 
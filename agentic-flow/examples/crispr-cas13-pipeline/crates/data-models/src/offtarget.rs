@@ -0,0 +1,503 @@
+//! Seed k-mer index for fast off-target candidate lookup
+//!
+//! Scanning every guide against a whole transcriptome costs
+//! O(guides × reference length). [`OffTargetIndex`] instead indexes every
+//! k-mer of the reference once, keyed by sequence, so a guide only needs to be
+//! compared against windows that share its seed k-mer exactly.
+//!
+//! # Seed length and false negatives
+//!
+//! A candidate is only reported when the guide's seed region (the
+//! `seed_len` bases starting at `seed_offset` within the spacer) occurs without
+//! mismatches in the reference. Off-target sites carrying a mismatch inside the
+//! seed are therefore never returned. Shorter seeds tolerate fewer of these
+//! misses but return many more candidates to verify; longer seeds are faster
+//! but less sensitive.
+//!
+//! Cas13 is sensitive to mismatches in its seed, so when the index seed is the
+//! seed of the [`SeedModel`] used for scoring, the sites missed this way are
+//! also the least likely to be cleaved. [`OffTargetIndex::build`] takes any
+//! seed and does not check this; build with
+//! [`OffTargetIndex::for_seed_model`] to keep the two in agreement.
+//!
+//! # Scoring candidates
+//!
+//! [`OffTargetIndex::score_candidates`] verifies candidates and weights each
+//! mismatch by position with a [`SeedModel`]: mismatches in the seed disrupt
+//! binding more than distal ones, so they add a larger penalty.
+
+use crate::error::{DataModelError, Result};
+use crate::guides::{self, GuideRna};
+use crate::targets::mismatch_count;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Default seed length in nucleotides
+pub const DEFAULT_SEED_LEN: usize = 8;
+
+/// A reference window sharing a seed k-mer with the queried spacer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Candidate {
+    /// Index of the reference sequence the window lies in
+    pub sequence: usize,
+    /// 0-based start of the spacer-length window in that sequence
+    pub start: usize,
+}
+
+/// Position-dependent mismatch weights for off-target scoring
+///
+/// The seed is the `seed_len` spacer positions starting at the 0-based
+/// `seed_start`. Each mismatch adds `seed_penalty` inside the seed and
+/// `distal_penalty` elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SeedModel {
+    /// 0-based start of the seed within the spacer
+    pub seed_start: usize,
+    /// Seed length in nucleotides
+    pub seed_len: usize,
+    /// Penalty per mismatch inside the seed
+    pub seed_penalty: f64,
+    /// Penalty per mismatch outside the seed
+    pub distal_penalty: f64,
+}
+
+impl Default for SeedModel {
+    /// Central seed (spacer positions 9-16) as reported for Cas13a, with seed
+    /// mismatches weighted four times more than distal ones
+    fn default() -> Self {
+        Self {
+            seed_start: 8,
+            seed_len: 8,
+            seed_penalty: 1.0,
+            distal_penalty: 0.25,
+        }
+    }
+}
+
+impl SeedModel {
+    /// Check if a 0-based spacer position lies in the seed
+    pub fn in_seed(&self, pos: usize) -> bool {
+        (self.seed_start..self.seed_start + self.seed_len).contains(&pos)
+    }
+
+    /// Sum the position-weighted mismatch penalties of a spacer against a
+    /// target window, compared up to the shorter length
+    pub fn penalty(&self, spacer: &str, window: &str) -> f64 {
+        normalize(spacer)
+            .bytes()
+            .zip(normalize(window).bytes())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(pos, _)| {
+                if self.in_seed(pos) {
+                    self.seed_penalty
+                } else {
+                    self.distal_penalty
+                }
+            })
+            .sum()
+    }
+
+    /// Convert a penalty into a specificity score between 0 and 1
+    ///
+    /// A perfect match scores 0 (the site is as likely to be cleaved as the
+    /// target) and the score approaches 1 as the penalty grows.
+    pub fn specificity(&self, penalty: f64) -> f64 {
+        1.0 - (-penalty).exp()
+    }
+}
+
+/// A verified candidate with its position-weighted mismatch penalty
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScoredCandidate {
+    /// Reference window
+    pub candidate: Candidate,
+    /// Unweighted mismatch count
+    pub mismatches: usize,
+    /// Sum of [`SeedModel`] mismatch penalties
+    pub penalty: f64,
+    /// Specificity derived from the penalty; higher is safer
+    pub specificity: f64,
+}
+
+/// Reference sequence stored in the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedSequence {
+    name: String,
+    bases: String,
+}
+
+/// Seed k-mer index over a set of reference sequences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OffTargetIndex {
+    seed_len: usize,
+    seed_offset: usize,
+    sequences: Vec<IndexedSequence>,
+    kmers: HashMap<String, Vec<(usize, usize)>>,
+}
+
+impl OffTargetIndex {
+    /// Build an index over `(name, sequence)` reference pairs
+    ///
+    /// The seed is the `seed_len` bases starting at `seed_offset` within each
+    /// queried spacer. Sequences are upper-cased and `U` is treated as `T`.
+    /// Fails with [`DataModelError::ValidationError`] if a sequence contains
+    /// non-ASCII characters.
+    pub fn build<N: AsRef<str>, S: AsRef<str>>(
+        reference: &[(N, S)],
+        seed_len: usize,
+        seed_offset: usize,
+    ) -> Result<Self> {
+        if seed_len == 0 {
+            return Err(DataModelError::ValidationError(
+                "seed length must be greater than zero".to_string(),
+            ));
+        }
+
+        let mut index = Self {
+            seed_len,
+            seed_offset,
+            sequences: Vec::with_capacity(reference.len()),
+            kmers: HashMap::new(),
+        };
+
+        for (seq_idx, (name, sequence)) in reference.iter().enumerate() {
+            if !sequence.as_ref().is_ascii() {
+                return Err(DataModelError::ValidationError(format!(
+                    "reference sequence '{}' contains non-ASCII characters",
+                    name.as_ref()
+                )));
+            }
+            let bases = normalize(sequence.as_ref());
+            if bases.len() >= seed_len {
+                for pos in 0..=bases.len() - seed_len {
+                    index
+                        .kmers
+                        .entry(bases[pos..pos + seed_len].to_string())
+                        .or_default()
+                        .push((seq_idx, pos));
+                }
+            }
+            index.sequences.push(IndexedSequence {
+                name: name.as_ref().to_string(),
+                bases,
+            });
+        }
+
+        Ok(index)
+    }
+
+    /// Build an index over `(name, sequence)` reference pairs, seeded on the
+    /// seed region of `model`
+    ///
+    /// Equivalent to [`build`](Self::build) with `model.seed_len` and
+    /// `model.seed_start`, so only sites with a mismatch in the seed the
+    /// model penalizes most are missed.
+    pub fn for_seed_model<N: AsRef<str>, S: AsRef<str>>(
+        reference: &[(N, S)],
+        model: &SeedModel,
+    ) -> Result<Self> {
+        Self::build(reference, model.seed_len, model.seed_start)
+    }
+
+    /// Get the seed length in nucleotides
+    pub fn seed_len(&self) -> usize {
+        self.seed_len
+    }
+
+    /// Get the seed start offset within a spacer
+    pub fn seed_offset(&self) -> usize {
+        self.seed_offset
+    }
+
+    /// Get the number of indexed reference sequences
+    pub fn sequence_count(&self) -> usize {
+        self.sequences.len()
+    }
+
+    /// Get the name of an indexed reference sequence
+    pub fn sequence_name(&self, sequence: usize) -> Option<&str> {
+        self.sequences.get(sequence).map(|s| s.name.as_str())
+    }
+
+    /// Find reference windows whose seed region matches the spacer's seed exactly
+    ///
+    /// Returned windows have the spacer's length and lie entirely within their
+    /// reference sequence. Candidates still need full mismatch verification.
+    /// The index holds ASCII only, so a spacer with non-ASCII characters in
+    /// its seed has no candidates.
+    pub fn candidates(&self, spacer: &str) -> Vec<Candidate> {
+        let spacer = normalize(spacer);
+        let seed_end = self.seed_offset + self.seed_len;
+        let Some(hits) = spacer
+            .get(self.seed_offset..seed_end)
+            .and_then(|seed| self.kmers.get(seed))
+        else {
+            return Vec::new();
+        };
+
+        hits.iter()
+            .filter_map(|&(sequence, pos)| {
+                let start = pos.checked_sub(self.seed_offset)?;
+                let len = self.sequences[sequence].bases.len();
+                (start + spacer.len() <= len).then_some(Candidate { sequence, start })
+            })
+            .collect()
+    }
+
+    /// Verify the candidates of a spacer and score them with a seed model
+    ///
+    /// Candidates are returned in index order; sort by `specificity` to rank
+    /// the riskiest sites first.
+    pub fn score_candidates(&self, spacer: &str, model: &SeedModel) -> Vec<ScoredCandidate> {
+        let spacer = normalize(spacer);
+        self.candidates(&spacer)
+            .into_iter()
+            .filter_map(|candidate| {
+                let window = self.window(&candidate, spacer.len())?;
+                let penalty = model.penalty(&spacer, window);
+                Some(ScoredCandidate {
+                    candidate,
+                    mismatches: mismatch_count(spacer.as_bytes(), window.as_bytes()),
+                    penalty,
+                    specificity: model.specificity(penalty),
+                })
+            })
+            .collect()
+    }
+
+    /// Get the reference bases of a candidate window of length `len`
+    pub fn window(&self, candidate: &Candidate, len: usize) -> Option<&str> {
+        self.sequences
+            .get(candidate.sequence)
+            .and_then(|s| s.bases.get(candidate.start..candidate.start + len))
+    }
+}
+
+/// Off-target sites of one guide, bucketed by mismatch count
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OffTargetSummary {
+    /// Guide identifier
+    pub guide_id: String,
+    /// Number of sites with exactly `m` mismatches at index `m`, for
+    /// `m` from 0 to the report's mismatch limit
+    pub counts: Vec<usize>,
+}
+
+impl OffTargetSummary {
+    /// Get the total number of sites within the mismatch limit
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Get the number of sites with exactly `mismatches` mismatches
+    pub fn count(&self, mismatches: usize) -> usize {
+        self.counts.get(mismatches).copied().unwrap_or(0)
+    }
+}
+
+/// Summarize each guide's off-target sites across the indexed reference
+///
+/// Every candidate window sharing the guide's seed is verified and counted in
+/// the bucket of its mismatch count if it has at most `max_mismatches`. The
+/// seed limitation described in the module docs applies, and an intended
+/// target present in the reference is counted as a 0-mismatch site.
+pub fn off_target_report(
+    guides: &[GuideRna],
+    index: &OffTargetIndex,
+    max_mismatches: usize,
+) -> Vec<OffTargetSummary> {
+    guides
+        .iter()
+        .map(|guide| {
+            let spacer = normalize(&guide.spacer);
+            let mut counts = vec![0; max_mismatches + 1];
+            for candidate in index.candidates(&spacer) {
+                if let Some(window) = index.window(&candidate, spacer.len()) {
+                    let mismatches = mismatch_count(spacer.as_bytes(), window.as_bytes());
+                    if let Some(count) = counts.get_mut(mismatches) {
+                        *count += 1;
+                    }
+                }
+            }
+            OffTargetSummary {
+                guide_id: guide.id.clone(),
+                counts,
+            }
+        })
+        .collect()
+}
+
+/// [`guides::normalize`] a sequence into a `String`
+fn normalize(sequence: &str) -> String {
+    String::from_utf8_lossy(&guides::normalize(sequence)).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::targets::hamming_distance;
+
+    #[test]
+    fn test_candidates_include_true_off_target() {
+        let spacer = "ACGTACGGTTCAGCTAGCTA";
+        // One mismatch outside the seed (last base)
+        let off_target = "ACGTACGGTTCAGCTAGCTT";
+        let reference = vec![
+            ("tx1", format!("GGGGGGGGGG{}CCCCCCCCCC", off_target)),
+            ("tx2", "TTTTTTTTTTTTTTTTTTTTTTTTTTTTTT".to_string()),
+        ];
+
+        let index = OffTargetIndex::build(&reference, DEFAULT_SEED_LEN, 0).unwrap();
+        let candidates = index.candidates(spacer);
+
+        assert_eq!(
+            candidates,
+            vec![Candidate {
+                sequence: 0,
+                start: 10
+            }]
+        );
+        let window = index.window(&candidates[0], spacer.len()).unwrap();
+        assert_eq!(hamming_distance(spacer, window).unwrap(), 1);
+        assert_eq!(index.sequence_name(0), Some("tx1"));
+    }
+
+    #[test]
+    fn test_non_ascii_input() {
+        let reference = vec![("tx1", "ACGTÅCGTACGT")];
+        assert!(matches!(
+            OffTargetIndex::build(&reference, 4, 0),
+            Err(DataModelError::ValidationError(_))
+        ));
+
+        let reference = vec![("tx1", "TTACGTACGTACGTTT")];
+        let index = OffTargetIndex::build(&reference, 4, 2).unwrap();
+        assert!(!index.candidates("AAACGTAC").is_empty());
+        // Seeds cut through, or made of, multi-byte characters
+        assert!(index.candidates("AÅCGTAC").is_empty());
+        assert!(index.candidates("AAÅÅ").is_empty());
+        assert!(index
+            .score_candidates("AAACGÅ", &SeedModel::default())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_candidates_skip_unrelated_regions() {
+        let reference = vec![("tx1", "TTTTTTTTTTGGGGGGGGGGTTTTTTTTTT")];
+        let index = OffTargetIndex::build(&reference, DEFAULT_SEED_LEN, 0).unwrap();
+
+        assert!(index.candidates("ACGTACGGTTCAGCTAGCTA").is_empty());
+    }
+
+    #[test]
+    fn test_seed_offset_and_rna_input() {
+        let spacer = "AAAAAAACGUACGUAAAAAA";
+        let reference = vec![("tx1", "CCCCCCACGTACGTCCCCCC")];
+        let index = OffTargetIndex::build(&reference, 8, 6).unwrap();
+
+        let candidates = index.candidates(spacer);
+        assert_eq!(
+            candidates,
+            vec![Candidate {
+                sequence: 0,
+                start: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_index_for_seed_model() {
+        let model = SeedModel::default();
+        let spacer = "ACGTACGGTTCAGCTAGCTA";
+        let reference = vec![
+            // Mismatch before the seed, missed by an index seeded at offset 0
+            ("proximal", "GGGGGACCTACGGTTCAGCTAGCTAGGGGG"),
+            // Mismatch inside the seed
+            ("seed", "GGGGGACGTACGGTTGAGCTAGCTAGGGGG"),
+        ];
+        let index = OffTargetIndex::for_seed_model(&reference, &model).unwrap();
+        assert_eq!((index.seed_offset(), index.seed_len()), (8, 8));
+
+        let candidates = index.candidates(spacer);
+        assert_eq!(
+            candidates,
+            vec![Candidate {
+                sequence: 0,
+                start: 5
+            }]
+        );
+        // An index seeded elsewhere finds the seed mismatch instead
+        let offset_zero = OffTargetIndex::build(&reference, DEFAULT_SEED_LEN, 0).unwrap();
+        assert_eq!(
+            offset_zero.candidates(spacer),
+            vec![Candidate {
+                sequence: 1,
+                start: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn test_zero_seed_len_rejected() {
+        let reference = vec![("tx1", "ACGT")];
+        assert!(OffTargetIndex::build(&reference, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_off_target_report_buckets() {
+        let perfect = "ACGTACGGTTCAGCTAGCTA";
+        let one_mismatch = "ACGTACGGTTCAGCTAGCTT";
+        let reference = vec![
+            ("tx1", format!("GGGGG{}CCCCC{}GGGGG", perfect, one_mismatch)),
+            ("tx2", format!("TTTTT{}TTTTT", perfect)),
+        ];
+        let index = OffTargetIndex::build(&reference, DEFAULT_SEED_LEN, 0).unwrap();
+        let guides = vec![
+            GuideRna::new("hit".to_string(), "ACGUACGGUUCAGCUAGCUA".to_string()),
+            GuideRna::new("clean".to_string(), "CATCATCATCATCATCATCA".to_string()),
+        ];
+
+        let report = off_target_report(&guides, &index, 2);
+
+        assert_eq!(report[0].guide_id, "hit");
+        assert_eq!(report[0].counts, vec![2, 1, 0]);
+        assert_eq!(report[0].total(), 3);
+        assert_eq!(report[1].guide_id, "clean");
+        assert_eq!(report[1].counts, vec![0, 0, 0]);
+        assert_eq!(report[1].count(5), 0);
+    }
+
+    #[test]
+    fn test_seed_mismatch_penalized_more() {
+        let model = SeedModel::default();
+        let spacer = "ACGTACGGTTCAGCTAGCTA";
+        let seed_mismatch = "ACGTACGGTTGAGCTAGCTA";
+        let distal_mismatch = "ACGTACGGTTCAGCTAGCTT";
+
+        assert!(model.in_seed(10));
+        assert!(!model.in_seed(19));
+        assert_eq!(model.penalty(spacer, seed_mismatch), 1.0);
+        assert_eq!(model.penalty(spacer, distal_mismatch), 0.25);
+
+        let reference = vec![
+            ("seed", format!("GGGGG{}GGGGG", seed_mismatch)),
+            ("distal", format!("GGGGG{}GGGGG", distal_mismatch)),
+        ];
+        let index = OffTargetIndex::build(&reference, DEFAULT_SEED_LEN, 0).unwrap();
+        let scored = index.score_candidates(spacer, &model);
+
+        assert_eq!(scored.len(), 2);
+        let by_sequence = |name: &str| {
+            scored
+                .iter()
+                .find(|s| index.sequence_name(s.candidate.sequence) == Some(name))
+                .unwrap()
+        };
+        let (seed, distal) = (by_sequence("seed"), by_sequence("distal"));
+        assert_eq!(seed.mismatches, distal.mismatches);
+        assert!(seed.penalty > distal.penalty);
+        assert!(seed.specificity > distal.specificity);
+        assert_eq!(model.specificity(0.0), 0.0);
+    }
+}
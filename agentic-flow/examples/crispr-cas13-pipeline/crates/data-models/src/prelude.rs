@@ -0,0 +1,75 @@
+//! Convenience re-exports of the most commonly used types
+//!
+//! ```
+//! use data_models::prelude::*;
+//! ```
+
+pub use crate::assembly::{assembly_stats, AssemblyStats};
+pub use crate::counting::GuideCounter;
+pub use crate::coverage::CoverageTrack;
+pub use crate::demux::{
+    parse_illumina_header, BarcodeMatch, BarcodeMatcher, ReadHeaderInfo, TenXRead, TenXReader,
+};
+pub use crate::error::{DataModelError, ErrorMode, ParseReport, Result};
+pub use crate::expression::{
+    CorrelationMethod, CountMatrixFormat, DifferentialExpression, ExpressionData, ExpressionMatrix,
+    ExpressionSample, GeneAnnotation, SampleMetadata,
+};
+pub use crate::formats::{read_fasta, read_fastq, read_gff, GffFeature};
+pub use crate::guides::{CasVariant, GuideRna, GuideScore, GuideScorer, TargetSite};
+pub use crate::intervals::{BedInterval, GffInterval};
+pub use crate::metadata::{Experiment, PipelineRun, Sample};
+pub use crate::offtarget::{off_target_report, OffTargetIndex, OffTargetSummary, SeedModel};
+pub use crate::primers::{design_primers, PrimerPair};
+pub use crate::qc::{QcGate, QcStatus, QcVerdict, SampleQcStats};
+pub use crate::reads::ConsensusBuilder;
+pub use crate::sample_sheet::SampleSheet;
+pub use crate::scoring::{
+    DesignThresholds, GuideDesignReport, OnTargetModel, PfsModel, ScoringContext,
+};
+pub use crate::sequencing::{
+    detect_phred_encoding, AlignedRead, CompositionProfile, FastaRecord, FastqRecord,
+    GenomicCoordinate, PhredEncoding, SeqQual,
+};
+pub use crate::sparse::SparseExpressionMatrix;
+pub use crate::tables::{CsvWriter, JsonLinesWriter, TableWriter, TsvWriter};
+pub use crate::targets::{CrisprTarget, OffTargetSite, TmMethod};
+pub use crate::transcripts::{
+    validate_target_placement, CdsBoundary, CdsProximity, TargetPlacement, TranscriptModel,
+};
+pub use crate::translation::{translate, CodonTable};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_position(field: &str) -> Result<GenomicCoordinate> {
+        let (start, end) = field
+            .split_once('-')
+            .ok_or_else(|| DataModelError::ParseError(format!("no range in '{}'", field)))?;
+        GenomicCoordinate::new(0, start.parse()?, end.parse()?, true)
+    }
+
+    fn parse_score(bytes: &[u8]) -> Result<f64> {
+        Ok(std::str::from_utf8(bytes)?.trim().parse::<f64>()?)
+    }
+
+    #[test]
+    fn test_prelude_error_propagation() {
+        assert_eq!(parse_position("100-250").unwrap().length(), 150);
+        assert!(matches!(
+            parse_position("100-abc"),
+            Err(DataModelError::ParseError(_))
+        ));
+
+        assert_eq!(parse_score(b" 0.75 ").unwrap(), 0.75);
+        assert!(matches!(
+            parse_score(b"high"),
+            Err(DataModelError::ParseError(_))
+        ));
+        assert!(matches!(
+            parse_score(&[0xff, 0xfe]),
+            Err(DataModelError::ParseError(_))
+        ));
+    }
+}
@@ -0,0 +1,205 @@
+//! PCR primer design for validating guide target sites
+//!
+//! A target site is validated by amplifying the transcript region around it
+//! and sequencing the amplicon. [`design_primers`] picks a forward primer
+//! upstream of the site and a reverse primer downstream of it so that the
+//! amplicon covers the whole site.
+
+use crate::error::{DataModelError, Result};
+use crate::guides::TargetSite;
+use crate::sequencing::CompositionProfile;
+use crate::targets::{melting_temp, reverse_complement, TmMethod};
+use serde::{Deserialize, Serialize};
+use std::ops::{Range, RangeInclusive};
+
+/// Allowed primer lengths in nucleotides
+pub const PRIMER_LEN: RangeInclusive<usize> = 18..=25;
+
+/// Allowed primer GC fraction
+pub const PRIMER_GC: RangeInclusive<f64> = 0.40..=0.60;
+
+/// Largest accepted difference between a primer's Tm and the target, in °C
+pub const PRIMER_TM_TOLERANCE: f64 = 3.0;
+
+/// Forward and reverse primers flanking a target site
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrimerPair {
+    /// Forward primer, identical to the transcript at `forward_start`
+    pub forward: String,
+    /// Reverse primer, 5'→3', the reverse complement of the transcript
+    /// ending at `reverse_end`
+    pub reverse: String,
+    /// 0-based start of the forward primer, and of the amplicon
+    pub forward_start: usize,
+    /// 0-based end (exclusive) of the reverse primer's binding site, and of
+    /// the amplicon
+    pub reverse_end: usize,
+    /// Melting temperature of the forward primer in °C
+    pub forward_tm: f64,
+    /// Melting temperature of the reverse primer in °C
+    pub reverse_tm: f64,
+}
+
+impl PrimerPair {
+    /// Get the amplicon length in nucleotides
+    pub fn amplicon_len(&self) -> usize {
+        self.reverse_end - self.forward_start
+    }
+}
+
+/// A primer binding window that passed the Tm and GC constraints
+struct Candidate {
+    start: usize,
+    end: usize,
+    tm: f64,
+}
+
+/// Design a primer pair amplifying the region around a target site
+///
+/// Forward primers are taken from transcript windows ending at or before
+/// `site.start`, reverse primers from windows starting at or after `site.end`.
+/// Both must be [`PRIMER_LEN`] long, have a GC fraction within [`PRIMER_GC`]
+/// and a nearest-neighbor Tm (default [`TmMethod`]) within
+/// [`PRIMER_TM_TOLERANCE`] of `tm_target`; windows with ambiguous bases are
+/// skipped. Among pairs whose amplicon length lies in `amplicon_size`, the one
+/// with the smallest total Tm deviation is returned, preferring shorter
+/// amplicons on ties.
+///
+/// Fails with [`DataModelError::ValidationError`] if the site lies outside
+/// the transcript or no pair satisfies the constraints.
+pub fn design_primers(
+    transcript: &str,
+    site: &TargetSite,
+    amplicon_size: Range<usize>,
+    tm_target: f64,
+) -> Result<PrimerPair> {
+    let seq = transcript.to_ascii_uppercase().replace('U', "T");
+    if site.start >= site.end || site.end > seq.len() {
+        return Err(DataModelError::ValidationError(format!(
+            "target site {}..{} lies outside a {} nt transcript",
+            site.start,
+            site.end,
+            seq.len()
+        )));
+    }
+
+    let forward = candidates(&seq, 0, site.start, tm_target);
+    let reverse = candidates(&seq, site.end, seq.len(), tm_target);
+
+    let mut best: Option<(f64, usize, &Candidate, &Candidate)> = None;
+    for f in &forward {
+        for r in &reverse {
+            let amplicon = r.end - f.start;
+            if !amplicon_size.contains(&amplicon) {
+                continue;
+            }
+            let deviation = (f.tm - tm_target).abs() + (r.tm - tm_target).abs();
+            let better = match best {
+                Some((d, len, _, _)) => deviation < d || (deviation == d && amplicon < len),
+                None => true,
+            };
+            if better {
+                best = Some((deviation, amplicon, f, r));
+            }
+        }
+    }
+
+    let (_, _, f, r) = best.ok_or_else(|| {
+        DataModelError::ValidationError(format!(
+            "no primer pair with Tm {:.1}±{:.1} °C gives a {}..{} nt amplicon around {}..{}",
+            tm_target,
+            PRIMER_TM_TOLERANCE,
+            amplicon_size.start,
+            amplicon_size.end,
+            site.start,
+            site.end
+        ))
+    })?;
+    Ok(PrimerPair {
+        forward: seq[f.start..f.end].to_string(),
+        reverse: reverse_complement(&seq[r.start..r.end]),
+        forward_start: f.start,
+        reverse_end: r.end,
+        forward_tm: f.tm,
+        reverse_tm: r.tm,
+    })
+}
+
+/// Collect the primer windows within `seq[from..to]` meeting the constraints
+fn candidates(seq: &str, from: usize, to: usize, tm_target: f64) -> Vec<Candidate> {
+    let mut found = Vec::new();
+    for start in from..to {
+        for len in PRIMER_LEN {
+            let end = start + len;
+            if end > to {
+                break;
+            }
+            let window = &seq[start..end];
+            let gc = CompositionProfile::from_sequence(window).gc_fraction();
+            if !PRIMER_GC.contains(&gc) {
+                continue;
+            }
+            // Ambiguous bases make melting_temp fail; such windows are skipped
+            let Ok(tm) = melting_temp(window, TmMethod::default()) else {
+                continue;
+            };
+            if (tm - tm_target).abs() <= PRIMER_TM_TOLERANCE {
+                found.push(Candidate { start, end, tm });
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSCRIPT: &str = concat!(
+        "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACTTGTTGGCCCAGTGTGAATCG",
+        "CTTAAGGGTTAAGTAAGTGTGATGCATACGCCTTTACTTGCTGTGTCCACCCCATCGGAC",
+        "TGGCATTTTTATTACACTCAGAAACAGAACTCGGGTAATTTTGACAGGTCACGCAGAGGC",
+    );
+
+    fn site(start: usize, end: usize) -> TargetSite {
+        TargetSite {
+            start,
+            end,
+            mismatches: 0,
+            ambiguous_positions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_design_valid_pair() {
+        let site = site(80, 100);
+        let pair = design_primers(TRANSCRIPT, &site, 100..160, 58.0).unwrap();
+
+        assert!(pair.forward_start + pair.forward.len() <= site.start);
+        assert!(pair.reverse_end - pair.reverse.len() >= site.end);
+        assert!((100..160).contains(&pair.amplicon_len()));
+        assert!(TRANSCRIPT[pair.forward_start..].starts_with(&pair.forward));
+        assert_eq!(
+            reverse_complement(&pair.reverse),
+            TRANSCRIPT[pair.reverse_end - pair.reverse.len()..pair.reverse_end]
+        );
+        for (primer, tm) in [
+            (&pair.forward, pair.forward_tm),
+            (&pair.reverse, pair.reverse_tm),
+        ] {
+            assert!(PRIMER_LEN.contains(&primer.len()));
+            assert!((tm - 58.0).abs() <= PRIMER_TM_TOLERANCE);
+            assert!((melting_temp(primer, TmMethod::default()).unwrap() - tm).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_transcript_too_short_for_amplicon() {
+        let short = &TRANSCRIPT[..60];
+        let err = design_primers(short, &site(25, 35), 100..160, 58.0).unwrap_err();
+        assert!(matches!(err, DataModelError::ValidationError(_)));
+
+        let outside = design_primers(short, &site(50, 70), 20..60, 58.0).unwrap_err();
+        assert!(outside.to_string().contains("outside"));
+    }
+}
@@ -0,0 +1,248 @@
+//! Sample-level quality control gate
+//!
+//! A [`QcGate`] turns a sample's [`SampleQcStats`] into a [`QcVerdict`]:
+//! each metric is compared with a failure limit and a stricter warning
+//! limit, and the sample's status is the worst of its metrics. Gates
+//! deserialize with missing fields taken from [`QcGate::default`], so a
+//! config file only needs the limits it changes.
+
+use crate::sequencing::FastqRecord;
+use serde::{Deserialize, Serialize};
+
+/// Lowest Phred score counted towards [`SampleQcStats::q30_fraction`]
+pub const Q30: u8 = 30;
+
+/// Metrics a [`QcGate`] decides on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleQcStats {
+    /// Sample name
+    pub sample: String,
+    /// Number of reads sequenced
+    pub read_count: u64,
+    /// Fraction of bases called at Q30 or above
+    pub q30_fraction: f64,
+    /// Number of genes with a non-zero count
+    pub detected_genes: usize,
+}
+
+impl SampleQcStats {
+    /// Compute read count and Q30 fraction from Phred+33 reads
+    ///
+    /// The gene count comes from quantification, e.g.
+    /// [`ExpressionSample::detected_genes`]. Without bases the Q30 fraction
+    /// is 0.
+    ///
+    /// [`ExpressionSample::detected_genes`]: crate::expression::ExpressionSample::detected_genes
+    pub fn from_reads(sample: String, reads: &[FastqRecord], detected_genes: usize) -> Self {
+        let bases: usize = reads.iter().map(|r| r.quality().len()).sum();
+        let q30: usize = reads
+            .iter()
+            .map(|r| {
+                r.quality()
+                    .iter()
+                    .filter(|&&q| q.saturating_sub(33) >= Q30)
+                    .count()
+            })
+            .sum();
+        Self {
+            sample,
+            read_count: reads.len() as u64,
+            q30_fraction: if bases == 0 {
+                0.0
+            } else {
+                q30 as f64 / bases as f64
+            },
+            detected_genes,
+        }
+    }
+}
+
+/// Outcome of a QC check, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum QcStatus {
+    /// Every metric is at or above its warning limit
+    Pass,
+    /// Some metric is below its warning limit but none below its failure
+    /// limit
+    Warn,
+    /// Some metric is below its failure limit
+    Fail,
+}
+
+/// Status of a sample and the metrics that caused it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QcVerdict {
+    /// Worst status over all metrics
+    pub status: QcStatus,
+    /// Names of the metrics below their failure limit, in field order
+    pub failures: Vec<String>,
+    /// Names of the metrics below their warning limit only, in field order
+    pub warnings: Vec<String>,
+}
+
+impl QcVerdict {
+    /// Check if the sample is usable, possibly with warnings
+    pub fn is_usable(&self) -> bool {
+        self.status != QcStatus::Fail
+    }
+}
+
+/// Thresholds deciding whether a sample is usable
+///
+/// A metric below its `min_` limit fails the sample and one below its
+/// `warn_` limit flags it; warning limits should be at least the failure
+/// limits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QcGate {
+    /// Fewest reads accepted
+    pub min_read_count: u64,
+    /// Fewest reads accepted without a warning
+    pub warn_read_count: u64,
+    /// Lowest Q30 fraction accepted
+    pub min_q30_fraction: f64,
+    /// Lowest Q30 fraction accepted without a warning
+    pub warn_q30_fraction: f64,
+    /// Fewest detected genes accepted
+    pub min_detected_genes: usize,
+    /// Fewest detected genes accepted without a warning
+    pub warn_detected_genes: usize,
+}
+
+impl Default for QcGate {
+    fn default() -> Self {
+        Self {
+            min_read_count: 1_000_000,
+            warn_read_count: 5_000_000,
+            min_q30_fraction: 0.75,
+            warn_q30_fraction: 0.8,
+            min_detected_genes: 5_000,
+            warn_detected_genes: 10_000,
+        }
+    }
+}
+
+impl QcGate {
+    /// Evaluate a sample against the thresholds
+    ///
+    /// A NaN Q30 fraction fails.
+    pub fn evaluate(&self, stats: &SampleQcStats) -> QcVerdict {
+        let checks = [
+            (
+                "read_count",
+                stats.read_count >= self.min_read_count,
+                stats.read_count >= self.warn_read_count,
+            ),
+            (
+                "q30_fraction",
+                stats.q30_fraction >= self.min_q30_fraction,
+                stats.q30_fraction >= self.warn_q30_fraction,
+            ),
+            (
+                "detected_genes",
+                stats.detected_genes >= self.min_detected_genes,
+                stats.detected_genes >= self.warn_detected_genes,
+            ),
+        ];
+
+        let mut failures = Vec::new();
+        let mut warnings = Vec::new();
+        for (metric, passes, clear) in checks {
+            if !passes {
+                failures.push(metric.to_string());
+            } else if !clear {
+                warnings.push(metric.to_string());
+            }
+        }
+
+        let status = if !failures.is_empty() {
+            QcStatus::Fail
+        } else if !warnings.is_empty() {
+            QcStatus::Warn
+        } else {
+            QcStatus::Pass
+        };
+        QcVerdict {
+            status,
+            failures,
+            warnings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(read_count: u64, q30_fraction: f64, detected_genes: usize) -> SampleQcStats {
+        SampleQcStats {
+            sample: "S1".to_string(),
+            read_count,
+            q30_fraction,
+            detected_genes,
+        }
+    }
+
+    #[test]
+    fn test_passing_sample() {
+        let verdict = QcGate::default().evaluate(&stats(20_000_000, 0.92, 14_000));
+        assert_eq!(verdict.status, QcStatus::Pass);
+        assert!(verdict.failures.is_empty() && verdict.warnings.is_empty());
+        assert!(verdict.is_usable());
+    }
+
+    #[test]
+    fn test_failing_on_q30() {
+        let verdict = QcGate::default().evaluate(&stats(20_000_000, 0.6, 14_000));
+        assert_eq!(verdict.status, QcStatus::Fail);
+        assert_eq!(verdict.failures, ["q30_fraction"]);
+        assert!(!verdict.is_usable());
+
+        let nan = QcGate::default().evaluate(&stats(20_000_000, f64::NAN, 14_000));
+        assert_eq!(nan.failures, ["q30_fraction"]);
+    }
+
+    #[test]
+    fn test_warning_on_borderline_read_count() {
+        let verdict = QcGate::default().evaluate(&stats(3_000_000, 0.92, 14_000));
+        assert_eq!(verdict.status, QcStatus::Warn);
+        assert!(verdict.failures.is_empty());
+        assert_eq!(verdict.warnings, ["read_count"]);
+        assert!(verdict.is_usable());
+
+        // A failure outranks warnings on other metrics
+        let verdict = QcGate::default().evaluate(&stats(3_000_000, 0.92, 100));
+        assert_eq!(verdict.status, QcStatus::Fail);
+        assert_eq!(verdict.failures, ["detected_genes"]);
+        assert_eq!(verdict.warnings, ["read_count"]);
+    }
+
+    #[test]
+    fn test_gate_from_partial_config() {
+        let gate: QcGate =
+            serde_json::from_str(r#"{"min_read_count": 100, "warn_read_count": 500}"#).unwrap();
+        assert_eq!(gate.min_read_count, 100);
+        assert_eq!(gate.min_q30_fraction, QcGate::default().min_q30_fraction);
+        assert_eq!(
+            gate.evaluate(&stats(400, 0.92, 14_000)).warnings,
+            ["read_count"]
+        );
+    }
+
+    #[test]
+    fn test_stats_from_reads() {
+        let read = |quality: &[u8]| {
+            FastqRecord::new("r".to_string(), "ACGT".to_string(), quality.to_vec()).unwrap()
+        };
+        // '?' is Q30, '>' is Q29
+        let reads = [read(b"????"), read(b"??>>")];
+        let stats = SampleQcStats::from_reads("S1".to_string(), &reads, 12);
+        assert_eq!(stats.read_count, 2);
+        assert_eq!(stats.q30_fraction, 0.75);
+        assert_eq!(stats.detected_genes, 12);
+        assert_eq!(
+            SampleQcStats::from_reads("S2".to_string(), &[], 0).q30_fraction,
+            0.0
+        );
+    }
+}
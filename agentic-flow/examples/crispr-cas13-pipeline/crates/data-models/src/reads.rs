@@ -0,0 +1,792 @@
+//! Read-level processing utilities
+//!
+//! These helpers operate on streams of [`FastqRecord`]s and are designed to be
+//! chained as iterator adapters between a reader and downstream analysis.
+
+use crate::error::{DataModelError, Result};
+use crate::guides::normalize;
+use crate::sequencing::{FastqRecord, SeqQual};
+use crate::targets::{complement, mismatch_count, reverse_complement};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+
+/// ASCII offset of Phred+33 quality encoding
+const PHRED_OFFSET: u8 = 33;
+
+/// Highest Phred score emitted for derived qualities
+const MAX_PHRED: u8 = 60;
+
+/// Shortest 3' G run removed by [`trim_poly_g`], matching fastp's default
+pub const POLY_G_MIN_LEN: usize = 10;
+
+/// Bases tallied when building a consensus
+const CONSENSUS_BASES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+/// Deterministically keep each read with probability `fraction`
+///
+/// Uses a seeded ChaCha RNG, so the same input order, `fraction` and `seed`
+/// always select the same subset regardless of platform.
+pub fn subsample(
+    reads: impl Iterator<Item = FastqRecord>,
+    fraction: f64,
+    seed: u64,
+) -> Result<impl Iterator<Item = FastqRecord>> {
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err(DataModelError::ValidationError(format!(
+            "subsample fraction must be between 0 and 1, got {}",
+            fraction
+        )));
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    Ok(reads.filter(move |_| rng.gen_bool(fraction)))
+}
+
+/// Compute the per-base Shannon entropy of a sequence in bits
+///
+/// Bases are compared case-insensitively with `U` treated as `T`. Ranges from
+/// 0 for a homopolymer to 2 for equal A/C/G/T usage; `N` counts as a fifth
+/// symbol. Empty sequences have entropy 0.
+pub fn shannon_entropy(sequence: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for base in String::from_utf8_lossy(&normalize(sequence)).chars() {
+        *counts.entry(base).or_insert(0) += 1;
+    }
+
+    let len = sequence.chars().count() as f64;
+    counts
+        .values()
+        .map(|&count| {
+            let freq = count as f64 / len;
+            -freq * freq.log2()
+        })
+        .sum()
+}
+
+/// Count the k-mers of a sequence
+///
+/// Bases are compared case-insensitively with `U` treated as `T`, and k-mers
+/// are reported upper-case; k-mers containing anything other than A/C/G/T,
+/// such as `N`, are skipped. With `canonical` set, each k-mer is counted
+/// under the lexicographically smaller of itself and its reverse complement,
+/// so a sequence and its reverse complement give the same table. Fails with
+/// [`DataModelError::ValidationError`] if `k` is 0.
+pub fn kmer_counts(sequence: &str, k: usize, canonical: bool) -> Result<HashMap<String, usize>> {
+    if k == 0 {
+        return Err(DataModelError::ValidationError(
+            "k-mer length must be at least 1".to_string(),
+        ));
+    }
+
+    let normalized = normalize(sequence);
+    let mut counts = HashMap::new();
+    for window in normalized.windows(k) {
+        if !window
+            .iter()
+            .all(|b| matches!(b, b'A' | b'C' | b'G' | b'T'))
+        {
+            continue;
+        }
+        let kmer = String::from_utf8_lossy(window).into_owned();
+        let kmer = if canonical {
+            let rc = reverse_complement(&kmer);
+            kmer.min(rc)
+        } else {
+            kmer
+        };
+        *counts.entry(kmer).or_insert(0) += 1;
+    }
+    Ok(counts)
+}
+
+/// Drop low-complexity reads such as poly-A tails and adapter dimers
+///
+/// Keeps reads whose [`shannon_entropy`] is at least `min_entropy_bits`.
+pub fn filter_by_complexity(
+    reads: impl Iterator<Item = FastqRecord>,
+    min_entropy_bits: f64,
+) -> impl Iterator<Item = FastqRecord> {
+    reads.filter(move |read| shannon_entropy(read.sequence()) >= min_entropy_bits)
+}
+
+/// Drop reads whose mean quality falls below a percentile of the dataset
+///
+/// The first pass computes each read's [`FastqRecord::average_quality`] and
+/// takes the `percentile` (0 to 100) of those means, interpolating linearly
+/// between the two nearest ranks; the second keeps reads whose mean is at
+/// least that threshold. Returns the kept reads in input order together with
+/// the threshold. An empty input gives no reads and a threshold of 0.
+pub fn filter_by_quality_percentile(
+    reads: &[FastqRecord],
+    percentile: f64,
+) -> Result<(Vec<FastqRecord>, f64)> {
+    if !(0.0..=100.0).contains(&percentile) {
+        return Err(DataModelError::ValidationError(format!(
+            "quality percentile must be between 0 and 100, got {}",
+            percentile
+        )));
+    }
+
+    let mut means: Vec<f64> = reads.iter().map(FastqRecord::average_quality).collect();
+    means.sort_by(f64::total_cmp);
+    let threshold = match means.len() {
+        0 => return Ok((Vec::new(), 0.0)),
+        n => {
+            let rank = percentile / 100.0 * (n - 1) as f64;
+            let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+            means[lower] + (means[upper] - means[lower]) * (rank - lower as f64)
+        }
+    };
+
+    let kept = reads
+        .iter()
+        .filter(|read| read.average_quality() >= threshold)
+        .cloned()
+        .collect();
+    Ok((kept, threshold))
+}
+
+/// Group reads into batches of `batch_size`
+///
+/// Every batch holds `batch_size` reads except the last, which holds the
+/// remainder; no empty batch is yielded. Reads are pulled lazily, one batch
+/// at a time, so batches can be handed to a parallel worker as they fill.
+pub fn batched(
+    mut reads: impl Iterator<Item = FastqRecord>,
+    batch_size: usize,
+) -> Result<impl Iterator<Item = Vec<FastqRecord>>> {
+    if batch_size == 0 {
+        return Err(DataModelError::ValidationError(
+            "batch size must be at least 1".to_string(),
+        ));
+    }
+
+    Ok(std::iter::from_fn(move || {
+        let batch: Vec<FastqRecord> = reads.by_ref().take(batch_size).collect();
+        (!batch.is_empty()).then_some(batch)
+    }))
+}
+
+/// Interleave paired reads as R1, R2, R1, R2, ...
+///
+/// Mates are paired by position and their names are not checked. When one
+/// stream ends before the other, an error naming the longer stream is yielded
+/// in place of its next read and iteration stops, so a truncated mate file is
+/// never written out as a shorter, seemingly valid interleaved file.
+pub fn interleave(
+    r1: impl Iterator<Item = FastqRecord>,
+    r2: impl Iterator<Item = FastqRecord>,
+) -> impl Iterator<Item = Result<FastqRecord>> {
+    let (mut r1, mut r2) = (r1.fuse(), r2.fuse());
+    let mut mate: Option<FastqRecord> = None;
+    let (mut pairs, mut done) = (0usize, false);
+
+    std::iter::from_fn(move || {
+        if let Some(mate) = mate.take() {
+            return Some(Ok(mate));
+        }
+        if done {
+            return None;
+        }
+        match (r1.next(), r2.next()) {
+            (Some(read), Some(next_mate)) => {
+                pairs += 1;
+                mate = Some(next_mate);
+                Some(Ok(read))
+            }
+            (None, None) => None,
+            (extra, _) => {
+                done = true;
+                let (longer, shorter) = if extra.is_some() { (1, 2) } else { (2, 1) };
+                Some(Err(DataModelError::ValidationError(format!(
+                    "R{} has more reads than R{}, which ends after {} reads",
+                    longer, shorter, pairs
+                ))))
+            }
+        }
+    })
+}
+
+/// Split an interleaved stream (R1, R2, R1, R2, ...) into its mate files
+///
+/// Fails with [`DataModelError::ValidationError`] on an odd number of reads,
+/// or when the two reads of a pair have different template names, i.e. ids
+/// that differ after removing a `/1` or `/2` suffix.
+pub fn deinterleave(
+    reads: impl Iterator<Item = FastqRecord>,
+) -> Result<(Vec<FastqRecord>, Vec<FastqRecord>)> {
+    let (mut r1, mut r2) = (Vec::new(), Vec::new());
+    let mut reads = reads.fuse();
+    while let Some(read) = reads.next() {
+        let Some(mate) = reads.next() else {
+            return Err(DataModelError::ValidationError(format!(
+                "interleaved stream has an odd number of reads; '{}' has no mate",
+                read.id
+            )));
+        };
+        if template_name(&read.id) != template_name(&mate.id) {
+            return Err(DataModelError::ValidationError(format!(
+                "reads '{}' and '{}' at pair {} are not mates",
+                read.id,
+                mate.id,
+                r1.len() + 1
+            )));
+        }
+        r1.push(read);
+        r2.push(mate);
+    }
+    Ok((r1, r2))
+}
+
+/// Strip a `/1` or `/2` mate suffix from a read id
+pub(crate) fn template_name(id: &str) -> &str {
+    id.strip_suffix("/1")
+        .or_else(|| id.strip_suffix("/2"))
+        .unwrap_or(id)
+}
+
+/// Remove a 3' run of `base` that is at least `min_len` long
+///
+/// The base is matched case-insensitively and quality scores are truncated to
+/// match. Reads whose tail run is shorter than `min_len` are returned
+/// unchanged.
+pub fn trim_poly_x(read: &FastqRecord, base: u8, min_len: usize) -> FastqRecord {
+    let run = read
+        .sequence()
+        .bytes()
+        .rev()
+        .take_while(|b| b.eq_ignore_ascii_case(&base))
+        .count();
+
+    let mut trimmed = read.clone();
+    if run >= min_len {
+        trimmed.seq_qual.truncate(read.seq_qual.len() - run);
+    }
+    trimmed
+}
+
+/// Remove a 3' poly-G tail of at least [`POLY_G_MIN_LEN`] bases
+///
+/// Two-color sequencers such as NovaSeq call G when no signal is detected, so
+/// reads that run past the end of their insert gain artificial G runs.
+pub fn trim_poly_g(read: &FastqRecord) -> FastqRecord {
+    trim_poly_x(read, b'G', POLY_G_MIN_LEN)
+}
+
+/// Merge the overlapping mates of a paired-end read into one read
+///
+/// `r2` is reverse-complemented and slid along `r1`; of the overlaps at least
+/// `min_overlap` long with at most `max_mismatches`, the one with the fewest
+/// mismatches is used, the longest on ties. Where the mates agree, the base is
+/// kept and the Phred scores are summed, as both reads would have to be wrong;
+/// where they disagree, the higher-quality base is kept with the difference of
+/// the scores, and equal-quality disagreements become `N` with quality 0.
+/// Scores are capped at Q60. Positions outside the overlap keep their original
+/// base and quality. Returns `None` if no overlap qualifies.
+pub fn merge_pair(
+    r1: &FastqRecord,
+    r2: &FastqRecord,
+    min_overlap: usize,
+    max_mismatches: usize,
+) -> Option<FastqRecord> {
+    let seq1 = r1.sequence().to_ascii_uppercase().into_bytes();
+    let seq2: Vec<u8> = r2
+        .sequence()
+        .to_ascii_uppercase()
+        .bytes()
+        .rev()
+        .map(complement)
+        .collect();
+    let qual2: Vec<u8> = r2.quality().iter().rev().copied().collect();
+    let overlap_at = |offset: usize| (seq1.len() - offset).min(seq2.len());
+
+    let mut best: Option<(usize, usize)> = None;
+    for offset in 0..seq1.len() {
+        let overlap = overlap_at(offset);
+        if overlap == 0 || overlap < min_overlap {
+            continue;
+        }
+        let mismatches = mismatch_count(&seq1[offset..offset + overlap], &seq2[..overlap]);
+        let improves = !matches!(best, Some((fewest, _)) if fewest <= mismatches);
+        if mismatches <= max_mismatches && improves {
+            best = Some((mismatches, offset));
+        }
+    }
+    let (_, offset) = best?;
+    let overlap = overlap_at(offset);
+
+    let mut sequence = seq1[..offset].to_vec();
+    let mut quality = r1.quality()[..offset].to_vec();
+    for i in 0..overlap {
+        let (b1, q1) = (
+            seq1[offset + i],
+            r1.quality()[offset + i].saturating_sub(PHRED_OFFSET),
+        );
+        let (b2, q2) = (seq2[i], qual2[i].saturating_sub(PHRED_OFFSET));
+        let (base, phred) = if b1 == b2 {
+            (b1, q1.saturating_add(q2))
+        } else if q1 > q2 {
+            (b1, q1 - q2)
+        } else if q2 > q1 {
+            (b2, q2 - q1)
+        } else {
+            (b'N', 0)
+        };
+        sequence.push(base);
+        quality.push(phred.min(MAX_PHRED) + PHRED_OFFSET);
+    }
+    if offset + overlap < seq1.len() {
+        sequence.extend_from_slice(&seq1[offset + overlap..]);
+        quality.extend_from_slice(&r1.quality()[offset + overlap..]);
+    } else {
+        sequence.extend_from_slice(&seq2[overlap..]);
+        quality.extend_from_slice(&qual2[overlap..]);
+    }
+
+    Some(FastqRecord {
+        id: r1.id.clone(),
+        seq_qual: SeqQual::new(String::from_utf8_lossy(&sequence).into_owned(), quality).ok()?,
+        description: r1.description.clone(),
+    })
+}
+
+/// Split a chimeric read at an internal adapter
+///
+/// The adapter is matched case-insensitively. Its first occurrence leaving at
+/// least `min_fragment` bases on both sides splits the read into the bases
+/// before and after it, with their quality scores; the adapter itself is
+/// dropped. Fragments are named `<id>_1` and `<id>_2`. Returns `None` when no
+/// such occurrence exists, including adapters at either end of the read.
+pub fn detect_chimera(
+    read: &FastqRecord,
+    adapter: &str,
+    min_fragment: usize,
+) -> Option<(FastqRecord, FastqRecord)> {
+    let adapter = adapter.as_bytes();
+    let sequence = read.sequence().as_bytes();
+    if adapter.is_empty() || sequence.len() < 2 * min_fragment + adapter.len() {
+        return None;
+    }
+
+    let last = sequence.len() - adapter.len() - min_fragment;
+    let start = (min_fragment..=last)
+        .find(|&i| sequence[i..i + adapter.len()].eq_ignore_ascii_case(adapter))?;
+
+    let fragment = |suffix: usize, from: usize, to: usize| {
+        let mut fragment = read.clone();
+        fragment.id = format!("{}_{}", read.id, suffix);
+        fragment.seq_qual.clip(from, to);
+        fragment
+    };
+    Some((
+        fragment(1, 0, start),
+        fragment(2, start + adapter.len(), sequence.len()),
+    ))
+}
+
+/// Builds a consensus read from reads starting at the same reference position
+///
+/// Each base contributes its probability of being correct (derived from its
+/// Phred quality) to the tally at its position, so low-quality calls are
+/// outvoted by confident ones. `N` bases contribute nothing.
+#[derive(Debug, Clone)]
+pub struct ConsensusBuilder {
+    id: String,
+    /// Per-position weights for A, C, G, T
+    tallies: Vec<[f64; 4]>,
+    depth: Vec<usize>,
+}
+
+impl ConsensusBuilder {
+    /// Create a builder for a consensus of `length` bases
+    pub fn new(id: String, length: usize) -> Self {
+        Self {
+            id,
+            tallies: vec![[0.0; 4]; length],
+            depth: vec![0; length],
+        }
+    }
+
+    /// Add a read aligned to the consensus start
+    ///
+    /// Reads longer than the consensus are rejected.
+    pub fn add(&mut self, read: &FastqRecord) -> Result<()> {
+        if read.seq_qual.len() > self.tallies.len() {
+            return Err(DataModelError::ValidationError(format!(
+                "read '{}' has length {}, longer than consensus length {}",
+                read.id,
+                read.seq_qual.len(),
+                self.tallies.len()
+            )));
+        }
+
+        for (pos, (base, &qual)) in read.sequence().chars().zip(read.quality()).enumerate() {
+            let base = match base.to_ascii_uppercase() {
+                'U' => 'T',
+                other => other,
+            };
+            if let Some(idx) = CONSENSUS_BASES.iter().position(|&b| b == base) {
+                self.tallies[pos][idx] += 1.0 - phred_to_error_prob(qual);
+                self.depth[pos] += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the number of called bases covering a position
+    pub fn depth(&self, pos: usize) -> usize {
+        self.depth.get(pos).copied().unwrap_or(0)
+    }
+
+    /// Build the consensus read
+    ///
+    /// Each position takes the base with the highest weight; its quality is
+    /// the Phred-scaled disagreement, i.e. `1 - weight / total weight`.
+    /// Uncovered positions and exact ties are emitted as `N` with quality 0.
+    pub fn build(&self) -> FastqRecord {
+        let mut sequence = String::with_capacity(self.tallies.len());
+        let mut quality = Vec::with_capacity(self.tallies.len());
+
+        for weights in &self.tallies {
+            let total: f64 = weights.iter().sum();
+            let (best, &best_weight) = weights
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .unwrap_or((0, &0.0));
+            let tied = weights.iter().filter(|&&w| w == best_weight).count() > 1;
+
+            if total <= 0.0 || tied {
+                sequence.push('N');
+                quality.push(PHRED_OFFSET);
+            } else {
+                sequence.push(CONSENSUS_BASES[best]);
+                quality.push(error_prob_to_phred(1.0 - best_weight / total) + PHRED_OFFSET);
+            }
+        }
+
+        FastqRecord {
+            id: self.id.clone(),
+            seq_qual: SeqQual::new(sequence, quality)
+                .expect("consensus bases and qualities are pushed together"),
+            description: None,
+        }
+    }
+}
+
+/// Convert a Phred+33 quality character to an error probability
+fn phred_to_error_prob(qual: u8) -> f64 {
+    10f64.powf(-f64::from(qual.saturating_sub(PHRED_OFFSET)) / 10.0)
+}
+
+/// Convert an error probability to a Phred score, capped at [`MAX_PHRED`]
+fn error_prob_to_phred(prob: f64) -> u8 {
+    if prob <= 0.0 {
+        return MAX_PHRED;
+    }
+    (-10.0 * prob.log10())
+        .round()
+        .clamp(0.0, f64::from(MAX_PHRED)) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reads(n: usize) -> Vec<FastqRecord> {
+        (0..n)
+            .map(|i| {
+                FastqRecord::new(format!("read{}", i), "ACGT".to_string(), vec![b'I'; 4]).unwrap()
+            })
+            .collect()
+    }
+
+    fn ids(reads: impl Iterator<Item = FastqRecord>) -> Vec<String> {
+        reads.map(|r| r.id).collect()
+    }
+
+    #[test]
+    fn test_subsample_is_reproducible() {
+        let first = ids(subsample(reads(200).into_iter(), 0.3, 42).unwrap());
+        let second = ids(subsample(reads(200).into_iter(), 0.3, 42).unwrap());
+        let other_seed = ids(subsample(reads(200).into_iter(), 0.3, 7).unwrap());
+
+        assert_eq!(first, second);
+        assert_ne!(first, other_seed);
+        assert!(first.len() > 20 && first.len() < 100);
+    }
+
+    #[test]
+    fn test_subsample_bounds() {
+        assert_eq!(subsample(reads(50).into_iter(), 0.0, 1).unwrap().count(), 0);
+        assert_eq!(
+            subsample(reads(50).into_iter(), 1.0, 1).unwrap().count(),
+            50
+        );
+        assert!(subsample(reads(50).into_iter(), 1.5, 1).is_err());
+        assert!(subsample(reads(50).into_iter(), -0.1, 1).is_err());
+    }
+
+    #[test]
+    fn test_consensus_majority_wins() {
+        let make = |id: &str, seq: &str| {
+            FastqRecord::new(id.to_string(), seq.to_string(), vec![b'I'; seq.len()]).unwrap()
+        };
+        let mut builder = ConsensusBuilder::new("consensus".to_string(), 10);
+        builder.add(&make("r1", "ACGTACGT")).unwrap();
+        builder.add(&make("r2", "ACGTACGT")).unwrap();
+        builder.add(&make("r3", "ACGAACGT")).unwrap();
+
+        let consensus = builder.build();
+        assert_eq!(consensus.sequence(), "ACGTACGTNN");
+        assert_eq!(builder.depth(3), 3);
+        assert_eq!(builder.depth(8), 0);
+
+        // Unanimous positions are capped, the disputed one reflects 2/3 agreement
+        assert_eq!(consensus.quality()[0], MAX_PHRED + PHRED_OFFSET);
+        assert_eq!(consensus.quality()[3], 5 + PHRED_OFFSET);
+        assert_eq!(consensus.quality()[8], PHRED_OFFSET);
+
+        assert!(builder.add(&make("long", "ACGTACGTACGT")).is_err());
+    }
+
+    #[test]
+    fn test_consensus_quality_weighting() {
+        let mut builder = ConsensusBuilder::new("consensus".to_string(), 1);
+        builder
+            .add(&FastqRecord::new("hq".to_string(), "G".to_string(), vec![b'I']).unwrap())
+            .unwrap();
+        for i in 0..2 {
+            let lq = FastqRecord::new(format!("lq{}", i), "A".to_string(), vec![b'#']).unwrap();
+            builder.add(&lq).unwrap();
+        }
+
+        assert_eq!(builder.build().sequence(), "G");
+    }
+
+    #[test]
+    fn test_shannon_entropy() {
+        assert_eq!(shannon_entropy("AAAAAAAA"), 0.0);
+        assert_eq!(shannon_entropy("ACGTACGT"), 2.0);
+        assert_eq!(shannon_entropy("acgu"), 2.0);
+        assert_eq!(shannon_entropy(""), 0.0);
+    }
+
+    #[test]
+    fn test_filter_by_complexity() {
+        let poly_a = FastqRecord::new("polyA".to_string(), "A".repeat(30), vec![b'I'; 30]).unwrap();
+        let dimer = FastqRecord::new(
+            "dimer".to_string(),
+            "AGAGAGAGAGAGAGAGAGAG".to_string(),
+            vec![b'I'; 20],
+        )
+        .unwrap();
+        let diverse = FastqRecord::new(
+            "diverse".to_string(),
+            "ACGTTGCAAGCTTCGAGCTAGGCT".to_string(),
+            vec![b'I'; 24],
+        )
+        .unwrap();
+
+        let kept = ids(filter_by_complexity(
+            vec![poly_a, dimer, diverse].into_iter(),
+            1.5,
+        ));
+        assert_eq!(kept, vec!["diverse".to_string()]);
+    }
+
+    #[test]
+    fn test_detect_chimera_splits_at_adapter() {
+        let read = record("ACGTACGTAGATCGGAAGTTGCATTGCA");
+        let (left, right) = detect_chimera(&read, "agatcggaag", 6).unwrap();
+
+        assert_eq!(left.id, "r_1");
+        assert_eq!(left.sequence(), "ACGTACGT");
+        assert_eq!(left.quality(), &read.quality()[..8]);
+        assert_eq!(right.id, "r_2");
+        assert_eq!(right.sequence(), "TTGCATTGCA");
+        assert_eq!(right.quality(), &read.quality()[18..]);
+
+        // The right fragment would be shorter than the minimum
+        assert!(detect_chimera(&read, "AGATCGGAAG", 11).is_none());
+    }
+
+    #[test]
+    fn test_detect_chimera_without_internal_adapter() {
+        let clean = record("ACGTACGTTTGCATTGCAACGT");
+        assert!(detect_chimera(&clean, "AGATCGGAAG", 4).is_none());
+
+        // A 3' adapter is ordinary read-through, not a chimera
+        let tail = record("ACGTACGTTTGCAAGATCGGAAG");
+        assert!(detect_chimera(&tail, "AGATCGGAAG", 4).is_none());
+    }
+
+    #[test]
+    fn test_filter_by_quality_percentile() {
+        // Mean qualities Q30, Q10, Q50, Q20, Q40
+        let reads: Vec<FastqRecord> = [30u8, 10, 50, 20, 40]
+            .iter()
+            .enumerate()
+            .map(|(i, &q)| {
+                FastqRecord::new(format!("read{}", i), "ACGT".to_string(), vec![q + 33; 4]).unwrap()
+            })
+            .collect();
+
+        let (kept, threshold) = filter_by_quality_percentile(&reads, 10.0).unwrap();
+        assert!((threshold - 14.0).abs() < 1e-9);
+        assert_eq!(ids(kept.into_iter()), ["read0", "read2", "read3", "read4"]);
+
+        let (kept, threshold) = filter_by_quality_percentile(&reads, 50.0).unwrap();
+        assert!((threshold - 30.0).abs() < 1e-9);
+        assert_eq!(ids(kept.into_iter()), ["read0", "read2", "read4"]);
+
+        assert!(filter_by_quality_percentile(&reads, 101.0).is_err());
+        assert_eq!(filter_by_quality_percentile(&[], 10.0).unwrap().1, 0.0);
+    }
+
+    #[test]
+    fn test_canonical_kmer_counts() {
+        let forward = "ACGGTTNACGu";
+        let reverse = reverse_complement(forward);
+
+        let counts = kmer_counts(forward, 3, false).unwrap();
+        assert_eq!(counts["ACG"], 2);
+        assert_eq!(counts["CGT"], 1);
+        assert!(!counts.keys().any(|kmer| kmer.contains('N')));
+        assert_ne!(counts, kmer_counts(&reverse, 3, false).unwrap());
+
+        let canonical = kmer_counts(forward, 3, true).unwrap();
+        assert_eq!(canonical, kmer_counts(&reverse, 3, true).unwrap());
+        // CGT is the reverse complement of ACG, so both count as ACG
+        assert_eq!(canonical["ACG"], 3);
+        assert!(!canonical.contains_key("CGT"));
+        assert_eq!(
+            canonical.values().sum::<usize>(),
+            counts.values().sum::<usize>()
+        );
+
+        assert!(kmer_counts(forward, 0, true).is_err());
+        assert!(kmer_counts("AC", 3, true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batched() {
+        let batches: Vec<Vec<String>> = batched(reads(7).into_iter(), 3)
+            .unwrap()
+            .map(|batch| ids(batch.into_iter()))
+            .collect();
+
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[0], ["read0", "read1", "read2"]);
+        assert_eq!(batches[2], ["read6"]);
+        assert_eq!(batched(reads(0).into_iter(), 3).unwrap().count(), 0);
+        assert!(batched(reads(2).into_iter(), 0).is_err());
+    }
+
+    fn mates(n: usize, mate: u8) -> Vec<FastqRecord> {
+        (0..n)
+            .map(|i| {
+                let sequence = if mate == 1 { "ACGT" } else { "TTGA" };
+                let id = format!("frag{}/{}", i, mate);
+                FastqRecord::new(id, sequence.to_string(), vec![b'I'; 4]).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_interleave_round_trip() {
+        let (r1, r2) = (mates(3, 1), mates(3, 2));
+        let interleaved: Vec<FastqRecord> =
+            interleave(r1.clone().into_iter(), r2.clone().into_iter())
+                .collect::<Result<_>>()
+                .unwrap();
+        assert_eq!(
+            ids(interleaved.clone().into_iter()),
+            ["frag0/1", "frag0/2", "frag1/1", "frag1/2", "frag2/1", "frag2/2"]
+        );
+
+        let (split1, split2) = deinterleave(interleaved.into_iter()).unwrap();
+        assert_eq!(split1, r1);
+        assert_eq!(split2, r2);
+
+        let (empty1, empty2) = deinterleave(std::iter::empty()).unwrap();
+        assert!(empty1.is_empty() && empty2.is_empty());
+    }
+
+    #[test]
+    fn test_interleave_length_mismatch() {
+        let results: Vec<Result<FastqRecord>> =
+            interleave(mates(3, 1).into_iter(), mates(2, 2).into_iter()).collect();
+        assert_eq!(results.len(), 5);
+        assert!(results[..4].iter().all(|r| r.is_ok()));
+        assert!(results[4].is_err());
+
+        let results: Vec<Result<FastqRecord>> =
+            interleave(mates(1, 1).into_iter(), mates(2, 2).into_iter()).collect();
+        assert_eq!(results.len(), 3);
+        assert!(results[2].is_err());
+    }
+
+    #[test]
+    fn test_deinterleave_validates_pairs() {
+        let mut odd = mates(2, 1);
+        odd.insert(1, mates(1, 2).remove(0));
+        assert!(deinterleave(odd.into_iter()).is_err());
+
+        let swapped = vec![mates(2, 1).remove(0), mates(2, 2).remove(1)];
+        assert!(deinterleave(swapped.into_iter()).is_err());
+    }
+
+    fn record(sequence: &str) -> FastqRecord {
+        let quality = (0..sequence.len()).map(|i| b'!' + i as u8).collect();
+        FastqRecord::new("r".to_string(), sequence.to_string(), quality).unwrap()
+    }
+
+    #[test]
+    fn test_trim_poly_g_tail() {
+        let read = record("ACGTACGTGGGGGGGGGGGG");
+        let trimmed = trim_poly_g(&read);
+
+        assert_eq!(trimmed.sequence(), "ACGTACGT");
+        assert_eq!(trimmed.quality(), &read.quality()[..8]);
+
+        let poly_a = trim_poly_x(&record("ACGTCAaaaa"), b'A', 4);
+        assert_eq!(poly_a.sequence(), "ACGTC");
+        assert_eq!(poly_a.quality().len(), 5);
+    }
+
+    #[test]
+    fn test_short_tail_untouched() {
+        let read = record("ACGTACGTGGGG");
+        assert_eq!(trim_poly_g(&read), read);
+        assert_eq!(trim_poly_x(&read, b'A', 0), read);
+    }
+
+    #[test]
+    fn test_merge_pair_combines_overlap_quality() {
+        // Insert AAACCCGGGTTTACGT; r2 reads its last 12 bases on the reverse
+        // strand, overlapping r1 at positions 4..12
+        let r1 =
+            FastqRecord::new("p".to_string(), "AAACCCGGGTTT".to_string(), vec![b'?'; 12]).unwrap();
+        let mut r2_quality = vec![b'?'; 12];
+        // Last base of r2 pairs with r1 position 4: a Q10 mismatch
+        r2_quality[11] = b'+';
+        let r2 = FastqRecord::new("p".to_string(), "ACGTAAACCCGA".to_string(), r2_quality).unwrap();
+
+        let merged = merge_pair(&r1, &r2, 6, 1).unwrap();
+
+        assert_eq!(merged.sequence(), "AAACCCGGGTTTACGT");
+        assert_eq!(merged.quality().len(), 16);
+        // Outside the overlap, qualities are unchanged
+        assert_eq!(merged.quality()[0], b'?');
+        assert_eq!(merged.quality()[15], b'?');
+        // Q30 vs Q10 disagreement keeps r1's base at Q20
+        assert_eq!(merged.quality()[4] - PHRED_OFFSET, 20);
+        // Q30 agreement is more confident than either read
+        assert!(merged.quality()[5] - PHRED_OFFSET > 30);
+        assert_eq!(merged.quality()[5] - PHRED_OFFSET, MAX_PHRED);
+
+        assert!(merge_pair(&r1, &r2, 6, 0).is_none());
+    }
+}
@@ -0,0 +1,273 @@
+//! Sample sheet loading for pipeline runs
+//!
+//! A sample sheet is a CSV file with one row per sample, mapping the sample id
+//! to its FASTQ files and experimental condition. Columns are matched by header
+//! name; `sample_id` and `r1` are required, `r2`, `condition` and
+//! `library_protocol` are optional, and any other column is kept as custom
+//! metadata. Fields are split on commas without quoting support.
+
+use crate::error::{DataModelError, Result};
+use crate::expression::SampleMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const REQUIRED_COLUMNS: [&str; 2] = ["sample_id", "r1"];
+
+/// A single row of a sample sheet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleSheetEntry {
+    /// Unique sample identifier
+    pub sample_id: String,
+    /// Experimental condition
+    pub condition: Option<String>,
+    /// Path to the R1 FASTQ file
+    pub r1: PathBuf,
+    /// Path to the R2 FASTQ file for paired-end runs
+    pub r2: Option<PathBuf>,
+    /// Sample metadata
+    pub metadata: SampleMetadata,
+}
+
+impl SampleSheetEntry {
+    /// Check if the sample was sequenced paired-end
+    pub fn is_paired(&self) -> bool {
+        self.r2.is_some()
+    }
+}
+
+/// Collection of samples loaded from a sample sheet
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SampleSheet {
+    /// Sample entries in file order
+    pub entries: Vec<SampleSheetEntry>,
+}
+
+impl SampleSheet {
+    /// Load a sample sheet from a CSV file
+    ///
+    /// Relative FASTQ paths are resolved against the directory containing the
+    /// sheet. When `check_files` is set, every referenced FASTQ file must exist.
+    /// All problems found in the sheet are reported together in a single
+    /// [`DataModelError::ValidationError`].
+    pub fn from_csv<P: AsRef<Path>>(path: P, check_files: bool) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::parse(&content, base_dir, check_files)
+    }
+
+    /// Parse sample sheet content, resolving relative paths against `base_dir`
+    pub fn parse(content: &str, base_dir: &Path, check_files: bool) -> Result<Self> {
+        let mut lines = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+
+        let header: Vec<String> = match lines.next() {
+            Some((_, line)) => line.split(',').map(|h| h.trim().to_lowercase()).collect(),
+            None => return Err(DataModelError::MissingField("header".to_string())),
+        };
+
+        let missing: Vec<&str> = REQUIRED_COLUMNS
+            .iter()
+            .filter(|col| !header.iter().any(|h| h == *col))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            return Err(DataModelError::ValidationError(format!(
+                "missing required column(s): {}",
+                missing.join(", ")
+            )));
+        }
+
+        let column = |name: &str| header.iter().position(|h| h == name);
+        let id_col = column("sample_id").unwrap_or_default();
+        let r1_col = column("r1").unwrap_or_default();
+        let r2_col = column("r2");
+        let condition_col = column("condition");
+        let protocol_col = column("library_protocol");
+
+        let mut problems = Vec::new();
+        let mut seen_ids = HashSet::new();
+        let mut entries = Vec::new();
+
+        for (idx, line) in lines {
+            let line_number = idx + 1;
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() != header.len() {
+                problems.push(format!(
+                    "line {}: expected {} fields, found {}",
+                    line_number,
+                    header.len(),
+                    fields.len()
+                ));
+                continue;
+            }
+
+            let optional = |col: Option<usize>| {
+                col.map(|c| fields[c])
+                    .filter(|v| !v.is_empty())
+                    .map(str::to_string)
+            };
+
+            let sample_id = fields[id_col].to_string();
+            if sample_id.is_empty() {
+                problems.push(format!("line {}: empty sample_id", line_number));
+                continue;
+            }
+            if !seen_ids.insert(sample_id.clone()) {
+                problems.push(format!(
+                    "line {}: duplicate sample_id '{}'",
+                    line_number, sample_id
+                ));
+            }
+
+            let r1 = match optional(Some(r1_col)) {
+                Some(r1) => base_dir.join(r1),
+                None => {
+                    problems.push(format!("line {}: empty r1 path", line_number));
+                    continue;
+                }
+            };
+            let r2 = optional(r2_col).map(|r2| base_dir.join(r2));
+
+            if check_files {
+                for file in std::iter::once(&r1).chain(r2.as_ref()) {
+                    if !file.exists() {
+                        problems.push(format!(
+                            "line {}: file not found: {}",
+                            line_number,
+                            file.display()
+                        ));
+                    }
+                }
+            }
+
+            let mut metadata = SampleMetadata {
+                library_protocol: optional(protocol_col),
+                ..SampleMetadata::default()
+            };
+            let known = [
+                Some(id_col),
+                Some(r1_col),
+                r2_col,
+                condition_col,
+                protocol_col,
+            ];
+            for (c, name) in header.iter().enumerate() {
+                if !known.contains(&Some(c)) && !fields[c].is_empty() {
+                    metadata.custom.insert(name.clone(), fields[c].to_string());
+                }
+            }
+
+            entries.push(SampleSheetEntry {
+                sample_id,
+                condition: optional(condition_col),
+                r1,
+                r2,
+                metadata,
+            });
+        }
+
+        if !problems.is_empty() {
+            return Err(DataModelError::ValidationError(problems.join("; ")));
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Look up an entry by sample id
+    pub fn get(&self, sample_id: &str) -> Option<&SampleSheetEntry> {
+        self.entries.iter().find(|e| e.sample_id == sample_id)
+    }
+
+    /// Get the number of samples
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the sheet has no samples
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_sheet(dir: &Path, content: &str) -> PathBuf {
+        let path = dir.join("samples.csv");
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load_valid_sheet() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("s1_R1.fastq"), "").unwrap();
+        fs::write(dir.path().join("s1_R2.fastq"), "").unwrap();
+        fs::write(dir.path().join("s2_R1.fastq"), "").unwrap();
+        let path = write_sheet(
+            dir.path(),
+            "sample_id,r1,r2,condition,tissue\n\
+             s1,s1_R1.fastq,s1_R2.fastq,control,liver\n\
+             s2,s2_R1.fastq,,treated,\n",
+        );
+
+        let sheet = SampleSheet::from_csv(&path, true).unwrap();
+        assert_eq!(sheet.len(), 2);
+
+        let s1 = sheet.get("s1").unwrap();
+        assert!(s1.is_paired());
+        assert_eq!(s1.condition.as_deref(), Some("control"));
+        assert_eq!(s1.r1, dir.path().join("s1_R1.fastq"));
+        assert_eq!(
+            s1.metadata.custom.get("tissue").map(String::as_str),
+            Some("liver")
+        );
+
+        let s2 = sheet.get("s2").unwrap();
+        assert!(!s2.is_paired());
+        assert!(s2.metadata.custom.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_sample_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_sheet(
+            dir.path(),
+            "sample_id,r1,condition\ns1,a.fastq,control\ns1,b.fastq,treated\n",
+        );
+
+        let err = SampleSheet::from_csv(&path, false).unwrap_err();
+        assert!(matches!(err, DataModelError::ValidationError(_)));
+        assert!(err.to_string().contains("duplicate sample_id 's1'"));
+    }
+
+    #[test]
+    fn test_missing_required_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_sheet(dir.path(), "sample_id,condition\ns1,control\n");
+
+        let err = SampleSheet::from_csv(&path, false).unwrap_err();
+        assert!(err.to_string().contains("missing required column(s): r1"));
+    }
+
+    #[test]
+    fn test_reports_all_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_sheet(
+            dir.path(),
+            "sample_id,r1\ns1,missing.fastq\ns1,other.fastq\n",
+        );
+
+        let err = SampleSheet::from_csv(&path, true).unwrap_err().to_string();
+        assert!(err.contains("file not found"));
+        assert!(err.contains("duplicate sample_id"));
+    }
+}
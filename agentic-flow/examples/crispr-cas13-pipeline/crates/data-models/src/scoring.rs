@@ -0,0 +1,262 @@
+//! Inputs for context-aware guide scoring
+//!
+//! [`GuideScorer::score`] looks at the spacer alone. With a [`ScoringContext`],
+//! [`GuideScorer::score_with_context`] also weighs the target transcript, the
+//! off-target landscape and trained models. Every component is optional and a
+//! missing one leaves its factor at 1.0, so a context with nothing set scores
+//! exactly like [`GuideScorer::score`].
+//!
+//! [`GuideScorer::design_report`] reports the individual metrics behind the
+//! score together with a pass/fail verdict.
+//!
+//! [`GuideScorer::score`]: crate::guides::GuideScorer::score
+//! [`GuideScorer::design_report`]: crate::guides::GuideScorer::design_report
+//! [`GuideScorer::score_with_context`]: crate::guides::GuideScorer::score_with_context
+
+use crate::offtarget::{OffTargetIndex, SeedModel};
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// Protospacer flanking site preference
+///
+/// The PFS is the transcript base just 3' of the target site. LshCas13a
+/// cleaves targets with a G there poorly, which the default model encodes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PfsModel {
+    /// Flanking bases that reduce activity, upper case with `T` for `U`
+    pub disfavored: Vec<u8>,
+    /// Score factor applied when the flanking base is disfavored
+    pub factor: f64,
+}
+
+impl Default for PfsModel {
+    /// Non-G (H) PFS preference of LshCas13a, halving the score of G sites
+    fn default() -> Self {
+        Self {
+            disfavored: vec![b'G'],
+            factor: 0.5,
+        }
+    }
+}
+
+impl PfsModel {
+    /// Get the score factor for a flanking base, or 1.0 when there is none
+    pub fn term(&self, flanking: Option<u8>) -> f64 {
+        match flanking.map(|b| b.to_ascii_uppercase()) {
+            Some(b'U') if self.disfavored.contains(&b'T') => self.factor,
+            Some(base) if self.disfavored.contains(&base) => self.factor,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Position-specific linear model of on-target activity
+///
+/// `weights[i]` holds the contributions of A, C, G and T/U at spacer position
+/// `i`; positions past the end of `weights`, and ambiguous bases, contribute
+/// nothing. The prediction is the logistic of `intercept` plus the
+/// contributions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OnTargetModel {
+    /// Constant term
+    pub intercept: f64,
+    /// Per-position weights for A, C, G, T
+    pub weights: Vec<[f64; 4]>,
+}
+
+impl OnTargetModel {
+    /// Predict the activity of a spacer, between 0 and 1
+    pub fn predict(&self, spacer: &str) -> f64 {
+        let logit: f64 = self.intercept
+            + spacer
+                .bytes()
+                .zip(&self.weights)
+                .filter_map(|(base, w)| {
+                    let idx = match base.to_ascii_uppercase() {
+                        b'A' => 0,
+                        b'C' => 1,
+                        b'G' => 2,
+                        b'T' | b'U' => 3,
+                        _ => return None,
+                    };
+                    Some(w[idx])
+                })
+                .sum::<f64>();
+        1.0 / (1.0 + (-logit).exp())
+    }
+}
+
+/// Everything [`GuideScorer::score_with_context`] may take into account
+///
+/// Built with the `with_*` methods from an empty context:
+///
+/// ```
+/// use data_models::offtarget::SeedModel;
+/// use data_models::scoring::{PfsModel, ScoringContext};
+///
+/// let pfs = PfsModel::default();
+/// let context = ScoringContext::new()
+///     .with_transcript("ACGUACGUACGUACGUACGUACGUAAAC")
+///     .with_pfs_model(&pfs);
+/// assert!(context.off_target_index.is_none());
+/// assert_eq!(context.seed_model(), SeedModel::default());
+/// ```
+///
+/// [`GuideScorer::score_with_context`]: crate::guides::GuideScorer::score_with_context
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScoringContext<'a> {
+    /// Transcript the guides target, needed to locate the PFS
+    pub transcript: Option<&'a str>,
+    /// Reference index used to find off-target sites
+    pub off_target_index: Option<&'a OffTargetIndex>,
+    /// PFS preference, applied when the transcript is set
+    pub pfs_model: Option<&'a PfsModel>,
+    /// On-target activity model
+    pub on_target_model: Option<&'a OnTargetModel>,
+    /// Mismatch weights for off-target sites, [`SeedModel::default`] if unset
+    ///
+    /// The index only finds sites matching its own seed exactly; build it
+    /// with [`OffTargetIndex::for_seed_model`] from this model so that the
+    /// sites it misses are ones the model would weigh as least risky.
+    pub seed_model: Option<&'a SeedModel>,
+}
+
+impl<'a> ScoringContext<'a> {
+    /// Create a context with no components
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the target transcript
+    pub fn with_transcript(mut self, transcript: &'a str) -> Self {
+        self.transcript = Some(transcript);
+        self
+    }
+
+    /// Set the off-target index
+    pub fn with_off_target_index(mut self, index: &'a OffTargetIndex) -> Self {
+        self.off_target_index = Some(index);
+        self
+    }
+
+    /// Set the PFS model
+    pub fn with_pfs_model(mut self, model: &'a PfsModel) -> Self {
+        self.pfs_model = Some(model);
+        self
+    }
+
+    /// Set the on-target model
+    pub fn with_on_target_model(mut self, model: &'a OnTargetModel) -> Self {
+        self.on_target_model = Some(model);
+        self
+    }
+
+    /// Set the seed model used to weigh off-target mismatches
+    pub fn with_seed_model(mut self, model: &'a SeedModel) -> Self {
+        self.seed_model = Some(model);
+        self
+    }
+
+    /// Get the seed model, falling back to [`SeedModel::default`]
+    pub fn seed_model(&self) -> SeedModel {
+        self.seed_model.copied().unwrap_or_default()
+    }
+}
+
+/// Pass/fail limits applied by [`GuideScorer::design_report`]
+///
+/// Limits on context metrics only apply when the metric could be computed.
+///
+/// [`GuideScorer::design_report`]: crate::guides::GuideScorer::design_report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DesignThresholds {
+    /// Accepted spacer GC fraction, the scorer variant's range if unset
+    pub gc_range: Option<RangeInclusive<f64>>,
+    /// Lowest accepted Shannon entropy of the spacer, in bits
+    pub min_complexity: f64,
+    /// Highest accepted self-fold penalty
+    pub max_self_fold: f64,
+    /// Lowest accepted on-target prediction
+    pub min_on_target: f64,
+    /// Most off-target sites accepted
+    pub max_off_targets: usize,
+    /// Lowest accepted PFS factor; the default of 1.0 rejects disfavored
+    /// flanking bases
+    pub min_pfs_term: f64,
+}
+
+impl Default for DesignThresholds {
+    fn default() -> Self {
+        Self {
+            gc_range: None,
+            min_complexity: 1.5,
+            max_self_fold: 0.5,
+            min_on_target: 0.5,
+            max_off_targets: 0,
+            min_pfs_term: 1.0,
+        }
+    }
+}
+
+/// Quality metrics and verdict of one designed guide
+///
+/// Metrics that need a [`ScoringContext`] component are `None` when the
+/// component is missing; `pfs_term` is also `None` when the guide's exact
+/// target site isn't found in the transcript.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuideDesignReport {
+    /// Guide identifier
+    pub guide_id: String,
+    /// Spacer sequence
+    pub spacer: String,
+    /// GC fraction of the spacer
+    pub gc: f64,
+    /// Shannon entropy of the spacer, in bits
+    pub complexity: f64,
+    /// Self-fold penalty, 0 for no hairpin
+    pub self_fold: f64,
+    /// On-target activity prediction
+    pub on_target: Option<f64>,
+    /// Off-target sites, excluding the intended target
+    pub off_target_count: Option<usize>,
+    /// PFS factor of the base after the target site
+    pub pfs_term: Option<f64>,
+    /// Context-aware design score
+    pub score: f64,
+    /// Names of the metrics outside their thresholds, in field order
+    pub failures: Vec<String>,
+    /// Whether every metric is within its threshold
+    pub passed: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pfs_term() {
+        let model = PfsModel::default();
+        assert_eq!(model.term(Some(b'g')), 0.5);
+        assert_eq!(model.term(Some(b'A')), 1.0);
+        assert_eq!(model.term(None), 1.0);
+
+        let u_averse = PfsModel {
+            disfavored: vec![b'T'],
+            factor: 0.25,
+        };
+        assert_eq!(u_averse.term(Some(b'U')), 0.25);
+    }
+
+    #[test]
+    fn test_on_target_predict() {
+        let model = OnTargetModel {
+            intercept: 0.0,
+            weights: vec![[1.0, 0.0, 0.0, -1.0]; 2],
+        };
+        assert_eq!(model.predict("CG"), 0.5);
+        assert!(model.predict("AA") > 0.8);
+        assert_eq!(model.predict("AU"), 0.5);
+        // Positions without weights are ignored
+        assert_eq!(model.predict("CGAAAA"), 0.5);
+    }
+}
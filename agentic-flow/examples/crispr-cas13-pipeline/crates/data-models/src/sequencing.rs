@@ -1,19 +1,178 @@
 //! Sequencing data structures for FASTQ and BAM formats
 
 use crate::error::{DataModelError, Result};
+use crate::intervals::BedInterval;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use uuid::Uuid;
 
+/// ASCII encoding of FASTQ quality scores
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PhredEncoding {
+    /// Sanger and Illumina 1.8+: Phred + 33
+    #[default]
+    Phred33,
+    /// Illumina 1.3 to 1.7: Phred + 64
+    Phred64,
+}
+
+impl PhredEncoding {
+    /// Get the ASCII value of Phred score 0
+    pub fn offset(&self) -> u8 {
+        match self {
+            PhredEncoding::Phred33 => 33,
+            PhredEncoding::Phred64 => 64,
+        }
+    }
+
+    /// Convert a quality character to its Phred score, clamping at 0
+    pub fn decode(&self, quality: u8) -> u8 {
+        quality.saturating_sub(self.offset())
+    }
+}
+
+/// Highest Phred score quality bins must cover, the Illumina maximum
+const MAX_BINNED_PHRED: u8 = 41;
+
+/// Lowest quality character a Phred+64 file can contain (Solexa Q-5)
+const PHRED64_MIN_CHAR: u8 = b';';
+
+/// Highest quality character expected from Phred+33 short reads (Q41)
+const PHRED33_MAX_CHAR: u8 = b'J';
+
+/// Infer the quality encoding of a sample of reads
+///
+/// A quality character below `;` cannot occur in Phred+64, so the reads are
+/// Phred+33. Otherwise, characters above `J` (Q41 in Phred+33) indicate
+/// Phred+64. Returns `None` when all characters fall in the range both
+/// encodings share, or when there are no quality scores at all.
+pub fn detect_phred_encoding(reads: &[FastqRecord]) -> Option<PhredEncoding> {
+    let qualities = reads.iter().flat_map(|r| r.quality().iter().copied());
+    let (min, max) = qualities.fold(None, |range, q| match range {
+        Some((min, max)) => Some((q.min(min), q.max(max))),
+        None => Some((q, q)),
+    })?;
+
+    if min < PHRED64_MIN_CHAR {
+        Some(PhredEncoding::Phred33)
+    } else if max > PHRED33_MAX_CHAR {
+        Some(PhredEncoding::Phred64)
+    } else {
+        None
+    }
+}
+
+/// Bases paired with their quality scores
+///
+/// Both halves always have the same length: the constructor rejects
+/// mismatched input and every mutating operation changes them together.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawSeqQual")]
+pub struct SeqQual {
+    sequence: String,
+    quality: Vec<u8>,
+}
+
+/// Unchecked form of [`SeqQual`] used for deserialization
+#[derive(Deserialize)]
+struct RawSeqQual {
+    sequence: String,
+    quality: Vec<u8>,
+}
+
+impl TryFrom<RawSeqQual> for SeqQual {
+    type Error = DataModelError;
+
+    fn try_from(raw: RawSeqQual) -> Result<Self> {
+        Self::new(raw.sequence, raw.quality)
+    }
+}
+
+impl SeqQual {
+    /// Pair a sequence with its quality scores
+    ///
+    /// The sequence must be ASCII and as long as `quality`.
+    pub fn new(sequence: String, quality: Vec<u8>) -> Result<Self> {
+        if !sequence.is_ascii() {
+            return Err(DataModelError::InvalidSequence(
+                "Sequence must be ASCII".to_string(),
+            ));
+        }
+        if sequence.len() != quality.len() {
+            return Err(DataModelError::InvalidSequence(
+                "Sequence and quality length mismatch".to_string(),
+            ));
+        }
+        Ok(Self { sequence, quality })
+    }
+
+    /// Get the bases
+    pub fn sequence(&self) -> &str {
+        &self.sequence
+    }
+
+    /// Get the quality scores (Phred+33 encoded)
+    pub fn quality(&self) -> &[u8] {
+        &self.quality
+    }
+
+    /// Get the number of bases
+    pub fn len(&self) -> usize {
+        self.sequence.len()
+    }
+
+    /// Check if there are no bases
+    pub fn is_empty(&self) -> bool {
+        self.sequence.is_empty()
+    }
+
+    /// Keep the first `len` bases, trimming the 3' end
+    pub fn truncate(&mut self, len: usize) {
+        self.sequence.truncate(len);
+        self.quality.truncate(len);
+    }
+
+    /// Keep only the bases in `start..end`, clamped to the read
+    pub fn clip(&mut self, start: usize, end: usize) {
+        let end = end.min(self.len());
+        let start = start.min(end);
+        self.truncate(end);
+        self.sequence.drain(..start);
+        self.quality.drain(..start);
+    }
+
+    /// Append one base and its quality score
+    pub fn push(&mut self, base: u8, quality: u8) -> Result<()> {
+        if !base.is_ascii() {
+            return Err(DataModelError::InvalidSequence(
+                "Sequence must be ASCII".to_string(),
+            ));
+        }
+        self.sequence.push(base as char);
+        self.quality.push(quality);
+        Ok(())
+    }
+
+    /// Append another read's bases and quality scores
+    pub fn append(&mut self, other: &SeqQual) {
+        self.sequence.push_str(&other.sequence);
+        self.quality.extend_from_slice(&other.quality);
+    }
+
+    /// Split into the sequence and quality scores
+    pub fn into_parts(self) -> (String, Vec<u8>) {
+        (self.sequence, self.quality)
+    }
+}
+
 /// Represents a single sequencing read from FASTQ format
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FastqRecord {
     /// Unique identifier for this read
     pub id: String,
-    /// DNA/RNA sequence
-    pub sequence: String,
-    /// Quality scores (Phred+33 encoded)
-    pub quality: Vec<u8>,
+    /// DNA/RNA sequence with its Phred+33 quality scores
+    #[serde(flatten)]
+    pub seq_qual: SeqQual,
     /// Optional description
     pub description: Option<String>,
 }
@@ -21,12 +180,6 @@ pub struct FastqRecord {
 impl FastqRecord {
     /// Create a new FASTQ record
     pub fn new(id: String, sequence: String, quality: Vec<u8>) -> Result<Self> {
-        if sequence.len() != quality.len() {
-            return Err(DataModelError::InvalidSequence(
-                "Sequence and quality length mismatch".to_string(),
-            ));
-        }
-
         if !Self::is_valid_sequence(&sequence) {
             return Err(DataModelError::InvalidSequence(format!(
                 "Invalid nucleotides in sequence: {}",
@@ -36,8 +189,7 @@ impl FastqRecord {
 
         Ok(Self {
             id,
-            sequence,
-            quality,
+            seq_qual: SeqQual::new(sequence, quality)?,
             description: None,
         })
     }
@@ -48,24 +200,199 @@ impl FastqRecord {
             .all(|c| matches!(c.to_ascii_uppercase(), 'A' | 'C' | 'G' | 'T' | 'U' | 'N'))
     }
 
+    /// Get the DNA/RNA sequence
+    pub fn sequence(&self) -> &str {
+        self.seq_qual.sequence()
+    }
+
+    /// Get the quality scores (Phred+33 encoded)
+    pub fn quality(&self) -> &[u8] {
+        self.seq_qual.quality()
+    }
+
+    /// Replace bases called below `min_phred` with `N`
+    ///
+    /// Unlike trimming, the read length and quality scores are unchanged, so
+    /// positions still line up with the original read.
+    pub fn mask_low_quality(&self, min_phred: u8, encoding: PhredEncoding) -> FastqRecord {
+        let mut masked = self.clone();
+        masked.seq_qual.sequence = self
+            .sequence()
+            .bytes()
+            .zip(self.quality())
+            .map(|(base, &q)| {
+                if encoding.decode(q) < min_phred {
+                    'N'
+                } else {
+                    base as char
+                }
+            })
+            .collect();
+        masked
+    }
+
+    /// Collapse quality scores into bins, as NovaSeq's 4-bin scheme does
+    ///
+    /// Each bin is `(max, value)`: it covers Phred scores from the previous
+    /// bin's `max + 1` (0 for the first bin) up to `max`, and its scores are
+    /// rewritten to `value`. Scores above the last `max` fall in the last bin.
+    /// The bins must cover 0..=41 with increasing `max`es and each `value`
+    /// must lie in its own bin, otherwise this fails with
+    /// [`DataModelError::ValidationError`].
+    pub fn bin_quality(&self, bins: &[(u8, u8)], encoding: PhredEncoding) -> Result<FastqRecord> {
+        let mut low = 0u8;
+        for (i, &(max, value)) in bins.iter().enumerate() {
+            if max < low || !(low..=max).contains(&value) {
+                return Err(DataModelError::ValidationError(format!(
+                    "quality bin {} ({}, {}) must cover Q{} upwards and contain its value",
+                    i, max, value, low
+                )));
+            }
+            low = max.saturating_add(1);
+        }
+        if !matches!(bins.last(), Some(&(max, _)) if max >= MAX_BINNED_PHRED) {
+            return Err(DataModelError::ValidationError(format!(
+                "quality bins must cover Q0 to Q{}",
+                MAX_BINNED_PHRED
+            )));
+        }
+
+        let mut binned = self.clone();
+        binned.seq_qual.quality = self
+            .quality()
+            .iter()
+            .map(|&q| {
+                let phred = encoding.decode(q);
+                let (_, value) = bins
+                    .iter()
+                    .find(|&&(max, _)| phred <= max)
+                    .unwrap_or(&bins[bins.len() - 1]);
+                value + encoding.offset()
+            })
+            .collect();
+        Ok(binned)
+    }
+
     /// Get average quality score
     pub fn average_quality(&self) -> f64 {
-        if self.quality.is_empty() {
+        let quality = self.quality();
+        if quality.is_empty() {
             return 0.0;
         }
-        let sum: u64 = self.quality.iter().map(|&q| (q - 33) as u64).sum();
-        sum as f64 / self.quality.len() as f64
+        let sum: u64 = quality.iter().map(|&q| (q - 33) as u64).sum();
+        sum as f64 / quality.len() as f64
     }
 
     /// Get GC content percentage
     pub fn gc_content(&self) -> f64 {
-        let gc_count = self
-            .sequence
-            .chars()
-            .filter(|&c| matches!(c.to_ascii_uppercase(), 'G' | 'C'))
-            .count();
-        (gc_count as f64 / self.sequence.len() as f64) * 100.0
+        CompositionProfile::from_sequence(self.sequence()).gc_fraction() * 100.0
+    }
+}
+
+/// Nucleotide counts accumulated over one or more sequences
+///
+/// Bases are counted case-insensitively; `T` and `U` are kept apart so RNA
+/// and DNA input can be told apart, and anything outside A/C/G/T/U/N is
+/// counted as `other`. Fractions are relative to all counted characters and
+/// are 0 for an empty profile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompositionProfile {
+    /// Count of `A`
+    pub a: u64,
+    /// Count of `C`
+    pub c: u64,
+    /// Count of `G`
+    pub g: u64,
+    /// Count of `T`
+    pub t: u64,
+    /// Count of `U`
+    pub u: u64,
+    /// Count of `N`
+    pub n: u64,
+    /// Count of any other character
+    pub other: u64,
+}
+
+impl CompositionProfile {
+    /// Create an empty profile
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Profile a single sequence
+    pub fn from_sequence(seq: &str) -> Self {
+        let mut profile = Self::new();
+        profile.add_sequence(seq);
+        profile
+    }
+
+    /// Add the bases of a sequence to the counts
+    pub fn add_sequence(&mut self, seq: &str) {
+        for base in seq.chars() {
+            match base.to_ascii_uppercase() {
+                'A' => self.a += 1,
+                'C' => self.c += 1,
+                'G' => self.g += 1,
+                'T' => self.t += 1,
+                'U' => self.u += 1,
+                'N' => self.n += 1,
+                _ => self.other += 1,
+            }
+        }
+    }
+
+    /// Get the total number of counted characters
+    pub fn total(&self) -> u64 {
+        self.a + self.c + self.g + self.t + self.u + self.n + self.other
+    }
+
+    /// Get the number of G and C bases
+    pub fn gc_count(&self) -> u64 {
+        self.g + self.c
+    }
+
+    /// Get the fraction of G and C bases
+    pub fn gc_fraction(&self) -> f64 {
+        self.ratio(self.gc_count())
+    }
+
+    /// Get the fraction of `N` bases
+    pub fn n_fraction(&self) -> f64 {
+        self.ratio(self.n)
+    }
+
+    /// Get the fraction of a base (A, C, G, T, U or N, any case); any other
+    /// character gives the fraction of `other`
+    pub fn fraction(&self, base: char) -> f64 {
+        let count = match base.to_ascii_uppercase() {
+            'A' => self.a,
+            'C' => self.c,
+            'G' => self.g,
+            'T' => self.t,
+            'U' => self.u,
+            'N' => self.n,
+            _ => self.other,
+        };
+        self.ratio(count)
     }
+
+    fn ratio(&self, count: u64) -> f64 {
+        match self.total() {
+            0 => 0.0,
+            total => count as f64 / total as f64,
+        }
+    }
+}
+
+/// Represents a single named sequence from FASTA format
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FastaRecord {
+    /// Sequence identifier (header text up to the first whitespace)
+    pub id: String,
+    /// Optional description (rest of the header line)
+    pub description: Option<String>,
+    /// Sequence with line breaks removed
+    pub sequence: String,
 }
 
 /// Genomic coordinates
@@ -104,6 +431,11 @@ impl GenomicCoordinate {
     pub fn overlaps(&self, other: &Self) -> bool {
         self.reference_id == other.reference_id && self.start < other.end && other.start < self.end
     }
+
+    /// Get the region as a 0-based half-open interval
+    pub fn interval(&self) -> Result<BedInterval> {
+        BedInterval::new(self.start, self.end)
+    }
 }
 
 /// Represents an aligned read from BAM format
@@ -224,6 +556,134 @@ mod tests {
         assert!(record.is_err());
     }
 
+    #[test]
+    fn test_seq_qual_length_mismatch() {
+        assert!(SeqQual::new("ACGT".to_string(), vec![40; 3]).is_err());
+        assert!(FastqRecord::new("read1".to_string(), "ACGT".to_string(), vec![40; 5]).is_err());
+
+        let json = r#"{"id":"r","sequence":"ACG","quality":[40,40],"description":null}"#;
+        assert!(serde_json::from_str::<FastqRecord>(json).is_err());
+    }
+
+    #[test]
+    fn test_seq_qual_edits_stay_in_sync() {
+        let mut reads = SeqQual::new("ACGTACGT".to_string(), (1..=8).collect()).unwrap();
+
+        reads.truncate(6);
+        assert_eq!(
+            (reads.sequence(), reads.quality()),
+            ("ACGTAC", &[1, 2, 3, 4, 5, 6][..])
+        );
+
+        reads.clip(2, 100);
+        assert_eq!(
+            (reads.sequence(), reads.quality()),
+            ("GTAC", &[3, 4, 5, 6][..])
+        );
+
+        reads.push(b'G', 9).unwrap();
+        reads.append(&SeqQual::new("TT".to_string(), vec![10, 11]).unwrap());
+        assert_eq!(reads.sequence(), "GTACGTT");
+        assert_eq!(reads.quality(), [3, 4, 5, 6, 9, 10, 11]);
+        assert_eq!(reads.len(), reads.quality().len());
+
+        let (sequence, quality) = reads.into_parts();
+        assert_eq!(sequence.len(), quality.len());
+    }
+
+    #[test]
+    fn test_mask_low_quality() {
+        let quality = b"IIII#II+IIII".to_vec();
+        let read =
+            FastqRecord::new("r".to_string(), "ACGTACGTACGT".to_string(), quality.clone()).unwrap();
+
+        let masked = read.mask_low_quality(20, PhredEncoding::Phred33);
+        assert_eq!(masked.sequence(), "ACGTNCGNACGT");
+        assert_eq!(masked.seq_qual.len(), read.seq_qual.len());
+        assert_eq!(masked.quality(), quality);
+
+        // The same characters read as Phred+64 are all below Q20
+        let phred64 = read.mask_low_quality(20, PhredEncoding::Phred64);
+        assert_eq!(phred64.sequence(), "NNNNNNNNNNNN");
+    }
+
+    #[test]
+    fn test_detect_phred_encoding() {
+        let reads = |qualities: &[&[u8]]| -> Vec<FastqRecord> {
+            qualities
+                .iter()
+                .map(|q| {
+                    FastqRecord::new("r".to_string(), "A".repeat(q.len()), q.to_vec()).unwrap()
+                })
+                .collect()
+        };
+
+        let phred33 = reads(&[b"IIII#I", b"FFF:,F"]);
+        assert_eq!(
+            detect_phred_encoding(&phred33),
+            Some(PhredEncoding::Phred33)
+        );
+
+        let phred64 = reads(&[b"hhhhBh", b"eee^Ke"]);
+        assert_eq!(
+            detect_phred_encoding(&phred64),
+            Some(PhredEncoding::Phred64)
+        );
+
+        // Characters between ';' and 'J' are valid in both encodings
+        let ambiguous = reads(&[b"IIIIDI", b"FFF@BF"]);
+        assert_eq!(detect_phred_encoding(&ambiguous), None);
+        assert_eq!(detect_phred_encoding(&[]), None);
+    }
+
+    #[test]
+    fn test_bin_quality() {
+        const NOVASEQ: [(u8, u8); 4] = [(2, 2), (14, 12), (30, 23), (41, 37)];
+        let phred: Vec<u8> = vec![0, 2, 3, 10, 14, 15, 30, 31, 40, 41];
+        let read = FastqRecord::new(
+            "r".to_string(),
+            "ACGTACGTAC".to_string(),
+            phred.iter().map(|q| q + 33).collect(),
+        )
+        .unwrap();
+
+        let binned = read.bin_quality(&NOVASEQ, PhredEncoding::Phred33).unwrap();
+        let scores: Vec<u8> = binned.quality().iter().map(|q| q - 33).collect();
+        assert_eq!(scores, vec![2, 2, 12, 12, 12, 23, 23, 37, 37, 37]);
+        assert_eq!(binned.sequence(), read.sequence());
+    }
+
+    #[test]
+    fn test_bin_quality_rejects_bad_bins() {
+        let read = FastqRecord::new("r".to_string(), "AC".to_string(), vec![40, 60]).unwrap();
+        let encoding = PhredEncoding::Phred33;
+
+        // Stops short of Q41
+        assert!(read.bin_quality(&[(14, 12), (30, 23)], encoding).is_err());
+        // Overlapping maxes
+        assert!(read
+            .bin_quality(&[(14, 12), (10, 5), (41, 37)], encoding)
+            .is_err());
+        // Representative outside its bin
+        assert!(read.bin_quality(&[(14, 20), (41, 37)], encoding).is_err());
+        assert!(read.bin_quality(&[], encoding).is_err());
+    }
+
+    #[test]
+    fn test_composition_profile_accumulates() {
+        let mut profile = CompositionProfile::new();
+        profile.add_sequence("GGCCAATT");
+        profile.add_sequence("acgunnxx");
+
+        assert_eq!(profile.total(), 16);
+        assert_eq!(profile.gc_count(), 6);
+        assert_eq!(profile.gc_fraction(), 6.0 / 16.0);
+        assert_eq!(profile.n_fraction(), 2.0 / 16.0);
+        assert_eq!(profile.fraction('u'), 1.0 / 16.0);
+        assert_eq!(profile.fraction('-'), 2.0 / 16.0);
+        assert_eq!(CompositionProfile::new().gc_fraction(), 0.0);
+    }
+
     #[test]
     fn test_gc_content() {
         let record =
@@ -0,0 +1,208 @@
+//! Compressed sparse row storage for mostly-zero expression matrices
+//!
+//! A dense [`ExpressionMatrix`] takes 8 bytes for every gene × sample cell.
+//! [`SparseExpressionMatrix`] takes 16 bytes per non-zero cell (the value and
+//! its column index) plus 8 bytes per gene, so it is smaller once fewer than
+//! about half the cells are non-zero. Single-cell count matrices, typically
+//! over 90% zeros, shrink by an order of magnitude.
+//!
+//! The cost is access time: a cell lookup is a binary search within its row
+//! instead of an index computation, and whole columns are gathered from every
+//! row. Use the dense matrix for bulk RNA-seq and other mostly non-zero data,
+//! and for per-cell random access; use the sparse one when memory dominates.
+//! Both implement [`ExpressionData`], so code written against the trait works
+//! with either.
+
+use crate::error::{DataModelError, Result};
+use crate::expression::{check_factors, ExpressionData, ExpressionMatrix, GeneId};
+use serde::{Deserialize, Serialize};
+
+/// Gene × sample expression matrix storing only non-zero values
+///
+/// Row `g` holds the values `values[row_ptr[g]..row_ptr[g + 1]]`, in
+/// increasing sample order given by the matching `col_indices`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SparseExpressionMatrix {
+    /// Gene identifiers, one per row
+    genes: Vec<GeneId>,
+    /// Sample names, one per column
+    samples: Vec<String>,
+    /// Non-zero values in row-major order
+    values: Vec<f64>,
+    /// Sample column of each value
+    col_indices: Vec<usize>,
+    /// Offset of each row's first value, plus the total count at the end
+    row_ptr: Vec<usize>,
+}
+
+impl SparseExpressionMatrix {
+    /// Convert a dense matrix, dropping its zero cells
+    ///
+    /// Gene annotations are not carried over.
+    pub fn from_dense(matrix: &ExpressionMatrix) -> Self {
+        let mut values = Vec::new();
+        let mut col_indices = Vec::new();
+        let mut row_ptr = Vec::with_capacity(matrix.n_genes() + 1);
+        row_ptr.push(0);
+        for g in 0..matrix.n_genes() {
+            for (s, &value) in matrix.row(g).iter().enumerate() {
+                if value != 0.0 {
+                    values.push(value);
+                    col_indices.push(s);
+                }
+            }
+            row_ptr.push(values.len());
+        }
+
+        Self {
+            genes: matrix.genes().to_vec(),
+            samples: matrix.samples().to_vec(),
+            values,
+            col_indices,
+            row_ptr,
+        }
+    }
+
+    /// Convert to a dense matrix, filling in the zero cells
+    pub fn to_dense(&self) -> Result<ExpressionMatrix> {
+        let n = self.samples.len();
+        let mut values = vec![0.0; self.genes.len() * n];
+        for g in 0..self.genes.len() {
+            for (s, value) in self.row(g) {
+                values[g * n + s] = value;
+            }
+        }
+        ExpressionMatrix::new(self.genes.clone(), self.samples.clone(), values)
+    }
+
+    /// Get the number of stored (non-zero) values
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Get the `(sample, value)` pairs of a gene row's non-zero cells
+    ///
+    /// Panics if `gene` is out of range, like [`ExpressionMatrix::row`].
+    pub fn row(&self, gene: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let range = self.row_ptr[gene]..self.row_ptr[gene + 1];
+        self.col_indices[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.values[range].iter().copied())
+    }
+}
+
+impl ExpressionData for SparseExpressionMatrix {
+    fn genes(&self) -> &[GeneId] {
+        &self.genes
+    }
+
+    fn samples(&self) -> &[String] {
+        &self.samples
+    }
+
+    fn get(&self, gene: usize, sample: usize) -> Option<f64> {
+        if gene >= self.genes.len() || sample >= self.samples.len() {
+            return None;
+        }
+        let range = self.row_ptr[gene]..self.row_ptr[gene + 1];
+        let found = self.col_indices[range.clone()].binary_search(&sample);
+        Some(found.map_or(0.0, |i| self.values[range.start + i]))
+    }
+
+    fn column_sums(&self) -> Vec<f64> {
+        let mut sums = vec![0.0; self.samples.len()];
+        for (&s, value) in self.col_indices.iter().zip(&self.values) {
+            sums[s] += value;
+        }
+        sums
+    }
+
+    fn scale_columns(&self, factors: &[f64]) -> Result<Self> {
+        check_factors(factors, self.samples.len())?;
+        let mut scaled = self.clone();
+        for (value, &s) in scaled.values.iter_mut().zip(&self.col_indices) {
+            *value *= factors[s];
+        }
+        Ok(scaled)
+    }
+}
+
+impl TryFrom<&SparseExpressionMatrix> for ExpressionMatrix {
+    type Error = DataModelError;
+
+    fn try_from(matrix: &SparseExpressionMatrix) -> Result<Self> {
+        matrix.to_dense()
+    }
+}
+
+impl From<&ExpressionMatrix> for SparseExpressionMatrix {
+    fn from(matrix: &ExpressionMatrix) -> Self {
+        Self::from_dense(matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mostly_zero() -> ExpressionMatrix {
+        let mut values = vec![0.0; 5 * 4];
+        values[1] = 3.0; // gene 0, sample 1
+        values[4 * 2] = 7.0; // gene 2, sample 0
+        values[4 * 2 + 3] = 1.0; // gene 2, sample 3
+        values[4 * 4 + 1] = 1.0; // gene 4, sample 1
+        ExpressionMatrix::new(
+            (1..=5).map(|g| format!("G{}", g)).collect(),
+            (1..=4).map(|s| format!("cell{}", s)).collect(),
+            values,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_dense_sparse_round_trip() {
+        let dense = mostly_zero();
+        let sparse = SparseExpressionMatrix::from_dense(&dense);
+
+        assert_eq!(sparse.nnz(), 4);
+        assert_eq!(sparse.to_dense().unwrap(), dense);
+        assert_eq!(sparse.row(2).collect::<Vec<_>>(), vec![(0, 7.0), (3, 1.0)]);
+        assert_eq!(sparse.row(1).count(), 0);
+
+        for g in 0..dense.n_genes() {
+            for s in 0..dense.n_samples() {
+                assert_eq!(
+                    ExpressionData::get(&sparse, g, s),
+                    dense.get(g, s),
+                    "({}, {})",
+                    g,
+                    s
+                );
+            }
+        }
+        assert_eq!(ExpressionData::get(&sparse, 5, 0), None);
+        assert_eq!(ExpressionData::gene_index(&sparse, "G3"), Some(2));
+    }
+
+    #[test]
+    fn test_cpm_matches_dense() {
+        let dense = mostly_zero();
+        let sparse = SparseExpressionMatrix::from_dense(&dense);
+
+        let dense_cpm = dense.cpm().unwrap();
+        let sparse_cpm = sparse.cpm().unwrap();
+        assert_eq!(sparse_cpm.to_dense().unwrap(), dense_cpm);
+        assert_eq!(dense_cpm.get(0, 1), Some(750_000.0));
+        // cell3 has no counts and stays at zero
+        for (sum, expected) in sparse_cpm
+            .column_sums()
+            .into_iter()
+            .zip([1e6, 1e6, 0.0, 1e6])
+        {
+            assert!((sum - expected).abs() < 1e-6);
+        }
+
+        assert!(sparse.scale_columns(&[1.0]).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Writers for tabular exports
+//!
+//! Exporters such as [`ExpressionMatrix::write_annotated`] and
+//! [`write_guide_library`] format each cell as text and hand a header and
+//! rows to a [`TableWriter`], which owns the output format: [`CsvWriter`],
+//! [`TsvWriter`] or [`JsonLinesWriter`]. Quoting and escaping live here, so
+//! every exporter handles awkward values the same way.
+//!
+//! [`ExpressionMatrix::write_annotated`]: crate::expression::ExpressionMatrix::write_annotated
+//! [`write_guide_library`]: crate::guides::write_guide_library
+
+use crate::error::{DataModelError, Result};
+use std::io::Write;
+
+/// Destination for a table of text cells
+///
+/// [`TableWriter::write_header`] is called once, before any row, and every
+/// row must have one field per header column.
+pub trait TableWriter {
+    /// Write the column names
+    fn write_header(&mut self, columns: &[&str]) -> Result<()>;
+
+    /// Write one row of already formatted fields
+    fn write_row(&mut self, fields: &[String]) -> Result<()>;
+}
+
+/// Check that a row matches the header written before it
+fn check_row_len(columns: Option<usize>, fields: usize) -> Result<()> {
+    match columns {
+        None => Err(DataModelError::ValidationError(
+            "row written before the table header".to_string(),
+        )),
+        Some(columns) if columns != fields => Err(DataModelError::ValidationError(format!(
+            "row has {} fields but the header has {} columns",
+            fields, columns
+        ))),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Comma-separated values following RFC 4180
+///
+/// Fields containing a comma, double quote or line break are wrapped in
+/// double quotes, with embedded quotes doubled.
+pub struct CsvWriter<W: Write> {
+    writer: W,
+    columns: Option<usize>,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Create a CSV writer over `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            columns: None,
+        }
+    }
+
+    /// Get the underlying writer back
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_line<S: AsRef<str>>(&mut self, fields: &[S]) -> Result<()> {
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b",")?;
+            }
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n', '\r']) {
+                write!(self.writer, "\"{}\"", field.replace('"', "\"\""))?;
+            } else {
+                self.writer.write_all(field.as_bytes())?;
+            }
+        }
+        writeln!(self.writer)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> TableWriter for CsvWriter<W> {
+    fn write_header(&mut self, columns: &[&str]) -> Result<()> {
+        self.columns = Some(columns.len());
+        self.write_line(columns)
+    }
+
+    fn write_row(&mut self, fields: &[String]) -> Result<()> {
+        check_row_len(self.columns, fields.len())?;
+        self.write_line(fields)
+    }
+}
+
+/// Tab-separated values
+///
+/// TSV has no quoting, so a field containing a tab or line break is rejected
+/// with [`DataModelError::ValidationError`] rather than written as a row that
+/// would read back with the wrong columns.
+pub struct TsvWriter<W: Write> {
+    writer: W,
+    columns: Option<usize>,
+}
+
+impl<W: Write> TsvWriter<W> {
+    /// Create a TSV writer over `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            columns: None,
+        }
+    }
+
+    /// Get the underlying writer back
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_line<S: AsRef<str>>(&mut self, fields: &[S]) -> Result<()> {
+        if let Some(field) = fields
+            .iter()
+            .map(AsRef::as_ref)
+            .find(|f| f.contains(['\t', '\n', '\r']))
+        {
+            return Err(DataModelError::ValidationError(format!(
+                "TSV field {:?} contains a tab or line break",
+                field
+            )));
+        }
+        let line: Vec<&str> = fields.iter().map(AsRef::as_ref).collect();
+        writeln!(self.writer, "{}", line.join("\t"))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> TableWriter for TsvWriter<W> {
+    fn write_header(&mut self, columns: &[&str]) -> Result<()> {
+        self.columns = Some(columns.len());
+        self.write_line(columns)
+    }
+
+    fn write_row(&mut self, fields: &[String]) -> Result<()> {
+        check_row_len(self.columns, fields.len())?;
+        self.write_line(fields)
+    }
+}
+
+/// One JSON object per row, keyed by column name in header order
+///
+/// The header itself writes nothing. Fields are written as JSON strings,
+/// exactly as the exporter formatted them.
+pub struct JsonLinesWriter<W: Write> {
+    writer: W,
+    columns: Option<Vec<String>>,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    /// Create a JSON Lines writer over `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            columns: None,
+        }
+    }
+
+    /// Get the underlying writer back
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> TableWriter for JsonLinesWriter<W> {
+    fn write_header(&mut self, columns: &[&str]) -> Result<()> {
+        self.columns = Some(columns.iter().map(|c| c.to_string()).collect());
+        Ok(())
+    }
+
+    fn write_row(&mut self, fields: &[String]) -> Result<()> {
+        check_row_len(self.columns.as_ref().map(Vec::len), fields.len())?;
+        let columns = self.columns.as_deref().unwrap_or_default();
+
+        self.writer.write_all(b"{")?;
+        for (i, (column, field)) in columns.iter().zip(fields).enumerate() {
+            if i > 0 {
+                self.writer.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut self.writer, column)?;
+            self.writer.write_all(b":")?;
+            serde_json::to_writer(&mut self.writer, field)?;
+        }
+        writeln!(self.writer, "}}")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let mut table = CsvWriter::new(Vec::new());
+        table.write_header(&["id", "note"]).unwrap();
+        table.write_row(&row(&["g1", "plain"])).unwrap();
+        table.write_row(&row(&["g2", "a, b"])).unwrap();
+        table.write_row(&row(&["g3", "say \"hi\""])).unwrap();
+
+        let out = String::from_utf8(table.into_inner()).unwrap();
+        assert_eq!(
+            out,
+            "id,note\ng1,plain\ng2,\"a, b\"\ng3,\"say \"\"hi\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_tsv_rejects_tabs_and_mismatched_rows() {
+        let mut table = TsvWriter::new(Vec::new());
+        assert!(table.write_row(&row(&["early"])).is_err());
+        table.write_header(&["id", "note"]).unwrap();
+        assert!(table.write_row(&row(&["g1", "a\tb"])).is_err());
+        assert!(table.write_row(&row(&["g1"])).is_err());
+        table.write_row(&row(&["g1", "a, b"])).unwrap();
+
+        let out = String::from_utf8(table.into_inner()).unwrap();
+        assert_eq!(out, "id\tnote\ng1\ta, b\n");
+    }
+
+    #[test]
+    fn test_json_lines_keep_column_order() {
+        let mut table = JsonLinesWriter::new(Vec::new());
+        table.write_header(&["z", "a"]).unwrap();
+        table.write_row(&row(&["1", "say \"hi\""])).unwrap();
+
+        let out = String::from_utf8(table.into_inner()).unwrap();
+        assert_eq!(out, "{\"z\":\"1\",\"a\":\"say \\\"hi\\\"\"}\n");
+    }
+}
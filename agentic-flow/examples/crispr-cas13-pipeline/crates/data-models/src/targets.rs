@@ -1,7 +1,8 @@
 //! CRISPR target and off-target prediction models
 
 use crate::error::{DataModelError, Result};
-use crate::sequencing::GenomicCoordinate;
+use crate::guides::normalize;
+use crate::sequencing::{CompositionProfile, GenomicCoordinate};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -56,12 +57,9 @@ impl CrisprTarget {
 
     /// Calculate guide RNA melting temperature (Tm)
     pub fn calculate_tm(&self) -> f64 {
-        let gc_count = self
-            .guide_rna
-            .chars()
-            .filter(|&c| matches!(c.to_ascii_uppercase(), 'G' | 'C'))
-            .count() as f64;
-        let at_count = self.guide_rna.len() as f64 - gc_count;
+        let profile = CompositionProfile::from_sequence(&self.guide_rna);
+        let gc_count = profile.gc_count() as f64;
+        let at_count = profile.total() as f64 - gc_count;
 
         // Simple Tm calculation: Tm = 4(G+C) + 2(A+T)
         4.0 * gc_count + 2.0 * at_count
@@ -221,10 +219,279 @@ pub struct PredictionMetadata {
     pub processing_time: f64,
 }
 
+/// Gas constant in cal/(K·mol)
+const GAS_CONSTANT: f64 = 1.987;
+
+/// SantaLucia (1998) unified nearest-neighbor parameters: ΔH (kcal/mol) and
+/// ΔS (cal/(K·mol)) per stack, listed by top-strand dinucleotide
+const NN_PARAMS: [(&str, f64, f64); 10] = [
+    ("AA", -7.9, -22.2),
+    ("AT", -7.2, -20.4),
+    ("TA", -7.2, -21.3),
+    ("CA", -8.5, -22.7),
+    ("GT", -8.4, -22.4),
+    ("CT", -7.8, -21.0),
+    ("GA", -8.2, -22.2),
+    ("CG", -10.6, -27.2),
+    ("GC", -9.8, -24.4),
+    ("GG", -8.0, -19.9),
+];
+
+/// Method used to estimate oligo melting temperature
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TmMethod {
+    /// Wallace rule, `2(A+T) + 4(G+C)`; only meaningful for oligos under ~14 nt
+    Wallace,
+    /// SantaLucia (1998) nearest-neighbor thermodynamics with a monovalent
+    /// salt correction
+    NearestNeighbor {
+        /// Na+ concentration in mM
+        sodium_mm: f64,
+        /// Total oligo strand concentration in nM
+        oligo_nm: f64,
+    },
+}
+
+impl Default for TmMethod {
+    fn default() -> Self {
+        TmMethod::NearestNeighbor {
+            sodium_mm: 50.0,
+            oligo_nm: 250.0,
+        }
+    }
+}
+
+/// Estimate the melting temperature of a DNA/RNA oligo in °C
+///
+/// `U` is treated as `T`, so RNA input is scored with DNA duplex parameters.
+/// Only A, C, G, T/U are accepted.
+pub fn melting_temp(seq: &str, method: TmMethod) -> Result<f64> {
+    let seq = normalize(seq);
+    if let Some(&b) = seq.iter().find(|b| !matches!(b, b'A' | b'C' | b'G' | b'T')) {
+        return Err(DataModelError::InvalidSequence(format!(
+            "invalid base '{}' for melting temperature",
+            b as char
+        )));
+    }
+
+    let gc = seq.iter().filter(|&&b| b == b'G' || b == b'C').count() as f64;
+    match method {
+        TmMethod::Wallace => Ok(2.0 * (seq.len() as f64 - gc) + 4.0 * gc),
+        TmMethod::NearestNeighbor {
+            sodium_mm,
+            oligo_nm,
+        } => {
+            if seq.len() < 2 {
+                return Err(DataModelError::InvalidSequence(
+                    "nearest-neighbor Tm needs at least 2 bases".to_string(),
+                ));
+            }
+            if sodium_mm <= 0.0 || oligo_nm <= 0.0 {
+                return Err(DataModelError::ValidationError(
+                    "salt and oligo concentrations must be positive".to_string(),
+                ));
+            }
+
+            let (mut dh, mut ds) = (0.0, 0.0);
+            for end in [seq[0], seq[seq.len() - 1]] {
+                let (h, s) = if matches!(end, b'G' | b'C') {
+                    (0.1, -2.8)
+                } else {
+                    (2.3, 4.1)
+                };
+                dh += h;
+                ds += s;
+            }
+            for pair in seq.windows(2) {
+                let (h, s) = nn_stack(pair[0], pair[1]);
+                dh += h;
+                ds += s;
+            }
+
+            let self_complementary = seq
+                .iter()
+                .zip(seq.iter().rev())
+                .all(|(&a, &b)| complement(a) == b);
+            let strand_conc = oligo_nm * 1e-9;
+            let effective_conc = if self_complementary {
+                ds -= 1.4;
+                strand_conc
+            } else {
+                strand_conc / 4.0
+            };
+            ds += 0.368 * (seq.len() - 1) as f64 * (sodium_mm / 1000.0).ln();
+
+            Ok(dh * 1000.0 / (ds + GAS_CONSTANT * effective_conc.ln()) - 273.15)
+        }
+    }
+}
+
+/// Look up the ΔH/ΔS of a dinucleotide stack, via its reverse complement if needed
+fn nn_stack(a: u8, b: u8) -> (f64, f64) {
+    let forward = [a, b];
+    let reverse = [complement(b), complement(a)];
+    NN_PARAMS
+        .iter()
+        .find(|(pair, _, _)| pair.as_bytes() == forward || pair.as_bytes() == reverse)
+        .map(|&(_, h, s)| (h, s))
+        .unwrap_or((0.0, 0.0))
+}
+
+/// Complement of a normalized DNA base
+pub(crate) fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'T' => b'A',
+        b'C' => b'G',
+        b'G' => b'C',
+        other => other,
+    }
+}
+
+/// Get the reverse complement of a DNA sequence
+///
+/// Input is upper-cased and `U` is read as `T`; bases other than A, C, G, T
+/// are kept as they are.
+pub fn reverse_complement(seq: &str) -> String {
+    seq.bytes()
+        .rev()
+        .map(|b| match b.to_ascii_uppercase() {
+            b'U' => b'A',
+            other => complement(other),
+        } as char)
+        .collect()
+}
+
+/// Count the positions at which two equal-length sequences differ
+///
+/// Sequences are compared byte by byte and case-sensitively; normalize case
+/// and `U`/`T` beforehand if those should be treated as equal.
+pub fn hamming_distance(a: &str, b: &str) -> Result<usize> {
+    if a.len() != b.len() {
+        return Err(DataModelError::ValidationError(format!(
+            "hamming distance needs equal lengths, got {} and {}",
+            a.len(),
+            b.len()
+        )));
+    }
+    Ok(mismatch_count(a.as_bytes(), b.as_bytes()))
+}
+
+/// Count differing positions of two byte sequences, up to the shorter length
+pub(crate) fn mismatch_count(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).filter(|(x, y)| x != y).count()
+}
+
+/// Compute the Levenshtein distance (substitutions, insertions and deletions)
+/// between two sequences
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, &x) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &y) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(x != y);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Set of bases matched by an IUPAC nucleotide code, as a bitmask of
+/// A = 1, C = 2, G = 4, T/U = 8; 0 for anything else
+fn iupac_mask(code: u8) -> u8 {
+    match code.to_ascii_uppercase() {
+        b'A' => 0b0001,
+        b'C' => 0b0010,
+        b'G' => 0b0100,
+        b'T' | b'U' => 0b1000,
+        b'R' => 0b0101,
+        b'Y' => 0b1010,
+        b'S' => 0b0110,
+        b'W' => 0b1001,
+        b'K' => 0b1100,
+        b'M' => 0b0011,
+        b'B' => 0b1110,
+        b'D' => 0b1101,
+        b'H' => 0b1011,
+        b'V' => 0b0111,
+        b'N' => 0b1111,
+        _ => 0,
+    }
+}
+
+/// Find every start position where a degenerate motif matches a sequence
+///
+/// `motif` may use IUPAC ambiguity codes (e.g. `DRACH` for m6A sites); each
+/// code matches the bases it stands for. Matching is case-insensitive, `U`
+/// equals `T`, and overlapping matches are all reported. An ambiguous base in
+/// the sequence only matches a motif code covering all of its bases, so `N`
+/// in the sequence matches only `N` in the motif.
+pub fn find_motif(seq: &str, motif: &str) -> Result<Vec<usize>> {
+    if motif.is_empty() {
+        return Err(DataModelError::ValidationError(
+            "motif is empty".to_string(),
+        ));
+    }
+    let motif_masks = motif
+        .bytes()
+        .map(|code| match iupac_mask(code) {
+            0 => Err(DataModelError::InvalidSequence(format!(
+                "invalid IUPAC code '{}' in motif '{}'",
+                code as char, motif
+            ))),
+            mask => Ok(mask),
+        })
+        .collect::<Result<Vec<u8>>>()?;
+
+    let seq_masks: Vec<u8> = seq.bytes().map(iupac_mask).collect();
+    Ok(seq_masks
+        .windows(motif_masks.len())
+        .enumerate()
+        .filter(|(_, window)| {
+            window
+                .iter()
+                .zip(&motif_masks)
+                .all(|(&base, &code)| base != 0 && base & code == base)
+        })
+        .map(|(start, _)| start)
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_find_motif_drach() {
+        // DRACH: D=[AGT] R=[AG] A C H=[ACT]
+        let seq = "CCCGGACTCCCAGACAUUUU";
+        assert_eq!(find_motif(seq, "DRACH").unwrap(), vec![3, 11]);
+        assert_eq!(
+            find_motif(&seq.to_lowercase(), "drach").unwrap(),
+            vec![3, 11]
+        );
+
+        assert!(find_motif("CCCCCCCCCCGGGGGGGG", "DRACH")
+            .unwrap()
+            .is_empty());
+        assert!(find_motif("AC", "DRACH").unwrap().is_empty());
+        assert!(find_motif("GGNCA", "GGACH").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_motif_rejects_invalid_codes() {
+        assert!(matches!(
+            find_motif("ACGT", "ACXT"),
+            Err(DataModelError::InvalidSequence(_))
+        ));
+        assert!(find_motif("ACGT", "").is_err());
+    }
+
     #[test]
     fn test_crispr_target_creation() {
         let location = GenomicCoordinate::new(0, 1000, 1023, true).unwrap();
@@ -278,4 +545,64 @@ mod tests {
 
         assert_eq!(prediction.specificity_score(), 1.0);
     }
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement("AACGTN"), "NACGTT");
+        assert_eq!(reverse_complement("gaUc"), "GATC");
+    }
+
+    #[test]
+    fn test_hamming_distance() {
+        assert_eq!(hamming_distance("ACGTACGT", "ACGTACGT").unwrap(), 0);
+        assert_eq!(hamming_distance("ACGTACGT", "ACCTACGA").unwrap(), 2);
+
+        let err = hamming_distance("ACGT", "ACG").unwrap_err();
+        assert!(matches!(err, DataModelError::ValidationError(_)));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        // Single inserted base
+        assert_eq!(edit_distance("ACGTACGT", "ACGGTACGT"), 1);
+        assert_eq!(edit_distance("ACGT", "ACGT"), 0);
+        assert_eq!(edit_distance("", "ACG"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_melting_temp_wallace() {
+        // 6 A/T and 6 G/C: 2 * 6 + 4 * 6
+        assert_eq!(
+            melting_temp("ACGTACGTACGT", TmMethod::Wallace).unwrap(),
+            36.0
+        );
+        assert_eq!(melting_temp("acgu", TmMethod::Wallace).unwrap(), 12.0);
+        assert!(matches!(
+            melting_temp("ACGNT", TmMethod::Wallace),
+            Err(DataModelError::InvalidSequence(_))
+        ));
+    }
+
+    #[test]
+    fn test_melting_temp_nearest_neighbor() {
+        let primer = "AGCGGATAACAATTTCACACAGGA";
+        let wallace = melting_temp(primer, TmMethod::Wallace).unwrap();
+        let nn = melting_temp(primer, TmMethod::default()).unwrap();
+
+        // The Wallace rule overestimates long oligos
+        assert!(nn > 55.0 && nn < 65.0, "nearest-neighbor Tm {}", nn);
+        assert!(wallace - nn > 5.0);
+        assert!(nn.fract() != 0.0);
+
+        let high_salt = melting_temp(
+            primer,
+            TmMethod::NearestNeighbor {
+                sodium_mm: 200.0,
+                oligo_nm: 250.0,
+            },
+        )
+        .unwrap();
+        assert!(high_salt > nn);
+    }
 }
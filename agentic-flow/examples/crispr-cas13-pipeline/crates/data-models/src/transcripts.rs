@@ -0,0 +1,427 @@
+//! Exon structure of transcripts and splice-aware target placement
+//!
+//! Guides act on mature transcripts, so a target site must be found in the
+//! spliced sequence. A spacer that only matches the genome contiguously
+//! across an exon-intron boundary would target unspliced pre-mRNA alone;
+//! [`validate_target_placement`] tells those apart from sites inside an exon
+//! or across an exon-exon junction.
+//!
+//! For coding transcripts, [`TranscriptModel::cds_boundary_proximity`]
+//! flags guides targeting close to the start or stop codon.
+
+use crate::error::{DataModelError, Result};
+use crate::guides::{find_target_site, GuideRna, TargetSearchConfig};
+use crate::intervals::BedInterval;
+use crate::targets::reverse_complement;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Length of a codon in bases
+const CODON_LEN: usize = 3;
+
+/// Exons of a transcript on a genomic sequence
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptModel {
+    /// Transcript identifier
+    pub id: String,
+    /// Exons in genomic order
+    exons: Vec<BedInterval>,
+    /// Whether the transcript is read from the reverse strand
+    pub reverse: bool,
+    /// Coding sequence in transcript-space coordinates, from the first base
+    /// of the start codon to the last base of the stop codon; `None` for
+    /// noncoding transcripts
+    #[serde(default)]
+    cds: Option<Range<usize>>,
+}
+
+/// Which end of the coding sequence a target site is closest to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CdsBoundary {
+    /// The start codon
+    Start,
+    /// The stop codon
+    Stop,
+}
+
+/// Distance of a guide's target site from the nearest CDS boundary codon
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CdsProximity {
+    /// Codon the site is closest to
+    pub boundary: CdsBoundary,
+    /// Bases between the site and that codon; 0 when they overlap
+    pub distance: usize,
+    /// Whether `distance` is within the requested maximum
+    pub flagged: bool,
+}
+
+impl TranscriptModel {
+    /// Create a model from genomic exon intervals, in any order
+    ///
+    /// Fails with [`DataModelError::ValidationError`] when there are no exons
+    /// or two of them overlap.
+    pub fn new(id: String, mut exons: Vec<BedInterval>, reverse: bool) -> Result<Self> {
+        exons.sort_by_key(|exon| exon.start());
+        if exons.is_empty() {
+            return Err(DataModelError::ValidationError(format!(
+                "transcript '{}' has no exons",
+                id
+            )));
+        }
+        if let Some(pair) = exons
+            .windows(2)
+            .find(|pair| pair[1].start() < pair[0].end())
+        {
+            return Err(DataModelError::ValidationError(format!(
+                "transcript '{}' has overlapping exons {}-{} and {}-{}",
+                id,
+                pair[0].start(),
+                pair[0].end(),
+                pair[1].start(),
+                pair[1].end()
+            )));
+        }
+        Ok(Self {
+            id,
+            exons,
+            reverse,
+            cds: None,
+        })
+    }
+
+    /// Set the coding sequence, in transcript-space coordinates 5' to 3'
+    ///
+    /// Fails with [`DataModelError::ValidationError`] unless the CDS lies
+    /// within the spliced transcript and is long enough to hold a start and a
+    /// stop codon.
+    pub fn with_cds(mut self, cds: Range<usize>) -> Result<Self> {
+        if cds.start + 2 * CODON_LEN > cds.end || cds.end as u64 > self.length() {
+            return Err(DataModelError::ValidationError(format!(
+                "CDS {}..{} does not fit transcript '{}' of length {}",
+                cds.start,
+                cds.end,
+                self.id,
+                self.length()
+            )));
+        }
+        self.cds = Some(cds);
+        Ok(self)
+    }
+
+    /// Get the coding sequence range, if the transcript is coding
+    pub fn cds(&self) -> Option<Range<usize>> {
+        self.cds.clone()
+    }
+
+    /// Get the exons in genomic order
+    pub fn exons(&self) -> &[BedInterval] {
+        &self.exons
+    }
+
+    /// Get the spliced length
+    pub fn length(&self) -> u64 {
+        self.exons.iter().map(BedInterval::length).sum()
+    }
+
+    /// Get the transcript-space range of each exon, 5' to 3'
+    pub fn exon_ranges(&self) -> Vec<Range<usize>> {
+        let mut lengths: Vec<usize> = self.exons.iter().map(|e| e.length() as usize).collect();
+        if self.reverse {
+            lengths.reverse();
+        }
+        let mut start = 0;
+        lengths
+            .into_iter()
+            .map(|len| {
+                start += len;
+                start - len..start
+            })
+            .collect()
+    }
+
+    /// Get the mature transcript sequence, 5' to 3', from the genomic
+    /// sequence the exons are placed on
+    pub fn spliced_sequence(&self, genome: &str) -> Result<String> {
+        let mut sequence = String::with_capacity(self.length() as usize);
+        for exon in &self.exons {
+            sequence.push_str(self.genomic_slice(genome, exon.start(), exon.end())?);
+        }
+        Ok(self.orient(sequence))
+    }
+
+    /// Get the unspliced pre-mRNA sequence, 5' to 3', from the first exon
+    /// start to the last exon end
+    pub fn pre_mrna_sequence(&self, genome: &str) -> Result<String> {
+        let (first, last) = (&self.exons[0], &self.exons[self.exons.len() - 1]);
+        let sequence = self.genomic_slice(genome, first.start(), last.end())?;
+        Ok(self.orient(sequence.to_string()))
+    }
+
+    /// Check whether a guide targets close to the start or stop codon
+    ///
+    /// The guide's site is located in the spliced sequence as in
+    /// [`validate_target_placement`] and compared with the start and stop
+    /// codons; the result describes the nearer one and is flagged when it is
+    /// at most `max_distance` bases away. Returns `Ok(None)` for noncoding
+    /// transcripts. Fails with [`DataModelError::ValidationError`] when the
+    /// guide has no site in the mature transcript, or if an exon lies outside
+    /// `genome`.
+    pub fn cds_boundary_proximity(
+        &self,
+        genome: &str,
+        guide: &GuideRna,
+        max_distance: usize,
+    ) -> Result<Option<CdsProximity>> {
+        let Some(cds) = &self.cds else {
+            return Ok(None);
+        };
+        let spliced = self.spliced_sequence(genome)?;
+        let site = find_target_site(&spliced, &guide.spacer, &TargetSearchConfig::default())
+            .ok_or_else(|| {
+                DataModelError::ValidationError(format!(
+                    "guide '{}' has no target site in transcript '{}'",
+                    guide.id, self.id
+                ))
+            })?;
+
+        let gap = |codon: Range<usize>| {
+            codon
+                .start
+                .saturating_sub(site.end)
+                .max(site.start.saturating_sub(codon.end))
+        };
+        let start = gap(cds.start..cds.start + CODON_LEN);
+        let stop = gap(cds.end - CODON_LEN..cds.end);
+        let (boundary, distance) = if start <= stop {
+            (CdsBoundary::Start, start)
+        } else {
+            (CdsBoundary::Stop, stop)
+        };
+        Ok(Some(CdsProximity {
+            boundary,
+            distance,
+            flagged: distance <= max_distance,
+        }))
+    }
+
+    fn genomic_slice<'g>(&self, genome: &'g str, start: u64, end: u64) -> Result<&'g str> {
+        genome
+            .get(start as usize..end as usize)
+            .ok_or(DataModelError::InvalidRange { start, end })
+    }
+
+    fn orient(&self, sequence: String) -> String {
+        if self.reverse {
+            reverse_complement(&sequence)
+        } else {
+            sequence
+        }
+    }
+}
+
+/// Where a guide's target site lies relative to a transcript's exons
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TargetPlacement {
+    /// The site lies inside one exon
+    WithinExon {
+        /// Exon index, 5' to 3'
+        exon: usize,
+        /// Transcript-space start of the site
+        start: usize,
+    },
+    /// The site spans one or more exon-exon junctions
+    SpansJunction {
+        /// Indices of the exons the site covers, 5' to 3'
+        exons: Vec<usize>,
+        /// Transcript-space start of the site
+        start: usize,
+    },
+    /// The site is only found in the unspliced pre-mRNA
+    PreMrnaOnly {
+        /// Pre-mRNA start of the site
+        start: usize,
+    },
+    /// The site is found in neither sequence
+    NotFound,
+}
+
+impl TargetPlacement {
+    /// Check if the guide targets the mature transcript
+    pub fn is_valid(&self) -> bool {
+        matches!(
+            self,
+            TargetPlacement::WithinExon { .. } | TargetPlacement::SpansJunction { .. }
+        )
+    }
+}
+
+/// Locate a guide's target site in the spliced and unspliced transcript
+///
+/// The spliced sequence is searched first with the default
+/// [`TargetSearchConfig`], so only exact matches count; the pre-mRNA is only
+/// searched when the spliced sequence has no site. Fails if an exon lies
+/// outside `genome`.
+pub fn validate_target_placement(
+    model: &TranscriptModel,
+    genome: &str,
+    guide: &GuideRna,
+) -> Result<TargetPlacement> {
+    let config = TargetSearchConfig::default();
+    let spliced = model.spliced_sequence(genome)?;
+    if let Some(site) = find_target_site(&spliced, &guide.spacer, &config) {
+        let exons: Vec<usize> = model
+            .exon_ranges()
+            .iter()
+            .enumerate()
+            .filter(|(_, range)| range.start < site.end && site.start < range.end)
+            .map(|(i, _)| i)
+            .collect();
+        return Ok(match exons.as_slice() {
+            [exon] => TargetPlacement::WithinExon {
+                exon: *exon,
+                start: site.start,
+            },
+            _ => TargetPlacement::SpansJunction {
+                exons,
+                start: site.start,
+            },
+        });
+    }
+
+    let pre_mrna = model.pre_mrna_sequence(genome)?;
+    Ok(match find_target_site(&pre_mrna, &guide.spacer, &config) {
+        Some(site) => TargetPlacement::PreMrnaOnly { start: site.start },
+        None => TargetPlacement::NotFound,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Exon 1, intron, exon 2
+    const GENOME: &str = concat!("ACGTTGCAAG", "GTAAGTTTAG", "CCATGGTACC");
+
+    fn model(reverse: bool) -> TranscriptModel {
+        let exons = vec![
+            BedInterval::new(20, 30).unwrap(),
+            BedInterval::new(0, 10).unwrap(),
+        ];
+        TranscriptModel::new("tx1".to_string(), exons, reverse).unwrap()
+    }
+
+    fn place(model: &TranscriptModel, spacer: &str) -> TargetPlacement {
+        let guide = GuideRna::new("g".to_string(), spacer.to_string());
+        validate_target_placement(model, GENOME, &guide).unwrap()
+    }
+
+    #[test]
+    fn test_transcript_sequences() {
+        let model = model(false);
+        assert_eq!(model.length(), 20);
+        assert_eq!(model.exon_ranges(), vec![0..10, 10..20]);
+        assert_eq!(
+            model.spliced_sequence(GENOME).unwrap(),
+            "ACGTTGCAAGCCATGGTACC"
+        );
+        assert_eq!(model.pre_mrna_sequence(GENOME).unwrap(), GENOME);
+        assert!(model.spliced_sequence("ACGT").is_err());
+
+        let overlapping = vec![
+            BedInterval::new(0, 10).unwrap(),
+            BedInterval::new(5, 15).unwrap(),
+        ];
+        assert!(TranscriptModel::new("tx".to_string(), overlapping, false).is_err());
+    }
+
+    #[test]
+    fn test_cds_boundary_proximity() {
+        let genome = "GCCATGGACCTTGAGCAGTCAAGGTCCATTAGCCTGATAACG";
+        let exons = vec![BedInterval::new(0, 42).unwrap()];
+        let noncoding = TranscriptModel::new("nc".to_string(), exons, false).unwrap();
+        let coding = noncoding.clone().with_cds(3..37).unwrap();
+        let guide = |spacer: &str| GuideRna::new("g".to_string(), spacer.to_string());
+
+        // Overlaps the start codon at 3..6
+        let near_start = coding
+            .cds_boundary_proximity(genome, &guide("CCAUGGAC"), 5)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            near_start,
+            CdsProximity {
+                boundary: CdsBoundary::Start,
+                distance: 0,
+                flagged: true
+            }
+        );
+
+        // Site 12..20 is 6 bases past the start codon and 14 before the stop
+        let middle = coding
+            .cds_boundary_proximity(genome, &guide("GAGCAGUC"), 5)
+            .unwrap()
+            .unwrap();
+        assert_eq!(middle.boundary, CdsBoundary::Start);
+        assert_eq!(middle.distance, 6);
+        assert!(!middle.flagged);
+
+        let near_stop = coding
+            .cds_boundary_proximity(genome, &guide("AUUAGCCU"), 5)
+            .unwrap()
+            .unwrap();
+        assert_eq!(near_stop.boundary, CdsBoundary::Stop);
+        assert!(near_stop.flagged);
+
+        assert_eq!(
+            noncoding
+                .cds_boundary_proximity(genome, &guide("CCAUGGAC"), 5)
+                .unwrap(),
+            None
+        );
+        assert!(coding
+            .cds_boundary_proximity(genome, &guide("GGGGGGGG"), 5)
+            .is_err());
+        assert!(noncoding.clone().with_cds(3..43).is_err());
+        assert!(noncoding.with_cds(3..8).is_err());
+    }
+
+    #[test]
+    fn test_target_within_exon() {
+        let placement = place(&model(false), "CGUUGCAA");
+        assert_eq!(placement, TargetPlacement::WithinExon { exon: 0, start: 1 });
+        assert!(placement.is_valid());
+    }
+
+    #[test]
+    fn test_target_spanning_junction() {
+        let placement = place(&model(false), "CAAGCCAT");
+        assert_eq!(
+            placement,
+            TargetPlacement::SpansJunction {
+                exons: vec![0, 1],
+                start: 6
+            }
+        );
+        assert!(placement.is_valid());
+
+        // On the reverse strand the genomic second exon comes first
+        let reverse = model(true);
+        let placement = place(&reverse, &reverse_complement("CAAGCCAT"));
+        assert_eq!(
+            placement,
+            TargetPlacement::SpansJunction {
+                exons: vec![0, 1],
+                start: 6
+            }
+        );
+    }
+
+    #[test]
+    fn test_target_across_intron_is_flagged() {
+        let placement = place(&model(false), "CAAGGTAA");
+        assert_eq!(placement, TargetPlacement::PreMrnaOnly { start: 6 });
+        assert!(!placement.is_valid());
+
+        assert_eq!(place(&model(false), "GGGGGGGG"), TargetPlacement::NotFound);
+    }
+}
@@ -0,0 +1,141 @@
+//! Translation of coding sequences into protein
+
+use crate::error::{DataModelError, Result};
+use crate::guides::normalize;
+use serde::{Deserialize, Serialize};
+
+/// Genetic code used to translate codons into amino acids
+///
+/// Tables follow the NCBI numbering. Only the codon-to-amino-acid mapping is
+/// modelled; alternative start codons are not.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CodonTable {
+    /// Standard code (NCBI table 1)
+    #[default]
+    Standard,
+    /// Vertebrate mitochondrial code (NCBI table 2): `TGA` is Trp, `ATA` is
+    /// Met, and `AGA`/`AGG` are stops
+    VertebrateMitochondrial,
+    /// Yeast mitochondrial code (NCBI table 3): `TGA` is Trp, `ATA` is Met,
+    /// and `CTN` is Thr
+    YeastMitochondrial,
+}
+
+impl CodonTable {
+    /// Amino acids of the 64 codons, in `TCAG` order of the first, second,
+    /// then third base; `*` marks a stop
+    fn amino_acids(&self) -> &'static [u8; 64] {
+        match self {
+            CodonTable::Standard => {
+                b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"
+            }
+            CodonTable::VertebrateMitochondrial => {
+                b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG"
+            }
+            CodonTable::YeastMitochondrial => {
+                b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG"
+            }
+        }
+    }
+
+    /// Get the one-letter amino acid for a normalized codon, `*` for a stop
+    pub fn translate_codon(&self, codon: [u8; 3]) -> Option<char> {
+        let mut index = 0;
+        for base in codon {
+            index = index * 4 + base_index(base)?;
+        }
+        Some(self.amino_acids()[index] as char)
+    }
+}
+
+/// Translate a coding sequence into a one-letter peptide
+///
+/// Translation starts `frame` bases in (0, 1 or 2) and stops before the
+/// first stop codon, or at the end of the sequence. The whole sequence is
+/// validated first: it must hold only A, C, G, T/U (any case), and the part
+/// from `frame` on must be a whole number of codons.
+pub fn translate(seq: &str, frame: usize, table: CodonTable) -> Result<String> {
+    if frame > 2 {
+        return Err(DataModelError::ValidationError(format!(
+            "reading frame {} is not 0, 1 or 2",
+            frame
+        )));
+    }
+    let seq = normalize(seq);
+    if let Some(&b) = seq.iter().find(|&&b| base_index(b).is_none()) {
+        return Err(DataModelError::InvalidSequence(format!(
+            "invalid base '{}' for translation",
+            b as char
+        )));
+    }
+    if seq.len() < frame || !seq[frame..].chunks_exact(3).remainder().is_empty() {
+        return Err(DataModelError::InvalidSequence(format!(
+            "{} bases from frame {} is not a whole number of codons",
+            seq.len().saturating_sub(frame),
+            frame
+        )));
+    }
+
+    let peptide = seq[frame..]
+        .chunks_exact(3)
+        .map(|codon| {
+            table
+                .translate_codon([codon[0], codon[1], codon[2]])
+                .unwrap_or('X')
+        })
+        .take_while(|&aa| aa != '*')
+        .collect();
+    Ok(peptide)
+}
+
+/// Get the position of a normalized base in `TCAG` order
+fn base_index(base: u8) -> Option<usize> {
+    match base {
+        b'T' => Some(0),
+        b'C' => Some(1),
+        b'A' => Some(2),
+        b'G' => Some(3),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_orf() {
+        let orf = "ATGGCCATTGTAATGGGCCGCTGAAAGGGTGCCCGATAG";
+        assert_eq!(translate(orf, 0, CodonTable::Standard).unwrap(), "MAIVMGR");
+
+        // RNA input in frame 1, stopping at the final UAA
+        assert_eq!(
+            translate("cauggcaaauuaa", 1, CodonTable::Standard).unwrap(),
+            "MAN"
+        );
+    }
+
+    #[test]
+    fn test_translate_alternative_tables() {
+        assert_eq!(
+            translate("ATGTGAAGA", 0, CodonTable::Standard).unwrap(),
+            "M"
+        );
+        assert_eq!(
+            translate("ATGTGAAGA", 0, CodonTable::VertebrateMitochondrial).unwrap(),
+            "MW"
+        );
+        assert_eq!(
+            translate("CTGATATGA", 0, CodonTable::YeastMitochondrial).unwrap(),
+            "TMW"
+        );
+    }
+
+    #[test]
+    fn test_translate_rejects_invalid_input() {
+        assert!(translate("ATGN", 0, CodonTable::Standard).is_err());
+        assert!(translate("ATGA", 0, CodonTable::Standard).is_err());
+        assert!(translate("ATG", 3, CodonTable::Standard).is_err());
+        assert_eq!(translate("", 0, CodonTable::Standard).unwrap(), "");
+    }
+}
@@ -12,11 +12,27 @@ use uuid::Uuid;
 /// DESeq2 analyzer for differential expression
 pub struct DeseqAnalyzer {
     params: AnalysisParameters,
+    size_factors: Option<Vec<f64>>,
 }
 
 impl DeseqAnalyzer {
     pub fn new(params: AnalysisParameters) -> Self {
-        Self { params }
+        Self {
+            params,
+            size_factors: None,
+        }
+    }
+
+    /// Divide counts by per-sample size factors before computing means and
+    /// fold changes
+    ///
+    /// Factors are given for the control samples followed by the treatment
+    /// samples, in the order passed to [`DeseqAnalyzer::analyze`], e.g. from
+    /// [`SizeFactorNormalizer::calculate_size_factors`](crate::normalization::SizeFactorNormalizer::calculate_size_factors).
+    /// Without size factors raw counts are compared.
+    pub fn with_size_factors(mut self, size_factors: Vec<f64>) -> Self {
+        self.size_factors = Some(size_factors);
+        self
     }
 
     /// Perform differential expression analysis
@@ -31,6 +47,25 @@ impl DeseqAnalyzer {
             ));
         }
 
+        let n_samples = control_samples.len() + treatment_samples.len();
+        let size_factors = match &self.size_factors {
+            Some(factors) if factors.len() != n_samples => {
+                return Err(AnalysisError::Config(format!(
+                    "expected {} size factors, one per sample, found {}",
+                    n_samples,
+                    factors.len()
+                )));
+            }
+            Some(factors) if factors.iter().any(|&f| f.is_nan() || f <= 0.0) => {
+                return Err(AnalysisError::Config(
+                    "size factors must be positive".to_string(),
+                ));
+            }
+            Some(factors) => factors.clone(),
+            None => vec![1.0; n_samples],
+        };
+        let (control_factors, treatment_factors) = size_factors.split_at(control_samples.len());
+
         // Get all genes
         let genes = self.collect_genes(&control_samples, &treatment_samples);
 
@@ -38,8 +73,12 @@ impl DeseqAnalyzer {
         let results: Vec<DifferentialExpression> = genes
             .par_iter()
             .filter_map(|gene_id| {
-                self.analyze_gene(gene_id, &control_samples, &treatment_samples)
-                    .ok()
+                self.analyze_gene(
+                    gene_id,
+                    (&control_samples, control_factors),
+                    (&treatment_samples, treatment_factors),
+                )
+                .ok()
             })
             .collect();
 
@@ -70,32 +109,39 @@ impl DeseqAnalyzer {
         genes.into_iter().collect()
     }
 
-    /// Analyze a single gene
+    /// Analyze a single gene, given each condition's samples and size factors
     fn analyze_gene(
         &self,
         gene_id: &str,
-        control_samples: &[ExpressionSample],
-        treatment_samples: &[ExpressionSample],
+        (control_samples, control_factors): (&[ExpressionSample], &[f64]),
+        (treatment_samples, treatment_factors): (&[ExpressionSample], &[f64]),
     ) -> Result<DifferentialExpression> {
-        // Get counts for this gene
-        let control_counts: Vec<u64> = control_samples
-            .iter()
-            .map(|s| *s.counts.get(gene_id).unwrap_or(&0))
-            .collect();
-
-        let treatment_counts: Vec<u64> = treatment_samples
-            .iter()
-            .map(|s| *s.counts.get(gene_id).unwrap_or(&0))
-            .collect();
+        let raw_count = |s: &ExpressionSample| *s.counts.get(gene_id).unwrap_or(&0);
 
         // Filter low-count genes
-        let total_count: u64 = control_counts.iter().chain(treatment_counts.iter()).sum();
+        let total_count: u64 = control_samples
+            .iter()
+            .chain(treatment_samples.iter())
+            .map(raw_count)
+            .sum();
         if total_count < self.params.min_counts {
             return Err(AnalysisError::InsufficientData("Low counts".to_string()));
         }
 
+        // Get size-factor-normalized counts for this gene
+        let normalized = |samples: &[ExpressionSample], factors: &[f64]| -> Vec<f64> {
+            samples
+                .iter()
+                .zip(factors)
+                .map(|(s, &factor)| raw_count(s) as f64 / factor)
+                .collect()
+        };
+        let control_counts = normalized(control_samples, control_factors);
+        let treatment_counts = normalized(treatment_samples, treatment_factors);
+
         // Calculate base mean
-        let base_mean = total_count as f64 / (control_counts.len() + treatment_counts.len()) as f64;
+        let base_mean = control_counts.iter().chain(&treatment_counts).sum::<f64>()
+            / (control_counts.len() + treatment_counts.len()) as f64;
 
         // Calculate log2 fold change
         let control_mean = Self::geometric_mean(&control_counts);
@@ -127,13 +173,13 @@ impl DeseqAnalyzer {
     }
 
     /// Calculate geometric mean
-    fn geometric_mean(values: &[u64]) -> f64 {
-        let product: f64 = values.iter().map(|&v| (v + 1) as f64).product();
+    fn geometric_mean(values: &[f64]) -> f64 {
+        let product: f64 = values.iter().map(|&v| v + 1.0).product();
         product.powf(1.0 / values.len() as f64)
     }
 
     /// Estimate standard error (simplified)
-    fn estimate_standard_error(&self, control: &[u64], treatment: &[u64]) -> f64 {
+    fn estimate_standard_error(&self, control: &[f64], treatment: &[f64]) -> f64 {
         let control_var = Self::variance(control);
         let treatment_var = Self::variance(treatment);
 
@@ -141,12 +187,12 @@ impl DeseqAnalyzer {
     }
 
     /// Calculate variance
-    fn variance(values: &[u64]) -> f64 {
-        let mean = values.iter().sum::<u64>() as f64 / values.len() as f64;
+    fn variance(values: &[f64]) -> f64 {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
         values
             .iter()
             .map(|&v| {
-                let diff = v as f64 - mean;
+                let diff = v - mean;
                 diff * diff
             })
             .sum::<f64>()
@@ -262,4 +308,52 @@ mod tests {
         let result = analyzer.analyze(control, treatment).unwrap();
         assert!(!result.results.is_empty());
     }
+
+    #[test]
+    fn test_size_factors_correct_depth_difference() {
+        let samples = |condition: &str, count: u64| {
+            vec![
+                create_test_sample("a", condition, vec![("GENE1".to_string(), count)]),
+                create_test_sample("b", condition, vec![("GENE1".to_string(), count)]),
+            ]
+        };
+        let log2_fc = |analyzer: DeseqAnalyzer| {
+            let result = analyzer
+                .analyze(samples("control", 100), samples("treatment", 200))
+                .unwrap();
+            result.results[0].log2_fold_change
+        };
+
+        // Treatment libraries were sequenced twice as deeply
+        let raw = log2_fc(DeseqAnalyzer::new(AnalysisParameters::default()));
+        let normalized = log2_fc(
+            DeseqAnalyzer::new(AnalysisParameters::default())
+                .with_size_factors(vec![1.0, 1.0, 2.0, 2.0]),
+        );
+
+        assert!(raw > 0.9);
+        assert!(normalized < raw);
+        assert!(normalized.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_size_factor_count_mismatch() {
+        let control = vec![create_test_sample(
+            "C1",
+            "control",
+            vec![("GENE1".to_string(), 10)],
+        )];
+        let treatment = vec![create_test_sample(
+            "T1",
+            "treatment",
+            vec![("GENE1".to_string(), 10)],
+        )];
+
+        let analyzer =
+            DeseqAnalyzer::new(AnalysisParameters::default()).with_size_factors(vec![1.0]);
+        assert!(matches!(
+            analyzer.analyze(control, treatment),
+            Err(AnalysisError::Config(_))
+        ));
+    }
 }
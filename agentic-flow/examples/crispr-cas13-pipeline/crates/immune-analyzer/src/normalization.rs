@@ -57,8 +57,8 @@ impl SizeFactorNormalizer {
         Self
     }
 
-    /// Calculate size factors for samples
-    fn calculate_size_factors(&self, samples: &[ExpressionSample]) -> Vec<f64> {
+    /// Calculate median-of-ratios size factors for samples, in sample order
+    pub fn calculate_size_factors(&self, samples: &[ExpressionSample]) -> Vec<f64> {
         // Get all genes
         let mut all_genes = std::collections::HashSet::new();
         for sample in samples {
@@ -1,10 +1,10 @@
 //! Feature extraction for off-target prediction
 
 use crate::error::{PredictionError, Result};
-use data_models::sequencing::AlignedRead;
+use data_models::reads::shannon_entropy;
+use data_models::sequencing::{AlignedRead, CompositionProfile};
 use data_models::targets::{CrisprTarget, OffTargetFeatures};
 use ndarray::{Array1, Array2};
-use std::collections::HashMap;
 
 /// Feature extractor for machine learning models
 pub struct FeatureExtractor {
@@ -135,11 +135,7 @@ impl FeatureExtractor {
 
     /// Calculate GC content percentage
     pub fn calculate_gc_content(&self, sequence: &str) -> f64 {
-        let gc_count = sequence
-            .chars()
-            .filter(|&c| matches!(c.to_ascii_uppercase(), 'G' | 'C'))
-            .count();
-        (gc_count as f64 / sequence.len() as f64) * 100.0
+        CompositionProfile::from_sequence(sequence).gc_fraction() * 100.0
     }
 
     /// Calculate melting temperature
@@ -185,20 +181,7 @@ impl FeatureExtractor {
 
     /// Calculate sequence complexity (Shannon entropy)
     fn calculate_complexity(&self, sequence: &str) -> f64 {
-        let mut counts: HashMap<char, usize> = HashMap::new();
-        for ch in sequence.chars() {
-            *counts.entry(ch.to_ascii_uppercase()).or_insert(0) += 1;
-        }
-
-        let len = sequence.len() as f64;
-        let mut entropy = 0.0;
-
-        for count in counts.values() {
-            let freq = *count as f64 / len;
-            entropy -= freq * freq.log2();
-        }
-
-        entropy
+        shannon_entropy(sequence)
     }
 
     /// Count dinucleotide repeats
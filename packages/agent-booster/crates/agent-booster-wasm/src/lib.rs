@@ -36,6 +36,7 @@ pub enum WasmLanguage {
     Java,
     C,
     Cpp,
+    Html,
 }
 
 impl From<WasmLanguage> for Language {
@@ -49,6 +50,7 @@ impl From<WasmLanguage> for Language {
             WasmLanguage::Java => Language::Java,
             WasmLanguage::C => Language::C,
             WasmLanguage::Cpp => Language::Cpp,
+            WasmLanguage::Html => Language::Html,
         }
     }
 }
@@ -64,6 +66,7 @@ impl From<Language> for WasmLanguage {
             Language::Java => WasmLanguage::Java,
             Language::C => WasmLanguage::C,
             Language::Cpp => WasmLanguage::Cpp,
+            Language::Html => WasmLanguage::Html,
         }
     }
 }
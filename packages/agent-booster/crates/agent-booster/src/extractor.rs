@@ -0,0 +1,222 @@
+//! Pluggable chunk extraction
+//!
+//! [`ChunkExtractor`] is the interface [`AgentBooster`](crate::AgentBooster) and
+//! [`Merger`](crate::merge::Merger) use to split code into chunks and check its
+//! syntax. Both the regex-based lite parser and the tree-sitter parser
+//! implement it, so either can be dropped in without changing call sites.
+//!
+//! [`ChunkExtractor::extract_embedded`] handles markup (HTML, Vue, Svelte) by
+//! running the JS/TS extractor over each `<script>` block.
+
+use crate::models::{line_column, CodeChunk, Language, Result, UNKNOWN_LINE};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// A `<script>` element, capturing its attributes and contents
+static SCRIPT_BLOCK: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<script\b([^>]*)>(.*?)</script\s*>").unwrap());
+
+/// A `lang="ts"` or `type="text/typescript"` style attribute
+static TYPESCRIPT_ATTR: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\b(?:lang|type)\s*=\s*["']?(?:[\w/-]*/)?(?:ts|typescript)\b"#).unwrap()
+});
+
+/// Source of code chunks and syntax checks
+pub trait ChunkExtractor {
+    /// Extract semantic code chunks, ordered by start position
+    fn extract_chunks(&self, code: &str, language: Language) -> Vec<CodeChunk>;
+
+    /// Check whether `code` is syntactically valid
+    ///
+    /// Returns an error only when the check itself could not run.
+    fn validate_syntax(&self, code: &str, language: Language) -> Result<bool>;
+
+    /// Extract the whole file as a single chunk, used when no chunks are found
+    fn extract_full_file(&self, code: &str) -> CodeChunk {
+        let (end_line, end_column) = line_column(code, code.len());
+        CodeChunk {
+            code: code.to_string(),
+            end_byte: code.len(),
+            end_line,
+            end_column,
+            node_type: "file".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Extract chunks from a file that may embed code in another language
+    ///
+    /// For [`Language::Html`] every `<script>` block is extracted as
+    /// TypeScript when its `lang` or `type` attribute says so and as
+    /// JavaScript otherwise, with positions relative to the whole file; lines
+    /// and columns equal to [`UNKNOWN_LINE`] are kept as they are. Other
+    /// languages are extracted directly.
+    fn extract_embedded(&self, code: &str, outer_language: Language) -> Vec<CodeChunk> {
+        if outer_language != Language::Html {
+            return self.extract_chunks(code, outer_language);
+        }
+
+        let mut chunks = Vec::new();
+        for cap in SCRIPT_BLOCK.captures_iter(code) {
+            let (Some(attributes), Some(body)) = (cap.get(1), cap.get(2)) else {
+                continue;
+            };
+            let language = if TYPESCRIPT_ATTR.is_match(attributes.as_str()) {
+                Language::TypeScript
+            } else {
+                Language::JavaScript
+            };
+
+            let (line, column) = line_column(code, body.start());
+            for mut chunk in self.extract_chunks(body.as_str(), language) {
+                if chunk.start_line == 0 {
+                    chunk.start_column += column;
+                }
+                if chunk.end_line == 0 {
+                    chunk.end_column += column;
+                }
+                chunk.start_byte += body.start();
+                chunk.end_byte += body.start();
+                if chunk.start_line != UNKNOWN_LINE {
+                    chunk.start_line += line;
+                }
+                if chunk.end_line != UNKNOWN_LINE {
+                    chunk.end_line += line;
+                }
+                chunk.language = language;
+                chunks.push(chunk);
+            }
+        }
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Config, EditRequest};
+    use crate::parser::Parser;
+    use crate::AgentBooster;
+
+    /// Extractor treating every non-empty line as a chunk
+    struct LineExtractor;
+
+    impl ChunkExtractor for LineExtractor {
+        fn extract_chunks(&self, code: &str, _language: Language) -> Vec<CodeChunk> {
+            let mut offset = 0;
+            let mut chunks = Vec::new();
+            for (line, text) in code.split_inclusive('\n').enumerate() {
+                let trimmed = text.trim_end();
+                if !trimmed.is_empty() {
+                    chunks.push(CodeChunk {
+                        code: trimmed.to_string(),
+                        start_byte: offset,
+                        end_byte: offset + trimmed.len(),
+                        start_line: line,
+                        end_line: line,
+                        end_column: trimmed.chars().count(),
+                        node_type: "line".to_string(),
+                        ..Default::default()
+                    });
+                }
+                offset += text.len();
+            }
+            chunks
+        }
+
+        fn validate_syntax(&self, _code: &str, _language: Language) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    /// Extractor returning the whole code as one chunk located by offset only
+    struct OffsetExtractor;
+
+    impl ChunkExtractor for OffsetExtractor {
+        fn extract_chunks(&self, code: &str, _language: Language) -> Vec<CodeChunk> {
+            vec![CodeChunk {
+                code: code.to_string(),
+                end_byte: code.len(),
+                start_line: UNKNOWN_LINE,
+                end_line: UNKNOWN_LINE,
+                start_column: UNKNOWN_LINE,
+                end_column: UNKNOWN_LINE,
+                node_type: "byte_window".to_string(),
+                ..Default::default()
+            }]
+        }
+
+        fn validate_syntax(&self, _code: &str, _language: Language) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn test_mock_extractor_drives_edits() {
+        let extractor = LineExtractor;
+        let chunks = extractor.extract_chunks("let a = 1;\n\nlet b = 2;\n", Language::JavaScript);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].start_line, 2);
+
+        let mut booster =
+            AgentBooster::with_extractor(Config::default(), Box::new(LineExtractor)).unwrap();
+        let result = booster
+            .apply_edit(EditRequest {
+                original_code: "let a = 1;\nlet b = 2;\n".to_string(),
+                edit_snippet: "let b = 3;".to_string(),
+                language: Language::JavaScript,
+                confidence_threshold: 0.5,
+            })
+            .unwrap();
+        assert_eq!(result.metadata.chunks_found, 2);
+        assert!(result.merged_code.contains("let b = 3;"));
+        assert!(result.merged_code.contains("let a = 1;"));
+    }
+
+    #[test]
+    fn test_default_full_file_chunk() {
+        let chunk = LineExtractor.extract_full_file("a\nbc");
+        assert_eq!(chunk.node_type, "file");
+        assert_eq!((chunk.end_byte, chunk.end_line, chunk.end_column), (4, 1, 2));
+    }
+
+    #[test]
+    fn test_extract_embedded_script() {
+        let code = r#"<html>
+<body>
+  <p>{ not code }</p>
+  <script lang="ts">
+function greet(name: string) {
+  return "hi " + name;
+}
+  </script>
+</body>
+</html>
+"#;
+        let parser = Parser::new().unwrap();
+
+        let chunks = parser.extract_embedded(code, Language::Html);
+        let greet = chunks
+            .iter()
+            .find(|c| c.node_type == "function_declaration")
+            .unwrap();
+
+        assert_eq!(greet.language, Language::TypeScript);
+        assert_eq!(greet.start_byte, code.find("function greet").unwrap());
+        assert_eq!(&code[greet.start_byte..greet.end_byte], greet.code);
+        assert_eq!((greet.start_line, greet.start_column), (4, 0));
+        assert_eq!((greet.end_line, greet.end_column), (6, 1));
+    }
+
+    #[test]
+    fn test_extract_embedded_keeps_unknown_lines() {
+        let code = "<p>hi</p>\n<script>var a=1;var b=2;</script>\n";
+        let chunks = OffsetExtractor.extract_embedded(code, Language::Html);
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].start_byte, code.find("var a").unwrap());
+        let chunk = &chunks[0];
+        assert_eq!((chunk.start_line, chunk.end_line), (UNKNOWN_LINE, UNKNOWN_LINE));
+        assert_eq!((chunk.start_column, chunk.end_column), (UNKNOWN_LINE, UNKNOWN_LINE));
+    }
+}
@@ -0,0 +1,75 @@
+//! JSON Lines import/export for code chunks
+//!
+//! Writing chunks one JSON object per line lets large repositories be indexed
+//! as a stream instead of holding every chunk in memory for a single array.
+
+use crate::models::{CodeChunk, Result};
+use std::io::{BufRead, Write};
+
+/// Write chunks as JSON Lines, one serialized chunk per line
+pub fn write_chunks_jsonl<W: Write>(
+    chunks: impl Iterator<Item = CodeChunk>,
+    writer: &mut W,
+) -> Result<()> {
+    for chunk in chunks {
+        serde_json::to_writer(&mut *writer, &chunk)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read chunks from JSON Lines, skipping blank lines
+pub fn read_chunks_jsonl<R: BufRead>(reader: R) -> Result<Vec<CodeChunk>> {
+    let mut chunks = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        chunks.push(serde_json::from_str(&line)?);
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(code: &str, start_byte: usize, node_type: &str) -> CodeChunk {
+        CodeChunk {
+            code: code.to_string(),
+            start_byte,
+            end_byte: start_byte + code.len(),
+            start_line: 0,
+            end_line: 0,
+            node_type: node_type.to_string(),
+            parent_type: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let chunks = vec![
+            chunk("function a() {}", 0, "function_declaration"),
+            chunk("class B {}", 16, "class_declaration"),
+            chunk("const c = \"line\\nbreak\";", 27, "variable_declaration"),
+        ];
+
+        let mut buffer = Vec::new();
+        write_chunks_jsonl(chunks.clone().into_iter(), &mut buffer).unwrap();
+
+        let text = String::from_utf8(buffer.clone()).unwrap();
+        assert_eq!(text.lines().count(), 3);
+
+        let read_back = read_chunks_jsonl(buffer.as_slice()).unwrap();
+        assert_eq!(read_back, chunks);
+    }
+
+    #[test]
+    fn test_read_invalid_line() {
+        let input = b"{\"code\": 1}\n";
+        assert!(read_chunks_jsonl(&input[..]).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+//! Lexical scanning shared by the parsers and chunk helpers
+//!
+//! [`CodeBytes`] walks source code while skipping string literals and
+//! comments, so bracket matching isn't thrown off by braces inside them.
+
+use crate::models::Language;
+use std::ops::Range;
+
+/// A string literal or comment skipped by [`CodeBytes`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Literal {
+    /// Offset just past the literal
+    end: usize,
+    /// Opening delimiter, e.g. `"` or `/*`
+    delimiter: &'static str,
+    /// Whether the closing delimiter was found
+    terminated: bool,
+}
+
+/// Iterator over the bytes of source code that lie outside string literals and
+/// comments, yielding `(offset, byte)` pairs
+///
+/// Scanning is lexical only: it knows each language's comment and string
+/// syntax (including Rust raw strings and lifetimes, Python triple-quoted
+/// strings, and JS template literals) but nothing else. Quoted strings end at
+/// a newline except in Rust and in multi-line forms (templates, triple quotes,
+/// raw strings). The first literal left open is available from
+/// [`CodeBytes::unterminated`].
+pub(crate) struct CodeBytes<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    language: Language,
+    unterminated: Option<(usize, &'static str)>,
+}
+
+impl Literal {
+    /// Check whether the literal is a comment rather than a string
+    fn is_comment(&self) -> bool {
+        matches!(self.delimiter, "//" | "/*" | "#")
+    }
+}
+
+impl<'a> CodeBytes<'a> {
+    pub(crate) fn new(code: &'a str, language: Language) -> Self {
+        Self {
+            bytes: code.as_bytes(),
+            pos: 0,
+            language,
+            unterminated: None,
+        }
+    }
+
+    /// Get the start and opening delimiter of the first unterminated literal
+    /// scanned so far
+    pub(crate) fn unterminated(&self) -> Option<(usize, &'static str)> {
+        self.unterminated
+    }
+
+    fn at(&self, pos: usize) -> Option<u8> {
+        self.bytes.get(pos).copied()
+    }
+
+    fn starts_with(&self, pos: usize, prefix: &[u8]) -> bool {
+        self.bytes[pos..].starts_with(prefix)
+    }
+
+    /// Get the literal or comment starting at `pos`, if one starts there
+    fn skip_literal(&self, pos: usize) -> Option<Literal> {
+        if self.language == Language::Html {
+            return None;
+        }
+        let byte = self.bytes[pos];
+        let c_like = self.language != Language::Python;
+
+        if c_like && self.starts_with(pos, b"//") || !c_like && byte == b'#' {
+            let end = self.find(pos, b"\n").unwrap_or(self.bytes.len());
+            let delimiter = if c_like { "//" } else { "#" };
+            return Some(Literal {
+                end,
+                delimiter,
+                terminated: true,
+            });
+        }
+        if c_like && self.starts_with(pos, b"/*") {
+            return Some(self.skip_block_comment(pos));
+        }
+
+        match (self.language, byte) {
+            (Language::Rust, b'r') | (Language::Rust, b'b') => self.skip_rust_raw_string(pos),
+            (Language::Rust, b'\'') => self.skip_rust_char(pos),
+            (Language::Python, b'"') if self.starts_with(pos, b"\"\"\"") => {
+                Some(self.skip_to(pos + 3, "\"\"\""))
+            }
+            (Language::Python, b'\'') if self.starts_with(pos, b"'''") => {
+                Some(self.skip_to(pos + 3, "'''"))
+            }
+            (Language::Java, b'"') if self.starts_with(pos, b"\"\"\"") => {
+                Some(self.skip_to(pos + 3, "\"\"\""))
+            }
+            (Language::JavaScript | Language::TypeScript, b'`') => {
+                Some(self.skip_quoted(pos, "`", true))
+            }
+            (Language::Go, b'`') => Some(self.skip_to(pos + 1, "`")),
+            (language, b'"') => Some(self.skip_quoted(pos, "\"", language == Language::Rust)),
+            (_, b'\'') => Some(self.skip_quoted(pos, "'", false)),
+            _ => None,
+        }
+    }
+
+    /// Find the next occurrence of `needle` at or after `from`
+    fn find(&self, from: usize, needle: &[u8]) -> Option<usize> {
+        (from..self.bytes.len()).find(|&i| self.starts_with(i, needle))
+    }
+
+    /// Skip to just past the next `closing` delimiter, which is also the
+    /// opening one
+    fn skip_to(&self, from: usize, closing: &'static str) -> Literal {
+        let end = self.find(from, closing.as_bytes());
+        Literal {
+            end: end.map_or(self.bytes.len(), |end| end + closing.len()),
+            delimiter: closing,
+            terminated: end.is_some(),
+        }
+    }
+
+    /// Skip a quoted literal with backslash escapes
+    fn skip_quoted(&self, pos: usize, delimiter: &'static str, multiline: bool) -> Literal {
+        let quote = self.bytes[pos];
+        let mut i = pos + 1;
+        while let Some(byte) = self.at(i) {
+            match byte {
+                b'\\' => i += 2,
+                b'\n' if !multiline => break,
+                _ if byte == quote => {
+                    return Literal {
+                        end: i + 1,
+                        delimiter,
+                        terminated: true,
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+        Literal {
+            end: i.min(self.bytes.len()),
+            delimiter,
+            terminated: false,
+        }
+    }
+
+    /// Skip a block comment; Rust block comments nest
+    fn skip_block_comment(&self, pos: usize) -> Literal {
+        let nests = self.language == Language::Rust;
+        let mut depth = 0;
+        let mut i = pos;
+        while i < self.bytes.len() {
+            if self.starts_with(i, b"/*") && (nests || depth == 0) {
+                depth += 1;
+                i += 2;
+            } else if self.starts_with(i, b"*/") {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return Literal {
+                        end: i,
+                        delimiter: "/*",
+                        terminated: true,
+                    };
+                }
+            } else {
+                i += 1;
+            }
+        }
+        Literal {
+            end: self.bytes.len(),
+            delimiter: "/*",
+            terminated: false,
+        }
+    }
+
+    /// Skip a Rust raw string (`r"..."`, `r#"..."#`, `br#"..."#`)
+    fn skip_rust_raw_string(&self, pos: usize) -> Option<Literal> {
+        let preceded_by_ident = pos > 0
+            && (self.bytes[pos - 1].is_ascii_alphanumeric() || self.bytes[pos - 1] == b'_');
+        if preceded_by_ident {
+            return None;
+        }
+
+        let mut i = pos;
+        if self.at(i) == Some(b'b') {
+            i += 1;
+        }
+        if self.at(i) != Some(b'r') {
+            return None;
+        }
+        i += 1;
+        let hashes = self.bytes[i..].iter().take_while(|&&b| b == b'#').count();
+        i += hashes;
+        if self.at(i) != Some(b'"') {
+            return None;
+        }
+
+        let mut closing = vec![b'"'];
+        closing.extend(std::iter::repeat(b'#').take(hashes));
+        let end = self.find(i + 1, &closing);
+        Some(Literal {
+            end: end.map_or(self.bytes.len(), |end| end + closing.len()),
+            delimiter: "r\"",
+            terminated: end.is_some(),
+        })
+    }
+
+    /// Skip a Rust char literal; lifetimes such as `'a` are left as code
+    fn skip_rust_char(&self, pos: usize) -> Option<Literal> {
+        if self.at(pos + 1) == Some(b'\\') {
+            return Some(self.skip_quoted(pos, "'", false));
+        }
+        let rest = std::str::from_utf8(&self.bytes[pos + 1..]).ok()?;
+        let ch = rest.chars().next()?;
+        let close = pos + 1 + ch.len_utf8();
+        (self.at(close) == Some(b'\'')).then_some(Literal {
+            end: close + 1,
+            delimiter: "'",
+            terminated: true,
+        })
+    }
+}
+
+impl Iterator for CodeBytes<'_> {
+    type Item = (usize, u8);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.bytes.len() {
+            match self.skip_literal(self.pos) {
+                Some(literal) => {
+                    if !literal.terminated && self.unterminated.is_none() {
+                        self.unterminated = Some((self.pos, literal.delimiter));
+                    }
+                    self.pos = literal.end;
+                }
+                None => {
+                    let pos = self.pos;
+                    self.pos += 1;
+                    return Some((pos, self.bytes[pos]));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Get the byte ranges of the comments in `code`, skipping comment markers
+/// inside string literals
+pub(crate) fn comment_ranges(code: &str, language: Language) -> Vec<Range<usize>> {
+    let scanner = CodeBytes::new(code, language);
+    let mut ranges = Vec::new();
+    let mut pos = 0;
+    while pos < code.len() {
+        match scanner.skip_literal(pos) {
+            Some(literal) => {
+                if literal.is_comment() {
+                    ranges.push(pos..literal.end);
+                }
+                pos = literal.end;
+            }
+            None => pos += 1,
+        }
+    }
+    ranges
+}
+
+/// Find the first `{` in `code` and its matching `}`, ignoring braces inside
+/// string literals and comments
+///
+/// Returns the byte offsets of both braces.
+pub(crate) fn block_end(code: &str, language: Language) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    let mut open = None;
+
+    for (pos, byte) in CodeBytes::new(code, language) {
+        match byte {
+            b'{' => {
+                open.get_or_insert(pos);
+                depth += 1;
+            }
+            b'}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    return open.map(|open| (open, pos));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Bytes after which a `{` opens an expression (object literal, struct
+/// literal argument) rather than a block of statements
+const EXPRESSION_PRECEDERS: &[u8] = b"=(,:[?!&|+-*/%<";
+
+/// Find the smallest range of whole statements covering `from..to`
+///
+/// Statements end at a `;` or at the `}` closing a block, and blocks contain
+/// statements; brackets, parentheses and braces opened after an operator or
+/// delimiter (see [`EXPRESSION_PRECEDERS`]) are part of the statement around
+/// them. The start moves back to the first non-blank byte after the last
+/// statement boundary before `from`. The end moves forward to the first
+/// boundary at or after `to` at the start's nesting level, or to just before
+/// the `}` closing the enclosing block, or to the end of `code`.
+#[cfg_attr(feature = "tree-sitter-parser", allow(dead_code))]
+pub(crate) fn statement_bounds(
+    code: &str,
+    from: usize,
+    to: usize,
+    language: Language,
+) -> (usize, usize) {
+    // `true` for a block of statements, `false` for a nested expression
+    let mut open: Vec<bool> = Vec::new();
+    let mut prev = b';';
+    let mut boundary = (0, 0);
+    let mut start = None;
+    let mut end = code.len();
+
+    for (pos, byte) in CodeBytes::new(code, language) {
+        if start.is_none() && pos >= from {
+            start = Some(boundary);
+        }
+        let statement_level = open.last().copied().unwrap_or(true);
+        let mut closed = None;
+        match byte {
+            b'(' | b'[' => open.push(false),
+            b'{' => {
+                let block = statement_level && !EXPRESSION_PRECEDERS.contains(&prev);
+                open.push(block);
+                if block && start.is_none() {
+                    boundary = (pos + 1, open.len());
+                }
+            }
+            b')' | b']' => {
+                open.pop();
+            }
+            b'}' => closed = (open.pop() == Some(true)).then_some(pos + 1),
+            b';' if statement_level => closed = Some(pos + 1),
+            _ => {}
+        }
+        if !byte.is_ascii_whitespace() {
+            prev = byte;
+        }
+
+        match (start, closed) {
+            (None, Some(after)) => boundary = (after, open.len()),
+            (Some((_, depth)), _) if open.len() < depth => {
+                end = pos;
+                break;
+            }
+            (Some((_, depth)), Some(after)) if after >= to && open.len() == depth => {
+                end = after;
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    let (after, _) = start.unwrap_or(boundary);
+    let first = code[after..]
+        .find(|c: char| !c.is_whitespace())
+        .map_or(code.len(), |i| after + i);
+    let end = end.max(to);
+    let end = code[..end].trim_end().len().max(to);
+    (first.min(from), end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_bytes_skips_literals() {
+        let code = r##"a"{"/*{*/b r#"}"#c'}'d<'e>//{"##;
+        let rust: String = CodeBytes::new(code, Language::Rust)
+            .map(|(_, b)| b as char)
+            .collect();
+        assert_eq!(rust, "ab cd<'e>");
+
+        let python: String = CodeBytes::new("x = '{' # }\ny = \"\"\"}\"\"\"", Language::Python)
+            .map(|(_, b)| b as char)
+            .collect();
+        assert_eq!(python, "x =  \ny = ");
+    }
+
+    #[test]
+    fn test_comment_ranges() {
+        let code = "a // x\ns = \"/* no */\" /* y */";
+        let comments: Vec<&str> = comment_ranges(code, Language::JavaScript)
+            .into_iter()
+            .map(|range| &code[range])
+            .collect();
+        assert_eq!(comments, ["// x", "/* y */"]);
+    }
+
+    #[test]
+    fn test_block_end() {
+        let code = "fn f() { let s = \"}\"; { 1 } }";
+        assert_eq!(block_end(code, Language::Rust), Some((7, code.len() - 1)));
+        assert_eq!(block_end("fn f() { ", Language::Rust), None);
+    }
+
+    #[test]
+    fn test_statement_bounds() {
+        let code = "let a = f(1,\n  2);\nif (a) {\n  b = { c: 1 };\n}\n";
+        // Snap the span of the first occurrence of `needle`
+        let snap = |needle: &str| {
+            let from = code.find(needle).unwrap();
+            let (start, end) =
+                statement_bounds(code, from, from + needle.len(), Language::JavaScript);
+            &code[start..end]
+        };
+
+        assert_eq!(snap("2)"), "let a = f(1,\n  2);");
+        assert_eq!(snap("c:"), "b = { c: 1 };");
+        assert_eq!(snap("(a)"), "if (a) {\n  b = { c: 1 };\n}");
+    }
+}
@@ -4,8 +4,12 @@
 //! the best matching location in existing code using tree-sitter parsing and text
 //! similarity algorithms.
 
+pub mod extractor;
+pub mod jsonl;
+mod lexer;
 pub mod merge;
 pub mod models;
+pub mod resolver;
 pub mod templates;
 
 #[cfg(feature = "tree-sitter-parser")]
@@ -20,9 +24,10 @@ pub use parser_lite as parser;
 pub mod similarity;
 
 pub use models::{
-    AgentBoosterError, CodeChunk, Config, EditMetadata, EditRequest, EditResult, Language,
-    MergeStrategy, Result,
+    byte_range_to_lines, AgentBoosterError, CodeChunk, Config, EditMetadata, EditRequest,
+    EditResult, HashOptions, Language, MergeStrategy, Result,
 };
+pub use extractor::ChunkExtractor;
 pub use templates::TemplateEngine;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -30,16 +35,32 @@ use std::time::Instant;
 
 /// Main AgentBooster API
 pub struct AgentBooster {
-    parser: parser::Parser,
+    extractor: Box<dyn ChunkExtractor + Send>,
     merger: merge::Merger,
     config: Config,
 }
 
+// Keep `AgentBooster` movable across threads, as it was before extractors
+// became pluggable
+const _: () = {
+    fn assert_send<T: Send>() {}
+    let _ = assert_send::<AgentBooster>;
+};
+
 impl AgentBooster {
     /// Create a new AgentBooster instance with the given configuration
     pub fn new(config: Config) -> Result<Self> {
+        Self::with_extractor(config, Box::new(parser::Parser::new()?))
+    }
+
+    /// Create a new AgentBooster instance that extracts chunks and checks
+    /// syntax with the given extractor instead of the built-in parser
+    pub fn with_extractor(
+        config: Config,
+        extractor: Box<dyn ChunkExtractor + Send>,
+    ) -> Result<Self> {
         Ok(Self {
-            parser: parser::Parser::new()?,
+            extractor,
             merger: merge::Merger::new()?,
             config,
         })
@@ -52,7 +73,10 @@ impl AgentBooster {
 
         // PHASE 1: Try template-based transformation first (bypasses similarity matching)
         if let Some(template_match) = TemplateEngine::try_template_transform(&request.original_code, &request.edit_snippet) {
-            let syntax_valid = self.parser.validate_syntax(&template_match.transformed_code, request.language);
+            let syntax_valid = self
+                .extractor
+                .validate_syntax(&template_match.transformed_code, request.language)
+                .unwrap_or(false);
             let confidence_f32 = template_match.confidence as f32;
 
             if confidence_f32 >= request.confidence_threshold && syntax_valid {
@@ -77,15 +101,15 @@ impl AgentBooster {
         }
 
         // PHASE 2: Fall back to similarity-based matching
-        // Parse original code
-        let tree = self
-            .parser
-            .parse(&request.original_code, request.language)?;
-        let mut chunks = self.parser.extract_chunks(&tree, &request.original_code);
+        let mut chunks = self
+            .extractor
+            .extract_chunks(&request.original_code, request.language);
 
         // If no chunks found, treat entire file as one chunk
         if chunks.is_empty() {
-            chunks.push(self.parser.extract_full_file(&request.original_code));
+            let mut file = self.extractor.extract_full_file(&request.original_code);
+            file.language = request.language;
+            chunks.push(file);
         }
 
         // Limit chunks if needed
@@ -102,7 +126,8 @@ impl AgentBooster {
         let best_similarity = best_match.similarity;
 
         // Apply merge
-        let merge_result = self.merger.merge(
+        let merge_result = self.merger.merge_with(
+            self.extractor.as_ref(),
             &request.original_code,
             &request.edit_snippet,
             &best_match,
@@ -1,3 +1,4 @@
+use crate::extractor::ChunkExtractor;
 use crate::models::{AgentBoosterError, Language, MergeStrategy, Result};
 use crate::parser::Parser;
 use crate::similarity::SearchResult;
@@ -23,11 +24,33 @@ impl Merger {
         best_match: &SearchResult,
         language: Language,
         confidence_threshold: f32,
+    ) -> Result<MergeResult> {
+        self.merge_with(
+            &self.parser,
+            original_code,
+            edit_snippet,
+            best_match,
+            language,
+            confidence_threshold,
+        )
+    }
+
+    /// Apply merge, checking syntax with the given extractor
+    pub fn merge_with(
+        &self,
+        extractor: &dyn ChunkExtractor,
+        original_code: &str,
+        edit_snippet: &str,
+        best_match: &SearchResult,
+        language: Language,
+        confidence_threshold: f32,
     ) -> Result<MergeResult> {
         // PHASE 1: Try template-based transformation first
         if let Some(template_match) = TemplateEngine::try_template_transform(original_code, edit_snippet) {
             // Template matched! Use template transformation
-            let syntax_valid = self.parser.validate_syntax(&template_match.transformed_code, language);
+            let syntax_valid = extractor
+                .validate_syntax(&template_match.transformed_code, language)
+                .unwrap_or(false);
             let confidence_f32 = template_match.confidence as f32;
 
             if confidence_f32 >= confidence_threshold && syntax_valid {
@@ -48,7 +71,7 @@ impl Merger {
         let merged_code = self.apply_merge(original_code, edit_snippet, best_match, strategy)?;
 
         // Validate syntax
-        let syntax_valid = self.parser.validate_syntax(&merged_code, language);
+        let syntax_valid = extractor.validate_syntax(&merged_code, language).unwrap_or(false);
 
         // Calculate final confidence
         let confidence = Self::calculate_confidence(best_match.similarity, strategy, syntax_valid);
@@ -224,6 +247,7 @@ mod tests {
                 end_line: 0,
                 node_type: "function_declaration".to_string(),
                 parent_type: None,
+                ..Default::default()
             },
             similarity,
             chunk_index: 0,
@@ -1,8 +1,32 @@
+use crate::lexer::{block_end, comment_ranges, CodeBytes};
+use crate::similarity::SimilarityMatcher;
+use fnv::FnvHasher;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "strong-hash")]
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::hash::Hasher;
+use std::path::Path;
+
+/// Text substituted for each match removed by [`CodeChunk::redact`]
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Line and column of chunks located by byte offset only, such as those the
+/// lite parser splits minified files into
+pub const UNKNOWN_LINE: usize = usize::MAX;
+
+/// Keywords opening a branch, counted by [`CodeChunk::cyclomatic_complexity`]
+const DECISION_KEYWORDS: &[&str] = &["if", "for", "while", "case", "catch"];
+
+/// Python's branch keywords, including its boolean operators
+const PYTHON_DECISION_KEYWORDS: &[&str] =
+    &["if", "elif", "for", "while", "case", "except", "and", "or"];
 
 /// Supported programming languages
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Language {
+    #[default]
     JavaScript,
     TypeScript,
     Python,
@@ -11,6 +35,8 @@ pub enum Language {
     Java,
     C,
     Cpp,
+    /// Markup embedding `<script>` blocks (HTML, Vue, Svelte)
+    Html,
 }
 
 impl Language {
@@ -24,10 +50,32 @@ impl Language {
             "java" => Ok(Language::Java),
             "c" => Ok(Language::C),
             "cpp" | "c++" | "cxx" => Ok(Language::Cpp),
+            "html" | "htm" | "vue" | "svelte" => Ok(Language::Html),
             _ => Err(AgentBoosterError::UnsupportedLanguage(s.to_string())),
         }
     }
 
+    /// Detect the language of a file from its extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase)
+            .unwrap_or_default();
+        match extension.as_str() {
+            "js" | "jsx" | "mjs" | "cjs" => Ok(Language::JavaScript),
+            "ts" | "tsx" | "mts" | "cts" => Ok(Language::TypeScript),
+            "py" | "pyi" => Ok(Language::Python),
+            "rs" => Ok(Language::Rust),
+            "go" => Ok(Language::Go),
+            "java" => Ok(Language::Java),
+            "c" | "h" => Ok(Language::C),
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Ok(Language::Cpp),
+            "html" | "htm" | "vue" | "svelte" => Ok(Language::Html),
+            _ => Err(AgentBoosterError::UnsupportedLanguage(path.display().to_string())),
+        }
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Language::JavaScript => "javascript",
@@ -38,6 +86,42 @@ impl Language {
             Language::Java => "java",
             Language::C => "c",
             Language::Cpp => "cpp",
+            Language::Html => "html",
+        }
+    }
+
+    /// Get the declaration node types chunks of this language can carry
+    ///
+    /// Structural chunks such as `"file"` or `"region"` may appear for any
+    /// language and are not listed. Markup reports no types of its own; its
+    /// embedded scripts use those of their language.
+    pub fn supported_node_types(&self) -> &'static [&'static str] {
+        match self {
+            Language::JavaScript | Language::TypeScript => &[
+                "function_declaration",
+                "function_expression",
+                "arrow_function",
+                "method_definition",
+                "class_declaration",
+                "class",
+                "interface_declaration",
+                "property_signature",
+                "method_signature",
+                "type_alias_declaration",
+                "enum_declaration",
+                "namespace",
+                "module",
+                "variable_declaration",
+                "export_statement",
+                "import_statement",
+            ],
+            Language::Python => &["function_definition", "class_definition"],
+            Language::Rust => &["fn", "struct", "enum", "impl"],
+            Language::Go => &["function_declaration", "type_declaration"],
+            Language::Java => &["class_declaration", "method_declaration"],
+            Language::C => &["function_definition"],
+            Language::Cpp => &["class_specifier", "function_definition"],
+            Language::Html => &[],
         }
     }
 }
@@ -70,7 +154,7 @@ impl MergeStrategy {
 }
 
 /// A code chunk extracted from parsing
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct CodeChunk {
     /// The code text
     pub code: String,
@@ -82,16 +166,373 @@ pub struct CodeChunk {
     pub start_line: usize,
     /// End line number (0-indexed)
     pub end_line: usize,
+    /// Start column (0-indexed, in chars; a tab counts as one column)
+    #[serde(default)]
+    pub start_column: usize,
+    /// End column (0-indexed, in chars, exclusive)
+    #[serde(default)]
+    pub end_column: usize,
     /// Node type from tree-sitter (e.g., "function_declaration")
     pub node_type: String,
+    /// Language the chunk is written in, which for code embedded in markup
+    /// differs from the file's language
+    #[serde(default)]
+    pub language: Language,
     /// Parent node type if available
     pub parent_type: Option<String>,
+    /// Declared name (function, class, method) if one was captured
+    pub name: Option<String>,
+    /// Decorators or annotations (`@Name(...)`) preceding the declaration,
+    /// in source order
+    #[serde(default)]
+    pub decorators: Vec<String>,
+    /// Set when an edit overlapped the chunk boundary, so its range and code
+    /// are unreliable and it should be reparsed
+    #[serde(default)]
+    pub dirty: bool,
+    /// Byte range of the pattern match that produced the chunk, which covers
+    /// just the declaration header rather than the whole block; only set by
+    /// the lite parser when its `ParserConfig::debug_spans` is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_span: Option<(usize, usize)>,
+}
+
+/// Normalization applied before [`CodeChunk::content_hash_with_options`]
+/// hashes a chunk's code
+///
+/// String literals are always hashed verbatim, whitespace inside them
+/// included. With both options off the code is hashed exactly as written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashOptions {
+    /// Collapse every run of whitespace outside string literals to a single
+    /// space and trim the ends, so reindenting or rewrapping code keeps its
+    /// hash; whitespace is still significant between tokens
+    pub ignore_whitespace: bool,
+    /// Drop comments, including trailing and block comments
+    pub ignore_comments: bool,
 }
 
 impl CodeChunk {
     pub fn line_count(&self) -> usize {
         self.end_line.saturating_sub(self.start_line) + 1
     }
+
+    /// Get the text strictly between the chunk's first `{` and its matching
+    /// `}`, e.g. a function's body without its signature
+    ///
+    /// Braces inside string literals and comments are ignored. Returns `None`
+    /// for chunks without a brace-delimited body, including all Python chunks.
+    pub fn body(&self) -> Option<&str> {
+        if matches!(self.language, Language::Python | Language::Html) {
+            return None;
+        }
+        let (open, close) = block_end(&self.code, self.language)?;
+        Some(&self.code[open + 1..close])
+    }
+
+    /// Check whether two chunks hold the same declaration, wherever it sits
+    ///
+    /// Compares `node_type`, `name`, `parent_type` and the code with
+    /// indentation, blank lines and comment lines removed. Byte, line and
+    /// column offsets are ignored, so a declaration moved within a file or
+    /// across file versions still matches.
+    pub fn semantically_equal(&self, other: &CodeChunk) -> bool {
+        self.node_type == other.node_type
+            && self.name == other.name
+            && self.parent_type == other.parent_type
+            && SimilarityMatcher::normalize_code(&self.code)
+                == SimilarityMatcher::normalize_code(&other.code)
+    }
+
+    /// Hash the code with indentation, blank lines and comment lines removed
+    ///
+    /// Chunks that are [`semantically_equal`](Self::semantically_equal) have
+    /// the same hash. Uses 64-bit FNV-1a, which is fast and depends only on
+    /// the code bytes, so values are identical across runs, platforms and
+    /// Rust releases and may be persisted. It is not collision resistant.
+    pub fn content_hash(&self) -> u64 {
+        self.content_hash_with(FnvHasher::default())
+    }
+
+    /// Hash the normalized code, as [`content_hash`](Self::content_hash)
+    /// does, with a caller-chosen hasher
+    ///
+    /// The normalized code is fed to `hasher` as one [`Hasher::write`] call.
+    /// Values are only comparable between hashers of the same type and
+    /// state; to persist them, use a hasher that is deterministic across
+    /// processes, which excludes a randomly seeded
+    /// [`RandomState`](std::collections::hash_map::RandomState) hasher.
+    pub fn content_hash_with<H: Hasher>(&self, mut hasher: H) -> u64 {
+        hasher.write(SimilarityMatcher::normalize_code(&self.code).as_bytes());
+        hasher.finish()
+    }
+
+    /// Get the SHA-256 digest of the normalized code hashed by
+    /// [`content_hash`](Self::content_hash)
+    ///
+    /// Use this instead of the 64-bit hash where a collision would be a
+    /// problem, such as keying a large persistent cache. The digest is
+    /// deterministic and may be persisted.
+    #[cfg(feature = "strong-hash")]
+    pub fn content_digest(&self) -> [u8; 32] {
+        Sha256::digest(SimilarityMatcher::normalize_code(&self.code).as_bytes()).into()
+    }
+
+    /// Hash the code with 64-bit FNV-1a after the normalization chosen in
+    /// `options`
+    ///
+    /// Like [`content_hash`](Self::content_hash) the value is deterministic
+    /// and may be persisted, but the two are computed differently and must
+    /// not be compared with each other, nor across different `options`.
+    pub fn content_hash_with_options(&self, options: HashOptions) -> u64 {
+        let code = self.code.as_bytes();
+        let mut is_code = vec![false; code.len()];
+        for (pos, _) in CodeBytes::new(&self.code, self.language) {
+            is_code[pos] = true;
+        }
+        let mut is_comment = vec![false; code.len()];
+        for range in comment_ranges(&self.code, self.language) {
+            is_comment[range].fill(true);
+        }
+
+        let mut normalized = Vec::with_capacity(code.len());
+        let mut pending_space = false;
+        for (i, &byte) in code.iter().enumerate() {
+            if options.ignore_comments && is_comment[i] {
+                pending_space |= options.ignore_whitespace;
+                continue;
+            }
+            if options.ignore_whitespace && is_code[i] && byte.is_ascii_whitespace() {
+                pending_space = true;
+                continue;
+            }
+            if pending_space && !normalized.is_empty() {
+                normalized.push(b' ');
+            }
+            pending_space = false;
+            normalized.push(byte);
+        }
+
+        let mut hasher = FnvHasher::default();
+        hasher.write(&normalized);
+        hasher.finish()
+    }
+
+    /// Estimate the cyclomatic complexity of the chunk's code
+    ///
+    /// This is an approximation from token scanning, not control-flow
+    /// analysis: it starts at 1 and adds one per `if`, `for`, `while`, `case`,
+    /// `catch`, `&&`, `||` and `?` outside string literals and comments
+    /// (Python uses `elif`, `except`, `and` and `or` instead of `catch`, `&&`
+    /// and `||`). `?.` and `??` are not counted, while a TypeScript optional
+    /// parameter such as `x?: T` is not told apart from a conditional.
+    pub fn cyclomatic_complexity(&self) -> u32 {
+        // Blank out literals and comments so keywords inside them don't count
+        let mut code = vec![b' '; self.code.len()];
+        for (pos, byte) in CodeBytes::new(&self.code, self.language) {
+            code[pos] = byte;
+        }
+        let keywords = match self.language {
+            Language::Python => PYTHON_DECISION_KEYWORDS,
+            _ => DECISION_KEYWORDS,
+        };
+
+        let mut complexity = 1;
+        let mut i = 0;
+        while i < code.len() {
+            if code[i].is_ascii_alphanumeric() || code[i] == b'_' {
+                let start = i;
+                while i < code.len() && (code[i].is_ascii_alphanumeric() || code[i] == b'_') {
+                    i += 1;
+                }
+                let word = std::str::from_utf8(&code[start..i]).unwrap_or_default();
+                if keywords.contains(&word) {
+                    complexity += 1;
+                }
+                continue;
+            }
+            match (code[i], code.get(i + 1)) {
+                (b'&', Some(b'&')) | (b'|', Some(b'|')) => {
+                    complexity += 1;
+                    i += 1;
+                }
+                (b'?', Some(b'?' | b'.')) => i += 1,
+                (b'?', _) => complexity += 1,
+                _ => {}
+            }
+            i += 1;
+        }
+        complexity
+    }
+
+    /// Count the source lines of code in the chunk
+    ///
+    /// A line counts when it has a non-whitespace character outside comments,
+    /// so blank lines and lines holding only a comment are excluded, while a
+    /// line with code followed by a trailing comment is kept. Lines inside a
+    /// multi-line block comment are excluded; lines inside a multi-line string,
+    /// such as a Python docstring, count as code.
+    pub fn sloc(&self) -> usize {
+        let mut is_code: Vec<bool> = self.code.bytes().map(|b| !b.is_ascii_whitespace()).collect();
+        for range in comment_ranges(&self.code, self.language) {
+            is_code[range].fill(false);
+        }
+
+        let mut count = 0;
+        let mut start = 0;
+        for line in self.code.split_inclusive('\n') {
+            let end = start + line.len();
+            if is_code[start..end].contains(&true) {
+                count += 1;
+            }
+            start = end;
+        }
+        count
+    }
+
+    /// Get the code with the closing brackets a truncated chunk is missing
+    ///
+    /// This is a heuristic for feeding cut-off blocks to formatters, not a
+    /// parser: brackets, braces and parentheses outside string literals and
+    /// comments are counted, and those still open at the end are closed in
+    /// reverse order on a new line after the code. Returns `None` when every
+    /// bracket is closed, and when the code can't be repaired by appending
+    /// closers, i.e. it has a mismatched or unexpected closing bracket or
+    /// ends inside a string literal or comment.
+    pub fn repaired(&self) -> Option<String> {
+        let mut scanner = CodeBytes::new(&self.code, self.language);
+        let mut open = Vec::new();
+        for (_, byte) in scanner.by_ref() {
+            let closer = match byte {
+                b'(' => b')',
+                b'[' => b']',
+                b'{' => b'}',
+                b')' | b']' | b'}' => {
+                    if open.pop() != Some(byte) {
+                        return None;
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            open.push(closer);
+        }
+        if open.is_empty() || scanner.unterminated().is_some() {
+            return None;
+        }
+
+        let closers: String = open.iter().rev().map(|&b| b as char).collect();
+        Some(format!("{}\n{}", self.code.trim_end(), closers))
+    }
+
+    /// Order chunks by `(node_type, name)`, ignoring their positions
+    ///
+    /// Use with a stable sort, e.g. `chunks.sort_by(CodeChunk::semantic_cmp)`,
+    /// to line up two chunk sets for diffing; chunks with equal keys keep their
+    /// source order.
+    pub fn semantic_cmp(&self, other: &CodeChunk) -> Ordering {
+        (&self.node_type, &self.name).cmp(&(&other.node_type, &other.name))
+    }
+
+    /// Get a copy of the chunk with every match of `patterns` in its code
+    /// replaced by [`REDACTED`]
+    ///
+    /// Use this before caching chunks or sending them elsewhere, with patterns
+    /// for secrets such as API keys. Newlines inside a match are kept after
+    /// the placeholder, so line numbers still hold; `end_byte` and
+    /// `end_column` are recomputed for the new code and no longer point into
+    /// the original file.
+    pub fn redact(&self, patterns: &[Regex]) -> CodeChunk {
+        let mut code = self.code.clone();
+        for pattern in patterns {
+            code = pattern
+                .replace_all(&code, |caps: &regex::Captures| {
+                    let newlines = caps[0].matches('\n').count();
+                    format!("{}{}", REDACTED, "\n".repeat(newlines))
+                })
+                .into_owned();
+        }
+
+        let (lines, column) = line_column(&code, code.len());
+        CodeChunk {
+            end_byte: self.start_byte + code.len(),
+            end_column: if lines == 0 {
+                self.start_column + column
+            } else {
+                column
+            },
+            code,
+            ..self.clone()
+        }
+    }
+}
+
+/// Get the 0-indexed line and column of a byte offset in `code`
+///
+/// Columns count chars from the last newline, so a tab is a single column.
+/// Offsets past the end of `code` are clamped, and offsets inside a
+/// multi-byte char are moved back to its start.
+pub fn line_column(code: &str, byte: usize) -> (usize, usize) {
+    let mut end = byte.min(code.len());
+    while !code.is_char_boundary(end) {
+        end -= 1;
+    }
+    let prefix = &code[..end];
+    let line_start = prefix.rfind('\n').map_or(0, |i| i + 1);
+    (
+        prefix.matches('\n').count(),
+        prefix[line_start..].chars().count(),
+    )
+}
+
+/// Get the 1-based lines holding the byte range `start..end` of `code`
+///
+/// Lines are numbered as on extracted chunks, so the result is a chunk's
+/// `(start_line + 1, end_line + 1)` for its byte range. In particular the
+/// end line is the line of the `end` offset itself: a range ending just
+/// before a newline ends on that line, while one ending just after it ends
+/// on the next. Offsets past the end of `code` are clamped.
+pub fn byte_range_to_lines(code: &str, start: usize, end: usize) -> (usize, usize) {
+    let line = |byte: usize| {
+        let prefix = &code.as_bytes()[..byte.min(code.len())];
+        prefix.iter().filter(|&&b| b == b'\n').count() + 1
+    };
+    (line(start), line(end))
+}
+
+/// Remap chunk byte offsets after replacing `removed_len` bytes at
+/// `edit_start` with `inserted_len` new bytes
+///
+/// Chunks ending at or before the edit are unchanged and chunks starting at or
+/// after its end are shifted. A chunk containing the edit is resized to cover
+/// the inserted text. A chunk the edit crosses a boundary of is widened to
+/// cover the edit and marked [`CodeChunk::dirty`]. Only `start_byte` and
+/// `end_byte` are updated; `code`, lines and columns are left as they were.
+pub fn apply_edit(
+    chunks: &mut [CodeChunk],
+    edit_start: usize,
+    removed_len: usize,
+    inserted_len: usize,
+) {
+    let edit_end = edit_start + removed_len;
+
+    for chunk in chunks.iter_mut() {
+        if chunk.end_byte <= edit_start {
+            continue;
+        }
+        if chunk.start_byte >= edit_end {
+            chunk.start_byte = chunk.start_byte + inserted_len - removed_len;
+            chunk.end_byte = chunk.end_byte + inserted_len - removed_len;
+            continue;
+        }
+
+        let contains_edit = chunk.start_byte <= edit_start && edit_end <= chunk.end_byte;
+        chunk.dirty |= !contains_edit;
+        chunk.start_byte = chunk.start_byte.min(edit_start);
+        chunk.end_byte = (chunk.end_byte + inserted_len)
+            .saturating_sub(removed_len)
+            .max(edit_start + inserted_len);
+    }
 }
 
 /// Request to apply an edit
@@ -184,8 +625,375 @@ pub enum AgentBoosterError {
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+
     #[error("Internal error: {0}")]
     InternalError(String),
 }
 
 pub type Result<T> = std::result::Result<T, AgentBoosterError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(start_byte: usize, end_byte: usize) -> CodeChunk {
+        CodeChunk {
+            start_byte,
+            end_byte,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cyclomatic_complexity() {
+        let chunk = CodeChunk {
+            code: concat!(
+                "function check(user, order) {\n",
+                "  if (user.active && order.paid) {\n",
+                "    ship(order); // if shipped, notify\n",
+                "  }\n",
+                "  if (order.total > 100) {\n",
+                "    log(\"large order || review\");\n",
+                "  }\n",
+                "  return user?.name ?? 'unknown';\n",
+                "}\n",
+            )
+            .to_string(),
+            language: Language::JavaScript,
+            ..Default::default()
+        };
+        assert_eq!(chunk.cyclomatic_complexity(), 4);
+
+        let python = CodeChunk {
+            code: "def f(x):\n    if x and ready():\n        return 1\n    return 0\n".to_string(),
+            language: Language::Python,
+            ..Default::default()
+        };
+        assert_eq!(python.cyclomatic_complexity(), 3);
+        assert_eq!(CodeChunk::default().cyclomatic_complexity(), 1);
+    }
+
+    #[test]
+    fn test_line_column() {
+        let code = "a\n\tbé\nc";
+        assert_eq!(line_column(code, 0), (0, 0));
+        assert_eq!(line_column(code, 3), (1, 1));
+        assert_eq!(line_column(code, 6), (1, 3));
+        assert_eq!(line_column(code, 100), (2, 1));
+
+        // Inside 'é' counts as its start
+        assert_eq!(line_column(code, 5), (1, 2));
+        assert_eq!(line_column("é", 1), (0, 0));
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        let chunk = CodeChunk {
+            code: "function a() {\n  // one\n  return 1;\n}".to_string(),
+            ..Default::default()
+        };
+        // FNV-1a of the normalized code "function a() {\nreturn 1;\n}"
+        assert_eq!(chunk.content_hash(), 0x43c2_c675_fa84_85b1);
+        assert_eq!(chunk.content_hash(), chunk.content_hash_with(FnvHasher::default()));
+    }
+
+    #[cfg(feature = "strong-hash")]
+    #[test]
+    fn test_content_digest() {
+        let chunk = CodeChunk {
+            code: "fn a() {\n    1\n}".to_string(),
+            ..Default::default()
+        };
+        let reindented = CodeChunk {
+            code: "fn a() {\n  // one\n  1\n}\n".to_string(),
+            ..Default::default()
+        };
+        let other = CodeChunk {
+            code: "fn b() {}".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(chunk.content_digest(), reindented.content_digest());
+        assert_ne!(chunk.content_digest(), other.content_digest());
+
+        // SHA-256 of the empty string
+        let empty: String = CodeChunk::default()
+            .content_digest()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(
+            empty,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_content_hash_with_hashers_agree_with_themselves() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let chunk = CodeChunk {
+            code: "fn a() {\n    1\n}".to_string(),
+            ..Default::default()
+        };
+        let reindented = CodeChunk {
+            code: "fn a() {\n  1\n}\n".to_string(),
+            ..Default::default()
+        };
+        let other = CodeChunk {
+            code: "fn b() {}".to_string(),
+            ..Default::default()
+        };
+
+        let fnv = |c: &CodeChunk| c.content_hash_with(FnvHasher::default());
+        let sip = |c: &CodeChunk| c.content_hash_with(DefaultHasher::new());
+        assert_eq!(fnv(&chunk), fnv(&reindented));
+        assert_eq!(sip(&chunk), sip(&reindented));
+        assert_ne!(fnv(&chunk), fnv(&other));
+        assert_ne!(sip(&chunk), sip(&other));
+    }
+
+    #[test]
+    fn test_repaired_closes_truncated_blocks() {
+        let chunk = |code: &str| CodeChunk {
+            code: code.to_string(),
+            language: Language::JavaScript,
+            ..Default::default()
+        };
+
+        let truncated = chunk("function f(a) {\n  if (a) {\n    log(\"}\");\n");
+        assert_eq!(
+            truncated.repaired().unwrap(),
+            "function f(a) {\n  if (a) {\n    log(\"}\");\n}}"
+        );
+        let call = chunk("run(() => {\n  go([1, 2");
+        assert_eq!(call.repaired().unwrap(), "run(() => {\n  go([1, 2\n])})");
+
+        assert_eq!(chunk("function f() {\n  return 1;\n}").repaired(), None);
+        assert_eq!(chunk("function f() {\n  return [1);\n").repaired(), None);
+        assert_eq!(chunk("function f() {\n  /* cut off").repaired(), None);
+    }
+
+    #[test]
+    fn test_byte_range_to_lines() {
+        let code = "fn a() {}\n\nfn b() {\n    1\n}\n";
+
+        assert_eq!(byte_range_to_lines(code, 0, 0), (1, 1));
+        assert_eq!(byte_range_to_lines(code, 0, 9), (1, 1));
+        // Ending on the newline keeps the line; ending past it starts the next
+        assert_eq!(byte_range_to_lines(code, 0, 10), (1, 2));
+        assert_eq!(byte_range_to_lines(code, 11, 27), (3, 5));
+        assert_eq!(byte_range_to_lines(code, 11, 100), (3, 6));
+
+        let (line, _) = line_column(code, 22);
+        assert_eq!(byte_range_to_lines(code, 22, 22).0, line + 1);
+    }
+
+    #[test]
+    fn test_content_hash_with_options() {
+        let chunk = |code: &str| CodeChunk {
+            code: code.to_string(),
+            language: Language::JavaScript,
+            ..Default::default()
+        };
+        let two_spaces = chunk("function f(a) {\n  return a + \"  x\";\n}");
+        let four_spaces = chunk("function f(a) {\n    return a +\n        \"  x\";\n}\n");
+        let string_changed = chunk("function f(a) {\n  return a + \" x\";\n}");
+        let commented = chunk(
+            "// Add x\nfunction f(a) { /* sum */\n  return a + \"  x\"; // done\n}",
+        );
+
+        let exact = HashOptions::default();
+        let whitespace = HashOptions {
+            ignore_whitespace: true,
+            ..Default::default()
+        };
+        let everything = HashOptions {
+            ignore_whitespace: true,
+            ignore_comments: true,
+        };
+
+        let hash = |c: &CodeChunk, options| c.content_hash_with_options(options);
+        assert_ne!(hash(&two_spaces, exact), hash(&four_spaces, exact));
+        assert_eq!(hash(&two_spaces, whitespace), hash(&four_spaces, whitespace));
+        assert_ne!(hash(&two_spaces, whitespace), hash(&string_changed, whitespace));
+        assert_ne!(hash(&two_spaces, whitespace), hash(&commented, whitespace));
+        assert_eq!(hash(&two_spaces, everything), hash(&commented, everything));
+    }
+
+    #[test]
+    fn test_sloc_excludes_blanks_and_comments() {
+        let chunk = CodeChunk {
+            code: concat!(
+                "function total(items) {\n",
+                "  // Sum the prices\n",
+                "\n",
+                "  let sum = 0;\n",
+                "  /*\n",
+                "   * Skip refunds\n",
+                "   */\n",
+                "  for (const item of items) {\n",
+                "    sum += item.price; // in cents\n",
+                "  }\n",
+                "\n",
+                "  return sum;\n",
+                "}\n",
+            )
+            .to_string(),
+            language: Language::JavaScript,
+            start_line: 0,
+            end_line: 12,
+            ..Default::default()
+        };
+        assert_eq!(chunk.line_count(), 13);
+        assert_eq!(chunk.sloc(), 7);
+
+        let python = CodeChunk {
+            code: "def f():\n    # comment\n    '''doc'''\n\n    return 1\n".to_string(),
+            language: Language::Python,
+            ..Default::default()
+        };
+        assert_eq!(python.sloc(), 3);
+    }
+
+    #[test]
+    fn test_semantically_equal_ignores_offsets() {
+        let original = CodeChunk {
+            code: "function greet(name) {\n  return name;\n}".to_string(),
+            start_byte: 10,
+            end_byte: 49,
+            start_line: 2,
+            end_line: 4,
+            end_column: 1,
+            node_type: "function_declaration".to_string(),
+            name: Some("greet".to_string()),
+            ..Default::default()
+        };
+        let moved = CodeChunk {
+            code: "    function greet(name) {\n        return name;\n    }".to_string(),
+            start_byte: 120,
+            end_byte: 171,
+            start_line: 9,
+            end_line: 11,
+            start_column: 4,
+            end_column: 5,
+            ..original.clone()
+        };
+
+        assert_ne!(original, moved);
+        assert!(original.semantically_equal(&moved));
+
+        let renamed = CodeChunk {
+            name: Some("hello".to_string()),
+            ..moved.clone()
+        };
+        assert!(!original.semantically_equal(&renamed));
+
+        let class = CodeChunk {
+            node_type: "class_declaration".to_string(),
+            name: Some("Zed".to_string()),
+            ..Default::default()
+        };
+        let mut chunks = vec![renamed.clone(), moved.clone(), class.clone(), original.clone()];
+        chunks.sort_by(CodeChunk::semantic_cmp);
+        assert_eq!(chunks, vec![class, moved, original, renamed]);
+    }
+
+    #[test]
+    fn test_redact_api_key() {
+        let code = concat!(
+            "function connect() {\n",
+            "  const key = \"sk-live-4f9a8b7c6d5e4f3a\";\n",
+            "  return open(key);\n",
+            "}"
+        );
+        let function = CodeChunk {
+            code: code.to_string(),
+            start_byte: 100,
+            end_byte: 100 + code.len(),
+            start_line: 4,
+            end_line: 7,
+            end_column: 1,
+            ..Default::default()
+        };
+        let api_key = Regex::new(r"sk-live-[0-9a-f]{16}").unwrap();
+
+        let redacted = function.redact(&[api_key]);
+        assert_eq!(
+            redacted.code,
+            "function connect() {\n  const key = \"[REDACTED]\";\n  return open(key);\n}"
+        );
+        assert_eq!(redacted.end_byte, 100 + redacted.code.len());
+        assert_eq!((redacted.start_line, redacted.end_line), (4, 7));
+        assert_eq!(redacted.end_column, 1);
+
+        let spanning = function.redact(&[Regex::new(r"(?s)const key.*?return").unwrap()]);
+        assert_eq!(spanning.code, "function connect() {\n  [REDACTED]\n open(key);\n}");
+    }
+
+    #[test]
+    fn test_chunk_body_excludes_braces() {
+        let function = CodeChunk {
+            code: "function add(a, b) {\n  const sum = a + b;\n  return `${sum}}`;\n}".to_string(),
+            language: Language::JavaScript,
+            ..Default::default()
+        };
+        assert_eq!(
+            function.body(),
+            Some("\n  const sum = a + b;\n  return `${sum}}`;\n")
+        );
+
+        let arrow = CodeChunk {
+            code: "const double = (x) => x * 2;".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(arrow.body(), None);
+
+        let python = CodeChunk {
+            code: "def f():\n    return {}".to_string(),
+            language: Language::Python,
+            ..Default::default()
+        };
+        assert_eq!(python.body(), None);
+    }
+
+    #[test]
+    fn test_apply_edit_before_chunk() {
+        let mut chunks = vec![chunk(20, 40)];
+        apply_edit(&mut chunks, 5, 2, 10);
+
+        assert_eq!((chunks[0].start_byte, chunks[0].end_byte), (28, 48));
+        assert!(!chunks[0].dirty);
+    }
+
+    #[test]
+    fn test_apply_edit_inside_chunk() {
+        let mut chunks = vec![chunk(20, 40)];
+        apply_edit(&mut chunks, 25, 5, 15);
+
+        assert_eq!((chunks[0].start_byte, chunks[0].end_byte), (20, 50));
+        assert!(!chunks[0].dirty);
+    }
+
+    #[test]
+    fn test_apply_edit_after_chunk() {
+        let mut chunks = vec![chunk(20, 40)];
+        apply_edit(&mut chunks, 40, 3, 0);
+
+        assert_eq!((chunks[0].start_byte, chunks[0].end_byte), (20, 40));
+        assert!(!chunks[0].dirty);
+    }
+
+    #[test]
+    fn test_apply_edit_across_boundary() {
+        let mut chunks = vec![chunk(20, 40), chunk(50, 60)];
+        apply_edit(&mut chunks, 35, 10, 2);
+
+        assert_eq!((chunks[0].start_byte, chunks[0].end_byte), (20, 37));
+        assert!(chunks[0].dirty);
+        assert_eq!((chunks[1].start_byte, chunks[1].end_byte), (42, 52));
+        assert!(!chunks[1].dirty);
+    }
+}
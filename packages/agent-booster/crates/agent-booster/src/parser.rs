@@ -1,10 +1,31 @@
-use crate::models::{AgentBoosterError, CodeChunk, Language, Result};
+use crate::extractor::ChunkExtractor;
+use crate::models::{line_column, AgentBoosterError, CodeChunk, Language, Result};
+use std::cell::RefCell;
 use tree_sitter::{Parser as TSParser, Tree};
 
+/// tree-sitter node kinds extracted as chunks
+///
+/// Kept separate from [`Language::supported_node_types`], which describes the
+/// chunk types either parser may report rather than selecting grammar nodes.
+const CHUNK_NODE_KINDS: &[&str] = &[
+    "function_declaration",
+    "function_expression",
+    "arrow_function",
+    "method_definition",
+    "class_declaration",
+    "class",
+    "interface_declaration",
+    "type_alias_declaration",
+    "enum_declaration",
+    "variable_declaration",
+    "export_statement",
+    "import_statement",
+];
+
 /// Parser for extracting code chunks using tree-sitter
 pub struct Parser {
-    js_parser: TSParser,
-    ts_parser: TSParser,
+    js_parser: RefCell<TSParser>,
+    ts_parser: RefCell<TSParser>,
 }
 
 impl Parser {
@@ -21,22 +42,35 @@ impl Parser {
             .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?;
 
         Ok(Self {
-            js_parser,
-            ts_parser,
+            js_parser: RefCell::new(js_parser),
+            ts_parser: RefCell::new(ts_parser),
         })
     }
 
+    /// Get the languages with a dedicated grammar
+    ///
+    /// Other languages are parsed with the JavaScript grammar.
+    pub fn supported_languages() -> &'static [Language] {
+        &[Language::JavaScript, Language::TypeScript]
+    }
+
     /// Parse code into a tree-sitter Tree
     pub fn parse(&mut self, code: &str, language: Language) -> Result<Tree> {
+        self.parse_shared(code, language)
+    }
+
+    /// Parse through a shared reference, as needed by [`ChunkExtractor`]
+    fn parse_shared(&self, code: &str, language: Language) -> Result<Tree> {
         let parser = match language {
-            Language::JavaScript => &mut self.js_parser,
-            Language::TypeScript => &mut self.ts_parser,
+            Language::JavaScript => &self.js_parser,
+            Language::TypeScript => &self.ts_parser,
             // For other languages in native build, fall back to JavaScript parser
             // (This is only used when tree-sitter feature is enabled, which is native-only)
-            _ => &mut self.js_parser,
+            _ => &self.js_parser,
         };
 
         parser
+            .borrow_mut()
             .parse(code, None)
             .ok_or_else(|| AgentBoosterError::ParseError("Failed to parse code".to_string()))
     }
@@ -63,21 +97,7 @@ impl Parser {
         let node_type = node.kind();
 
         // Extract meaningful code blocks
-        let is_chunk = matches!(
-            node_type,
-            "function_declaration"
-                | "function_expression"
-                | "arrow_function"
-                | "method_definition"
-                | "class_declaration"
-                | "class"
-                | "interface_declaration"
-                | "type_alias_declaration"
-                | "enum_declaration"
-                | "variable_declaration"
-                | "export_statement"
-                | "import_statement"
-        );
+        let is_chunk = CHUNK_NODE_KINDS.contains(&node_type);
 
         if is_chunk && !node.is_error() {
             let start_byte = node.start_byte();
@@ -86,6 +106,20 @@ impl Parser {
             if let Ok(chunk_code) = std::str::from_utf8(&code.as_bytes()[start_byte..end_byte]) {
                 let start_line = node.start_position().row;
                 let end_line = node.end_position().row;
+                // tree-sitter columns are in bytes; chunks report chars
+                let (_, start_column) = line_column(code, start_byte);
+                let (_, end_column) = line_column(code, end_byte);
+                let name = node
+                    .child_by_field_name("name")
+                    .and_then(|n| n.utf8_text(code.as_bytes()).ok())
+                    .map(|n| n.to_string());
+                let mut decorator_cursor = node.walk();
+                let decorators = node
+                    .children(&mut decorator_cursor)
+                    .filter(|c| c.kind() == "decorator")
+                    .filter_map(|c| c.utf8_text(code.as_bytes()).ok())
+                    .map(|d| d.to_string())
+                    .collect();
 
                 chunks.push(CodeChunk {
                     code: chunk_code.to_string(),
@@ -93,8 +127,15 @@ impl Parser {
                     end_byte,
                     start_line,
                     end_line,
+                    start_column,
+                    end_column,
                     node_type: node_type.to_string(),
+                    language: Language::default(),
                     parent_type: parent_type.clone(),
+                    name,
+                    decorators,
+                    dirty: false,
+                    match_span: None,
                 });
             }
         }
@@ -108,8 +149,8 @@ impl Parser {
     }
 
     /// Validate that code has valid syntax
-    pub fn validate_syntax(&mut self, code: &str, language: Language) -> bool {
-        match self.parse(code, language) {
+    pub fn validate_syntax(&self, code: &str, language: Language) -> bool {
+        match self.parse_shared(code, language) {
             Ok(tree) => !tree.root_node().has_error(),
             Err(_) => false,
         }
@@ -118,14 +159,22 @@ impl Parser {
     /// Extract full file as a single chunk (fallback for small files)
     pub fn extract_full_file(&self, code: &str) -> CodeChunk {
         let lines = code.lines().count();
+        let (_, end_column) = line_column(code, code.len());
         CodeChunk {
             code: code.to_string(),
             start_byte: 0,
             end_byte: code.len(),
             start_line: 0,
             end_line: lines.saturating_sub(1),
+            start_column: 0,
+            end_column,
             node_type: "file".to_string(),
+            language: Language::default(),
             parent_type: None,
+            name: None,
+            decorators: Vec::new(),
+            dirty: false,
+            match_span: None,
         }
     }
 }
@@ -136,6 +185,27 @@ impl Default for Parser {
     }
 }
 
+impl ChunkExtractor for Parser {
+    fn extract_chunks(&self, code: &str, language: Language) -> Vec<CodeChunk> {
+        let mut chunks = match self.parse_shared(code, language) {
+            Ok(tree) => Parser::extract_chunks(self, &tree, code),
+            Err(_) => Vec::new(),
+        };
+        for chunk in &mut chunks {
+            chunk.language = language;
+        }
+        chunks
+    }
+
+    fn validate_syntax(&self, code: &str, language: Language) -> Result<bool> {
+        Ok(!self.parse_shared(code, language)?.root_node().has_error())
+    }
+
+    fn extract_full_file(&self, code: &str) -> CodeChunk {
+        Parser::extract_full_file(self, code)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -179,8 +249,31 @@ class Bar {
     }
 
     #[test]
-    fn test_validate_syntax() {
+    fn test_interface_members_and_modules_are_not_chunks() {
         let mut parser = Parser::new().unwrap();
+        let code = r#"
+declare module "config" {
+    export const debug: boolean;
+}
+
+interface Point {
+    x: number;
+    move(dx: number): void;
+}
+"#;
+        let tree = parser.parse(code, Language::TypeScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+        let kinds: Vec<&str> = chunks.iter().map(|c| c.node_type.as_str()).collect();
+
+        assert!(kinds.contains(&"interface_declaration"));
+        assert!(!kinds.contains(&"module"));
+        assert!(!kinds.contains(&"property_signature"));
+        assert!(!kinds.contains(&"method_signature"));
+    }
+
+    #[test]
+    fn test_validate_syntax() {
+        let parser = Parser::new().unwrap();
         assert!(parser.validate_syntax("function test() {}", Language::JavaScript));
         assert!(!parser.validate_syntax("function test() {", Language::JavaScript));
     }
@@ -4,122 +4,578 @@
 //! It uses regex-based parsing for JavaScript/TypeScript code extraction.
 //!
 //! Trade-off: ~80% accuracy vs tree-sitter's ~95%, but compiles to WASM without issues.
-
-use crate::models::{AgentBoosterError, CodeChunk, Language, Result};
+//!
+//! Each language has its own set of declaration patterns, compiled on first use
+//! and selected by the `Language` passed to [`Parser::parse`].
+
+use crate::extractor::ChunkExtractor;
+use crate::lexer::{block_end, comment_ranges, statement_bounds, CodeBytes};
+use crate::models::{line_column, CodeChunk, Language, Result};
+pub use crate::models::UNKNOWN_LINE;
+use once_cell::sync::Lazy;
 use regex::Regex;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Keywords that look like method headers (`if (...) {`) inside a class body
+const JS_CONTROL_KEYWORDS: &[&str] = &[
+    "if", "for", "while", "switch", "catch", "with", "function", "return",
+];
+
+/// How the extent of a declaration is found once its header matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockStyle {
+    /// Block delimited by matching braces
+    Braces,
+    /// Block delimited by indentation (Python)
+    Indentation,
+}
 
-/// Placeholder tree type for lite parser (no actual tree structure)
-pub struct LiteTree {
-    code: String,
-    language: Language,
+/// A declaration header pattern and the chunk type it produces
+struct DeclarationPattern {
+    node_type: &'static str,
+    regex: Regex,
+    block: BlockStyle,
+    /// Whether members are extracted from the block body as child chunks
+    has_members: bool,
+    /// Whether the block body holds type member signatures, each extracted
+    /// as a child chunk
+    has_signatures: bool,
 }
 
-/// Lite parser that works in WASM without tree-sitter C dependencies
+impl DeclarationPattern {
+    fn new(node_type: &'static str, pattern: &str, block: BlockStyle) -> Self {
+        Self {
+            node_type,
+            regex: Regex::new(pattern).unwrap(),
+            block,
+            has_members: false,
+            has_signatures: false,
+        }
+    }
+
+    fn with_members(mut self) -> Self {
+        self.has_members = true;
+        self
+    }
+
+    fn with_signatures(mut self) -> Self {
+        self.has_signatures = true;
+        self
+    }
+}
+
+/// Declaration patterns for one language, applied in order
+struct LanguagePatterns {
+    declarations: Vec<DeclarationPattern>,
+    /// Pattern for members declared directly inside a container block
+    member: Option<DeclarationPattern>,
+}
+
+/// A JS/TS identifier: a Unicode letter, `$` or `_`, then letters, digits,
+/// `$`, `_` or joiners
+const JS_IDENTIFIER: &str = r"[\p{ID_Start}$_][\p{ID_Continue}$\x{200C}\x{200D}]*";
+
+/// Substitute [`JS_IDENTIFIER`] for each `{ident}` in a pattern
+fn js_pattern(pattern: &str) -> String {
+    pattern.replace("{ident}", JS_IDENTIFIER)
+}
+
+static JS_PATTERNS: Lazy<LanguagePatterns> = Lazy::new(|| LanguagePatterns {
+    declarations: vec![
+        DeclarationPattern::new(
+            "function_declaration",
+            &js_pattern(
+                r"(?m)^\s*(?:export\s+)?(?:async\s+)?function\s+({ident})\s*\([^)]*\)\s*\{",
+            ),
+            BlockStyle::Braces,
+        ),
+        DeclarationPattern::new(
+            "class_declaration",
+            &js_pattern(r"(?m)^\s*(?:export\s+)?class\s+({ident})(?:\s+extends\s+{ident})?\s*\{"),
+            BlockStyle::Braces,
+        )
+        .with_members(),
+        DeclarationPattern::new(
+            "interface_declaration",
+            &js_pattern(r"(?m)^\s*(?:export\s+)?(?:declare\s+)?interface\s+({ident})[^{;]*\{"),
+            BlockStyle::Braces,
+        )
+        .with_signatures(),
+        // TypeScript namespaces and ambient modules; their declarations are
+        // matched by the patterns above and parented in extraction
+        DeclarationPattern::new(
+            "namespace",
+            &js_pattern(
+                r"(?m)^\s*(?:export\s+)?(?:declare\s+)?namespace\s+({ident}(?:\.{ident})*)\s*\{",
+            ),
+            BlockStyle::Braces,
+        ),
+        DeclarationPattern::new(
+            "module",
+            &js_pattern(concat!(
+                r"(?m)^\s*(?:export\s+)?(?:declare\s+)?module\s+",
+                r#"(?:["']([^"'\n]+)["']|({ident}(?:\.{ident})*))\s*\{"#,
+            )),
+            BlockStyle::Braces,
+        ),
+    ],
+    member: Some(DeclarationPattern::new(
+        "method_definition",
+        &js_pattern(r"(?m)^[ \t]*(?:(?:static|async|get|set)\s+)*({ident})\s*\([^)]*\)\s*\{"),
+        BlockStyle::Braces,
+    )),
+});
+
+static PYTHON_PATTERNS: Lazy<LanguagePatterns> = Lazy::new(|| LanguagePatterns {
+    declarations: vec![
+        DeclarationPattern::new(
+            "function_definition",
+            r"(?m)^\s*(?:async\s+)?def\s+(\w+)\s*\([^)]*\)\s*:",
+            BlockStyle::Indentation,
+        ),
+        DeclarationPattern::new(
+            "class_definition",
+            r"(?m)^\s*class\s+(\w+)(?:\([^)]*\))?\s*:",
+            BlockStyle::Indentation,
+        ),
+    ],
+    member: None,
+});
+
+static RUST_PATTERNS: Lazy<LanguagePatterns> = Lazy::new(|| LanguagePatterns {
+    declarations: vec![
+        DeclarationPattern::new(
+            "fn",
+            r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?(?:const\s+)?(?:async\s+)?(?:unsafe\s+)?fn\s+(\w+)\s*(?:<[^>]*>)?\s*\([^)]*\)(?:\s*->\s*[^\{;]+)?(?:\s*where[^\{;]*)?\s*\{",
+            BlockStyle::Braces,
+        ),
+        DeclarationPattern::new(
+            "struct",
+            r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?struct\s+(\w+)(?:<[^>]*>)?(?:\s*where[^\{;]*)?\s*\{",
+            BlockStyle::Braces,
+        ),
+        DeclarationPattern::new(
+            "enum",
+            r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?enum\s+(\w+)(?:<[^>]*>)?(?:\s*where[^\{;]*)?\s*\{",
+            BlockStyle::Braces,
+        ),
+        // The captured name is the implementing type, also for trait impls
+        DeclarationPattern::new(
+            "impl",
+            r"(?m)^\s*(?:unsafe\s+)?impl(?:<[^>]*>)?\s+(?:[\w:]+(?:<[^>]*>)?\s+for\s+)?(\w+)(?:<[^>]*>)?(?:\s*where[^\{;]*)?\s*\{",
+            BlockStyle::Braces,
+        ),
+    ],
+    member: None,
+});
+
+static GO_PATTERNS: Lazy<LanguagePatterns> = Lazy::new(|| LanguagePatterns {
+    declarations: vec![
+        DeclarationPattern::new(
+            "function_declaration",
+            r"(?m)^\s*func\s+(?:\([^)]*\)\s+)?(\w+)\s*\([^)]*\)(?:\s*\([^)]*\))?\s*\{",
+            BlockStyle::Braces,
+        ),
+        DeclarationPattern::new(
+            "type_declaration",
+            r"(?m)^\s*type\s+(\w+)\s+struct\s*\{",
+            BlockStyle::Braces,
+        ),
+    ],
+    member: None,
+});
+
+static JAVA_PATTERNS: Lazy<LanguagePatterns> = Lazy::new(|| LanguagePatterns {
+    declarations: vec![
+        DeclarationPattern::new(
+            "class_declaration",
+            r"(?m)^\s*(?:public\s+)?(?:abstract\s+)?class\s+(\w+)(?:\s+extends\s+\w+)?(?:\s+implements\s+[\w,\s]+)?\s*\{",
+            BlockStyle::Braces,
+        ),
+        DeclarationPattern::new(
+            "method_declaration",
+            r"(?m)^\s*(?:public|private|protected)?\s*(?:static\s+)?(?:final\s+)?(?:\w+(?:<[^>]*>)?)\s+(\w+)\s*\([^)]*\)\s*(?:throws\s+[\w,\s]+)?\s*\{",
+            BlockStyle::Braces,
+        ),
+    ],
+    member: None,
+});
+
+const C_FUNCTION_PATTERN: &str =
+    r"(?m)^\s*(?:static\s+)?(?:inline\s+)?(?:\w+\s*\*?\s+)?(\w+)\s*\([^)]*\)\s*\{";
+
+static C_PATTERNS: Lazy<LanguagePatterns> = Lazy::new(|| LanguagePatterns {
+    declarations: vec![DeclarationPattern::new(
+        "function_definition",
+        C_FUNCTION_PATTERN,
+        BlockStyle::Braces,
+    )],
+    member: None,
+});
+
+static CPP_PATTERNS: Lazy<LanguagePatterns> = Lazy::new(|| LanguagePatterns {
+    declarations: vec![
+        DeclarationPattern::new(
+            "class_specifier",
+            r"(?m)^\s*(?:template\s*<[^>]*>\s*)?class\s+(\w+)(?:\s*:\s*(?:public|private|protected)\s+\w+)?\s*\{",
+            BlockStyle::Braces,
+        ),
+        DeclarationPattern::new("function_definition", C_FUNCTION_PATTERN, BlockStyle::Braces),
+    ],
+    member: None,
+});
+
+/// Markup has no declarations of its own; see [`ChunkExtractor::extract_embedded`]
+static MARKUP_PATTERNS: Lazy<LanguagePatterns> = Lazy::new(|| LanguagePatterns {
+    declarations: Vec::new(),
+    member: None,
+});
+
+/// Start of a decorator or annotation line (`@Component`, `@app.route`)
+static DECORATOR: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^[ \t]*@[\w.]+").unwrap());
+
+/// Comment opening a region: `// #region Name`, `# region Name`
+static REGION_START: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[ \t]*(?://|#)[ \t]*#?region\b[ \t]*(.*?)\s*$").unwrap());
+
+/// Comment closing the innermost open region
+static REGION_END: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[ \t]*(?://|#)[ \t]*#?endregion\b").unwrap());
+
+/// Comment starting a section that runs to the next one: `// MARK: - Name`
+static MARK: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[ \t]*//[ \t]*MARK:[ \t]*(?:-[ \t]*)?(.*?)\s*$").unwrap());
+
+/// Start of a JS/TS variable statement: `const`, `let` or `var`, optionally
+/// exported
+static VARIABLE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^\s*(?:export\s+)?(?:const|let|var)\s+").unwrap());
+
+/// Name at the start of a variable declarator; destructuring patterns have
+/// none
+static DECLARATOR_NAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(&js_pattern(r"^({ident})")).unwrap());
+
+/// Name of a type member signature, followed by `(` or `<` for a method
+static SIGNATURE_NAME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&js_pattern(r"^(?:readonly\s+)?({ident})\s*\??\s*(?:([(<])|:)")).unwrap()
+});
+
+/// Bytes that, ending a line, continue a type member onto the next one
+const CONTINUING_TYPE_ENDS: &[u8] = b":|&=";
+
+/// Bytes that, starting a line, continue the type member of the previous one
+const CONTINUING_TYPE_STARTS: &[u8] = b"|&=";
+
+/// Bytes that, ending a line, continue a statement onto the next one
+const CONTINUING_LINE_ENDS: &[u8] = b"=,+-*/%&|?:<>!.";
+
+/// Bytes that, starting a line, continue the statement of the previous one
+const CONTINUING_LINE_STARTS: &[u8] = b".?&|=<>,:+*%";
+
+/// Languages with declaration patterns, in [`Language`] order
+const SUPPORTED_LANGUAGES: &[Language] = &[
+    Language::JavaScript,
+    Language::TypeScript,
+    Language::Python,
+    Language::Rust,
+    Language::Go,
+    Language::Java,
+    Language::C,
+    Language::Cpp,
+    Language::Html,
+];
+
+/// Get the declaration patterns for a language, compiling them on first use
+fn patterns_for(language: Language) -> &'static LanguagePatterns {
+    match language {
+        Language::JavaScript | Language::TypeScript => &JS_PATTERNS,
+        Language::Python => &PYTHON_PATTERNS,
+        Language::Rust => &RUST_PATTERNS,
+        Language::Go => &GO_PATTERNS,
+        Language::Java => &JAVA_PATTERNS,
+        Language::C => &C_PATTERNS,
+        Language::Cpp => &CPP_PATTERNS,
+        Language::Html => &MARKUP_PATTERNS,
+    }
+}
+
+/// Get the offset of a declaration's first keyword, skipping the leading
+/// whitespace matched by `^\s*`
+fn declaration_start(m: &regex::Match) -> usize {
+    let text = m.as_str();
+    m.start() + text.len() - text.trim_start().len()
+}
+
+/// Get the name following a region marker, if one was given
+fn marker_name<'a>(cap: &regex::Captures<'a>) -> Option<&'a str> {
+    cap.get(1).map(|m| m.as_str()).filter(|name| !name.is_empty())
+}
+
+/// Split code into identifier, number and single punctuation tokens,
+/// skipping whitespace and comments
+fn code_tokens(code: &str, language: Language) -> Vec<&str> {
+    let comments = comment_ranges(code, language);
+    let mut tokens = Vec::new();
+    let mut word_start = None;
+    for (pos, c) in code.char_indices() {
+        let is_word = c.is_alphanumeric() || c == '_' || c == '$';
+        if let Some(start) = word_start.filter(|_| !is_word) {
+            tokens.push(&code[start..pos]);
+            word_start = None;
+        }
+        if comments.iter().any(|range| range.contains(&pos)) || c.is_whitespace() {
+            continue;
+        }
+        if is_word {
+            word_start.get_or_insert(pos);
+        } else {
+            tokens.push(&code[pos..pos + c.len_utf8()]);
+        }
+    }
+    if let Some(start) = word_start {
+        tokens.push(&code[start..]);
+    }
+    tokens
+}
+
+/// Check whether bytes skipped by [`CodeBytes`] hold a string literal, so
+/// the code before the next byte ends with a value rather than an operator
+fn ends_string_literal(skipped: &str) -> bool {
+    skipped.trim().starts_with(['"', '\'', '`'])
+}
+
+/// Sort byte ranges and merge those that overlap or touch
+fn merge_ranges(ranges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut sorted: Vec<(usize, usize)> = ranges.iter().copied().filter(|(s, e)| s < e).collect();
+    sorted.sort_unstable();
+
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in sorted {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Check whether `pos` lies in one of the sorted, disjoint `ranges`
+fn in_ranges(ranges: &[(usize, usize)], pos: usize) -> bool {
+    let i = ranges.partition_point(|&(start, _)| start <= pos);
+    i > 0 && pos < ranges[i - 1].1
+}
+
+/// Append chunks while `total` code bytes stay below `max_total_bytes`
 ///
-/// This parser uses regex-based matching instead of tree-sitter's C library.
-/// It provides ~80% accuracy vs tree-sitter's ~95%, but compiles to WASM.
-pub struct Parser {
-    // JavaScript/TypeScript patterns
-    js_function_regex: Regex,
-    js_class_regex: Regex,
+/// The chunk that reaches the budget is still appended whole. Returns whether
+/// any chunks were left out.
+fn push_within_budget(
+    chunks: &mut Vec<CodeChunk>,
+    new: impl IntoIterator<Item = CodeChunk>,
+    total: &mut usize,
+    max_total_bytes: usize,
+) -> bool {
+    for chunk in new {
+        if *total >= max_total_bytes {
+            return true;
+        }
+        *total += chunk.code.len();
+        chunks.push(chunk);
+    }
+    false
+}
 
-    // Python patterns
-    py_function_regex: Regex,
-    py_class_regex: Regex,
+/// Get the closing bracket matching an opening one
+fn closing_bracket(opener: u8) -> char {
+    match opener {
+        b'(' => ')',
+        b'[' => ']',
+        _ => '}',
+    }
+}
 
-    // Rust patterns
-    rust_function_regex: Regex,
-    rust_struct_regex: Regex,
-    rust_impl_regex: Regex,
+/// Placeholder tree type for lite parser (no actual tree structure)
+pub struct LiteTree {
+    code: String,
+    language: Language,
+}
 
-    // Go patterns
-    go_function_regex: Regex,
-    go_struct_regex: Regex,
+/// Options for the lite parser
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParserConfig {
+    /// When no declarations are found, split the file into windows of this
+    /// many lines, emitted as `"window"` chunks
+    pub fallback_window_lines: Option<usize>,
+    /// Emit `"module_body"` chunks for top-level code outside every extracted
+    /// declaration, such as imports and top-level `await`
+    pub emit_module_body: bool,
+    /// Emit `"region"` chunks for sections delimited by `#region` /
+    /// `#endregion` or `// MARK:` comments
+    pub emit_regions: bool,
+    /// Emit `"variable_declaration"` chunks for top-level `const`, `let` and
+    /// `var` statements in JavaScript and TypeScript, one per declarator
+    pub emit_variables: bool,
+    /// Treat files with a line longer than this many bytes as minified: skip
+    /// declaration patterns and split the file into `"byte_window"` chunks of
+    /// at most this size, cut after a top-level `;` or `}` where possible.
+    /// Their lines and columns are set to [`UNKNOWN_LINE`].
+    pub max_line_length: Option<usize>,
+    /// Record on declaration and member chunks the byte range of the pattern
+    /// match that produced them, in [`CodeChunk::match_span`], to help trace
+    /// false positives
+    pub debug_spans: bool,
+}
 
-    // Java patterns
-    java_method_regex: Regex,
-    java_class_regex: Regex,
 
-    // C/C++ patterns
-    c_function_regex: Regex,
-    cpp_class_regex: Regex,
+/// Most chunks [`Parser::find_near_duplicates`] compares, bounding its
+/// quadratic pairwise pass
+pub const MAX_DUPLICATE_CHUNKS: usize = 2_000;
+
+/// Tokens per shingle compared by [`Parser::find_near_duplicates`]
+const SHINGLE_TOKENS: usize = 3;
+
+/// Problem found by [`Parser::validate_syntax_detailed`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// What went wrong
+    pub kind: SyntaxErrorKind,
+    /// Byte offset of the offending delimiter
+    pub byte: usize,
+    /// 0-indexed line of the offending delimiter
+    pub line: usize,
+    /// 0-indexed column, in characters, of the offending delimiter
+    pub column: usize,
 }
 
-impl Parser {
-    /// Create a new lite parser
-    pub fn new() -> Result<Self> {
-        Ok(Self {
-            // JavaScript/TypeScript
-            js_function_regex: Regex::new(
-                r"(?m)^\s*(?:export\s+)?(?:async\s+)?function\s+(\w+)\s*\([^)]*\)\s*\{",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+/// Kind of [`SyntaxError`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxErrorKind {
+    /// A closing bracket with nothing open
+    UnexpectedClose { found: char },
+    /// A closing bracket that doesn't match the innermost open one
+    MismatchedClose { expected: char, found: char },
+    /// An opening bracket still open at the end of the code
+    Unclosed { delimiter: char },
+    /// A string, template or comment still open at the end of its line or
+    /// of the code
+    UnterminatedLiteral { delimiter: &'static str },
+}
 
-            js_class_regex: Regex::new(
-                r"(?m)^\s*(?:export\s+)?class\s+(\w+)(?:\s+extends\s+\w+)?\s*\{",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (line, column) = (self.line + 1, self.column + 1);
+        match &self.kind {
+            SyntaxErrorKind::UnexpectedClose { found } => {
+                write!(f, "unexpected '{}' at {}:{}", found, line, column)
+            }
+            SyntaxErrorKind::MismatchedClose { expected, found } => write!(
+                f,
+                "expected '{}' but found '{}' at {}:{}",
+                expected, found, line, column
+            ),
+            SyntaxErrorKind::Unclosed { delimiter } => {
+                write!(f, "unclosed '{}' opened at {}:{}", delimiter, line, column)
+            }
+            SyntaxErrorKind::UnterminatedLiteral { delimiter } => write!(
+                f,
+                "unterminated {} literal starting at {}:{}",
+                delimiter, line, column
+            ),
+        }
+    }
+}
 
-            // Python
-            py_function_regex: Regex::new(
-                r"(?m)^\s*(?:async\s+)?def\s+(\w+)\s*\([^)]*\)\s*:",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+impl std::error::Error for SyntaxError {}
 
-            py_class_regex: Regex::new(
-                r"(?m)^\s*class\s+(\w+)(?:\([^)]*\))?\s*:",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+impl SyntaxError {
+    fn at(code: &str, byte: usize, kind: SyntaxErrorKind) -> Self {
+        let (line, column) = line_column(code, byte);
+        Self {
+            kind,
+            byte,
+            line,
+            column,
+        }
+    }
+}
 
-            // Rust
-            rust_function_regex: Regex::new(
-                r"(?m)^\s*(?:pub\s+)?(?:async\s+)?fn\s+(\w+)\s*(?:<[^>]*>)?\s*\([^)]*\)(?:\s*->\s*[^\{]+)?\s*\{",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+/// Chunks and syntax check of one file, produced by [`Parser::analyze`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileAnalysis {
+    /// Chunks, as returned by [`Parser::extract_chunks`]
+    pub chunks: Vec<CodeChunk>,
+    /// Whether the code passed [`Parser::validate_syntax`]
+    pub is_valid: bool,
+    /// The problem that made the code invalid
+    pub first_error: Option<SyntaxError>,
+}
 
-            rust_struct_regex: Regex::new(
-                r"(?m)^\s*(?:pub\s+)?struct\s+(\w+)(?:<[^>]*>)?\s*\{",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+/// How a declaration changed between two versions of a file
+///
+/// Indices point into the `old` and `new` chunk slices given to
+/// [`Parser::diff_chunks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkChange {
+    /// Only in the new version
+    Added { new: usize },
+    /// Only in the old version
+    Removed { old: usize },
+    /// In both versions with different code
+    Modified { old: usize, new: usize },
+    /// In both versions with the same code, possibly moved
+    Unchanged { old: usize, new: usize },
+}
 
-            rust_impl_regex: Regex::new(
-                r"(?m)^\s*impl(?:<[^>]*>)?\s+(\w+)(?:<[^>]*>)?\s*\{",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+/// Chunk-level changes between two versions of a file, produced by
+/// [`Parser::diff_chunks`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkDiff {
+    /// Changes of old chunks in their order, then added chunks in theirs
+    pub changes: Vec<ChunkChange>,
+}
 
-            // Go
-            go_function_regex: Regex::new(
-                r"(?m)^\s*func\s+(?:\([^)]*\)\s+)?(\w+)\s*\([^)]*\)(?:\s*\([^)]*\))?\s*\{",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+impl ChunkDiff {
+    /// Check whether any chunk was added, removed or modified
+    pub fn has_changes(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|c| !matches!(c, ChunkChange::Unchanged { .. }))
+    }
+}
 
-            go_struct_regex: Regex::new(
-                r"(?m)^\s*type\s+(\w+)\s+struct\s*\{",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+/// Lite parser that works in WASM without tree-sitter C dependencies
+///
+/// This parser uses regex-based matching instead of tree-sitter's C library.
+/// It provides ~80% accuracy vs tree-sitter's ~95%, but compiles to WASM.
+pub struct Parser {
+    config: ParserConfig,
+}
 
-            // Java
-            java_method_regex: Regex::new(
-                r"(?m)^\s*(?:public|private|protected)?\s*(?:static\s+)?(?:final\s+)?(?:\w+(?:<[^>]*>)?)\s+(\w+)\s*\([^)]*\)\s*(?:throws\s+[\w,\s]+)?\s*\{",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+impl Parser {
+    /// Create a new lite parser
+    pub fn new() -> Result<Self> {
+        Self::with_config(ParserConfig::default())
+    }
 
-            java_class_regex: Regex::new(
-                r"(?m)^\s*(?:public\s+)?(?:abstract\s+)?class\s+(\w+)(?:\s+extends\s+\w+)?(?:\s+implements\s+[\w,\s]+)?\s*\{",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+    /// Create a new lite parser with the given options
+    pub fn with_config(config: ParserConfig) -> Result<Self> {
+        Ok(Self { config })
+    }
 
-            // C/C++
-            c_function_regex: Regex::new(
-                r"(?m)^\s*(?:static\s+)?(?:inline\s+)?(?:\w+\s*\*?\s+)?(\w+)\s*\([^)]*\)\s*\{",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
+    /// Get the parser options
+    pub fn config(&self) -> &ParserConfig {
+        &self.config
+    }
 
-            cpp_class_regex: Regex::new(
-                r"(?m)^\s*(?:template\s*<[^>]*>\s*)?class\s+(\w+)(?:\s*:\s*(?:public|private|protected)\s+\w+)?\s*\{",
-            )
-            .map_err(|e| AgentBoosterError::ParseError(e.to_string()))?,
-        })
+    /// Get the languages this parser extracts chunks from
+    ///
+    /// [`Language::supported_node_types`] lists the chunk types each one can
+    /// produce.
+    pub fn supported_languages() -> &'static [Language] {
+        SUPPORTED_LANGUAGES
     }
 
     /// Parse code (lite version returns code directly, not a tree)
@@ -130,425 +586,1087 @@ impl Parser {
         })
     }
 
-    /// Extract semantic code chunks from code
+    /// Extract semantic code chunks using the patterns of the tree's language
+    ///
+    /// Chunks are in source order: sorted by `start_byte`, then by
+    /// `end_byte` descending, so a container precedes the members and nested
+    /// declarations it starts with. Chunks with the same span keep the order
+    /// in which they were found.
     pub fn extract_chunks(&self, tree: &LiteTree, code: &str) -> Vec<CodeChunk> {
-        let mut chunks = Vec::new();
+        self.extract_chunks_bounded(code, tree.language, usize::MAX).0
+    }
 
-        match tree.language {
-            Language::JavaScript | Language::TypeScript => {
-                self.extract_js_chunks(code, &mut chunks);
-            }
-            Language::Python => {
-                self.extract_python_chunks(code, &mut chunks);
-            }
-            Language::Rust => {
-                self.extract_rust_chunks(code, &mut chunks);
-            }
-            Language::Go => {
-                self.extract_go_chunks(code, &mut chunks);
-            }
-            Language::Java => {
-                self.extract_java_chunks(code, &mut chunks);
-            }
-            Language::C => {
-                self.extract_c_chunks(code, &mut chunks);
-            }
-            Language::Cpp => {
-                self.extract_cpp_chunks(code, &mut chunks);
-            }
-        }
+    /// Extract chunks until their combined code reaches `max_total_bytes`
+    ///
+    /// Chunks are produced in the same order as [`Parser::extract_chunks`] and
+    /// never cut: the chunk that crosses the budget is emitted whole, and
+    /// extraction stops after it. The flag is `true` if chunks were left out.
+    /// Module body chunks are only emitted when nothing was left out, since
+    /// they would otherwise cover the skipped declarations.
+    pub fn extract_chunks_bounded(
+        &self,
+        code: &str,
+        language: Language,
+        max_total_bytes: usize,
+    ) -> (Vec<CodeChunk>, bool) {
+        self.extract_chunks_skipping(code, language, max_total_bytes, &[])
+    }
 
-        chunks
+    /// Extract chunks, skipping declarations that start in an ignored range
+    ///
+    /// `ignore_ranges` are half-open byte ranges `(start, end)` and may
+    /// overlap or touch. Members and variables starting in them are skipped
+    /// too, and with [`ParserConfig::emit_module_body`] the ranges count as
+    /// covered, so ignored code never shows up in a chunk of its own.
+    /// Declarations starting outside every range are kept whole, even if they
+    /// extend into one.
+    pub fn extract_chunks_ignoring(
+        &self,
+        tree: &LiteTree,
+        code: &str,
+        ignore_ranges: &[(usize, usize)],
+    ) -> Vec<CodeChunk> {
+        self.extract_chunks_skipping(code, tree.language, usize::MAX, ignore_ranges).0
     }
 
-    fn extract_js_chunks(&self, code: &str, chunks: &mut Vec<CodeChunk>) {
-        // Extract functions
-        for cap in self.js_function_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "function_declaration".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
+    fn extract_chunks_skipping(
+        &self,
+        code: &str,
+        language: Language,
+        max_total_bytes: usize,
+        ignore_ranges: &[(usize, usize)],
+    ) -> (Vec<CodeChunk>, bool) {
+        let ignored = merge_ranges(ignore_ranges);
+        let kept = |chunk: &CodeChunk| !in_ranges(&ignored, chunk.start_byte);
+        let mut chunks = Vec::new();
+        let mut total = 0;
+
+        if let Some(max_line) = self.config.max_line_length.filter(|&n| n > 0) {
+            if code.lines().any(|line| line.len() > max_line) {
+                let windows = Self::byte_window_chunks(code, max_line, language);
+                let truncated =
+                    push_within_budget(&mut chunks, windows, &mut total, max_total_bytes);
+                for chunk in &mut chunks {
+                    chunk.language = language;
                 }
+                return (chunks, truncated);
             }
         }
 
-        // Extract classes
-        for cap in self.js_class_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "class_declaration".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
+        let patterns = patterns_for(language);
+        let mut truncated = false;
+
+        'declarations: for declaration in &patterns.declarations {
+            for cap in declaration.regex.captures_iter(code) {
+                let Some(m) = cap.get(0) else { continue };
+                let start = declaration_start(&m);
+                if in_ranges(&ignored, start) {
+                    continue;
+                }
+                let Some(code_text) =
+                    self.extract_declaration(code, start, language, declaration.block)
+                else {
+                    continue;
+                };
+
+                let mut chunk = Self::make_chunk(
+                    code,
+                    start,
+                    &code_text,
+                    declaration.node_type,
+                    None,
+                    cap.iter().skip(1).flatten().next().map(|n| n.as_str()),
+                );
+                if self.config.debug_spans {
+                    chunk.match_span = Some((start, m.end()));
+                }
+                let members = match (declaration.has_members, &patterns.member) {
+                    (true, Some(member)) => self.extract_members(code, language, &chunk, member),
+                    _ if declaration.has_signatures => {
+                        Self::signature_members(code, language, &chunk)
+                    }
+                    _ => Vec::new(),
+                };
+                let members = members.into_iter().filter(kept);
+
+                let unit = std::iter::once(chunk).chain(members);
+                if push_within_budget(&mut chunks, unit, &mut total, max_total_bytes) {
+                    truncated = true;
+                    break 'declarations;
                 }
             }
         }
-    }
 
-    fn extract_python_chunks(&self, code: &str, chunks: &mut Vec<CodeChunk>) {
-        // Extract functions
-        for cap in self.py_function_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_python_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "function_definition".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
-                }
+        let javascript = matches!(language, Language::JavaScript | Language::TypeScript);
+        if self.config.emit_variables && javascript && !truncated {
+            let variables = Self::variable_chunks(code, language).into_iter().filter(kept);
+            truncated = push_within_budget(&mut chunks, variables, &mut total, max_total_bytes);
+        }
+
+        Self::parent_to_namespaces(&mut chunks);
+
+        let decorators = Self::decorator_spans(code, language);
+        if !decorators.is_empty() {
+            for chunk in &mut chunks {
+                chunk.decorators = Self::decorators_before(code, &decorators, chunk.start_byte);
             }
         }
 
-        // Extract classes
-        for cap in self.py_class_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_python_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "class_definition".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
-                }
+        if self.config.emit_regions && !truncated && language != Language::Html {
+            let regions = Self::region_chunks(code);
+            truncated = push_within_budget(&mut chunks, regions, &mut total, max_total_bytes);
+        }
+
+        match self.config.fallback_window_lines.filter(|&n| n > 0) {
+            Some(window_lines) if chunks.is_empty() => {
+                let windows = Self::window_chunks(code, window_lines);
+                truncated = push_within_budget(&mut chunks, windows, &mut total, max_total_bytes);
+            }
+            _ if self.config.emit_module_body && !truncated => {
+                let covered: Vec<(usize, usize)> = chunks
+                    .iter()
+                    .filter(|c| c.node_type != "region")
+                    .map(|c| (c.start_byte, c.end_byte))
+                    .chain(decorators)
+                    .chain(ignored)
+                    .collect();
+                let body = Self::module_body_chunks(code, covered);
+                truncated = push_within_budget(&mut chunks, body, &mut total, max_total_bytes);
             }
+            _ => {}
         }
+
+        chunks.sort_by_key(|c| (c.start_byte, Reverse(c.end_byte)));
+        for chunk in &mut chunks {
+            chunk.language = language;
+        }
+        (chunks, truncated)
+    }
+
+    /// Set the parent of chunks lying inside a `namespace` or `module` chunk
+    /// to the innermost one's type, unless they already have a parent
+    fn parent_to_namespaces(chunks: &mut [CodeChunk]) {
+        let containers: Vec<(usize, usize, String)> = chunks
+            .iter()
+            .filter(|c| matches!(c.node_type.as_str(), "namespace" | "module"))
+            .map(|c| (c.start_byte, c.end_byte, c.node_type.clone()))
+            .collect();
+        if containers.is_empty() {
+            return;
+        }
+
+        for chunk in chunks.iter_mut().filter(|c| c.parent_type.is_none()) {
+            let (start, end) = (chunk.start_byte, chunk.end_byte);
+            chunk.parent_type = containers
+                .iter()
+                .filter(|&&(s, e, _)| s <= start && end <= e && (s, e) != (start, end))
+                .min_by_key(|&&(s, e, _)| e - s)
+                .map(|(_, _, node_type)| node_type.clone());
+        }
+    }
+
+    /// Count declaration matches without extracting their bodies
+    ///
+    /// Runs only the language's declaration patterns, so it is much cheaper
+    /// than [`Parser::extract_chunks`]. Only top-level declarations are
+    /// counted, plus nested ones a pattern matches directly, such as Rust
+    /// `fn`s in an `impl`. The methods of classes and the signatures of
+    /// interfaces, which [`Parser::extract_chunks`] emits as separate chunks,
+    /// are not, so the count falls short of the number of chunks for code
+    /// containing them. It may also overcount: matches whose block can't be
+    /// extracted (for example inside a string, or with unbalanced braces)
+    /// are dropped by full extraction but counted here.
+    pub fn count_declarations(&self, code: &str, language: Language) -> usize {
+        patterns_for(language)
+            .declarations
+            .iter()
+            .map(|declaration| declaration.regex.find_iter(code).count())
+            .sum()
     }
 
-    fn extract_rust_chunks(&self, code: &str, chunks: &mut Vec<CodeChunk>) {
-        // Extract functions
-        for cap in self.rust_function_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "function_item".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
+    /// Emit a chunk for each declarator of the top-level variable statements
+    ///
+    /// A statement ends at a `;`, or at a line break where neither the line
+    /// nor the next one continues the expression. Declarators are split at
+    /// top-level commas, skipping those in brackets, braces, parentheses and,
+    /// in TypeScript, type arguments such as `Map<K, V>`. The first chunk
+    /// starts at the statement's keyword and the last includes its `;`, so
+    /// `const a = 1, b = 2;` gives `const a = 1` and `b = 2;`. Chunks are
+    /// named after the declared identifier; destructuring declarators are
+    /// unnamed. TypeScript `const enum`s are left out.
+    fn variable_chunks(code: &str, language: Language) -> Vec<CodeChunk> {
+        // Keep statements whose keyword is code at nesting depth 0
+        let mut starts = VARIABLE
+            .find_iter(code)
+            .map(|m| (declaration_start(&m), m.end()))
+            .peekable();
+        let mut statements = Vec::new();
+        let mut depth = 0usize;
+        for (pos, byte) in CodeBytes::new(code, language) {
+            while starts.next_if(|&(start, _)| start < pos).is_some() {}
+            if let Some(statement) = starts.next_if(|&(start, _)| start == pos) {
+                if depth == 0 {
+                    statements.push(statement);
                 }
             }
+            match byte {
+                b'(' | b'[' | b'{' => depth += 1,
+                b')' | b']' | b'}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
         }
 
-        // Extract structs
-        for cap in self.rust_struct_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "struct_item".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
+        let mut chunks = Vec::new();
+        for (start, body) in statements {
+            if language == Language::TypeScript && code[body..].starts_with("enum ") {
+                continue;
+            }
+            let (declarators, end) = Self::split_declarators(code, body, language);
+            let last = declarators.len().saturating_sub(1);
+            for (i, (from, to)) in declarators.into_iter().enumerate() {
+                let text = code[from..to].trim();
+                if text.is_empty() {
+                    continue;
                 }
+                let from = if i == 0 { start } else { to - code[from..to].trim_start().len() };
+                let to = if i == last { end } else { from + code[from..to].trim_end().len() };
+                let name = DECLARATOR_NAME.captures(text).and_then(|cap| cap.get(1));
+                chunks.push(Self::make_chunk(
+                    code,
+                    from,
+                    code[from..to].trim_end(),
+                    "variable_declaration",
+                    None,
+                    name.map(|n| n.as_str()),
+                ));
             }
         }
+        chunks
+    }
 
-        // Extract impl blocks
-        for cap in self.rust_impl_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "impl_item".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
+    /// Split the declarators of a variable statement whose first declarator
+    /// starts at `body`
+    ///
+    /// Returns the byte range of each declarator and the end of the
+    /// statement, just past its `;` if it has one.
+    fn split_declarators(
+        code: &str,
+        body: usize,
+        language: Language,
+    ) -> (Vec<(usize, usize)>, usize) {
+        let mut declarators = Vec::new();
+        let mut from = body;
+        let mut depth = 0usize;
+        let mut type_args = 0usize;
+        let mut prev = b'=';
+        let mut next_pos = body;
+        let mut end = code.len();
+
+        for (pos, byte) in CodeBytes::new(&code[body..], language) {
+            let pos = body + pos;
+            if ends_string_literal(&code[next_pos..pos]) {
+                prev = b'"';
+            }
+            next_pos = pos + 1;
+            let top_level = depth == 0 && type_args == 0;
+            match byte {
+                b'(' | b'[' | b'{' => depth += 1,
+                b')' | b']' | b'}' if depth == 0 => {
+                    end = pos;
+                    break;
+                }
+                b')' | b']' | b'}' => depth -= 1,
+                b'<' if language == Language::TypeScript
+                    && (prev.is_ascii_alphanumeric() || prev == b'_')
+                    && code.as_bytes()[pos - 1] == prev =>
+                {
+                    type_args += 1
+                }
+                b'>' if type_args > 0 && prev != b'=' => type_args -= 1,
+                b',' if top_level => {
+                    declarators.push((from, pos));
+                    from = pos + 1;
+                }
+                b';' if top_level => {
+                    end = pos + 1;
+                    break;
                 }
+                b'\n' if top_level && !CONTINUING_LINE_ENDS.contains(&prev) => {
+                    let next = code[pos..].trim_start().bytes().next();
+                    if !matches!(next, Some(b) if CONTINUING_LINE_STARTS.contains(&b)) {
+                        end = pos;
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            if !byte.is_ascii_whitespace() {
+                prev = byte;
             }
         }
+
+        let to = if code[..end].ends_with(';') { end - 1 } else { end };
+        declarators.push((from, to.max(from)));
+        (declarators, code[..end].trim_end().len().max(from))
     }
 
-    fn extract_go_chunks(&self, code: &str, chunks: &mut Vec<CodeChunk>) {
-        // Extract functions
-        for cap in self.go_function_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "function_declaration".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
+    /// Emit a chunk for each non-blank gap between covered byte ranges
+    fn module_body_chunks(code: &str, mut covered: Vec<(usize, usize)>) -> Vec<CodeChunk> {
+        covered.sort_unstable();
+        covered.push((code.len(), code.len()));
+
+        let mut chunks = Vec::new();
+        let mut gap_start = 0;
+        for (start, end) in covered {
+            if start > gap_start {
+                let gap = &code[gap_start..start];
+                let text = gap.trim();
+                if !text.is_empty() {
+                    let offset = gap_start + (gap.len() - gap.trim_start().len());
+                    chunks.push(Self::make_chunk(code, offset, text, "module_body", None, None));
                 }
             }
+            gap_start = gap_start.max(end);
         }
 
-        // Extract structs
-        for cap in self.go_struct_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "type_declaration".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
+        chunks
+    }
+
+    /// Emit a chunk for each region delimited by marker comments
+    ///
+    /// A `#region` chunk runs from its start marker to the end of the matching
+    /// `#endregion` line; regions nested in another get `parent_type`
+    /// `"region"`, and unclosed ones are dropped. A `// MARK:` chunk runs to
+    /// the last non-blank line before the next `MARK:` or the end of the file.
+    /// Chunks are named after the text following the marker, if any.
+    fn region_chunks(code: &str) -> Vec<CodeChunk> {
+        let mut chunks = Vec::new();
+        let mut open: Vec<(usize, Option<&str>)> = Vec::new();
+        let mut mark: Option<(usize, Option<&str>)> = None;
+        let region = |start: usize, end: usize, name: Option<&str>, parent: bool| {
+            let text = code[start..end].trim_end();
+            let parent = parent.then_some("region");
+            Self::make_chunk(code, start, text, "region", parent, name)
+        };
+
+        let mut offset = 0;
+        for line in code.split_inclusive('\n') {
+            let start = offset + (line.len() - line.trim_start().len());
+            offset += line.len();
+
+            if let Some(cap) = REGION_START.captures(line) {
+                open.push((start, marker_name(&cap)));
+            } else if REGION_END.is_match(line) {
+                if let Some((region_start, name)) = open.pop() {
+                    chunks.push(region(region_start, offset, name, !open.is_empty()));
+                }
+            } else if let Some(cap) = MARK.captures(line) {
+                if let Some((mark_start, name)) = mark.take() {
+                    chunks.push(region(mark_start, start, name, false));
                 }
+                mark = Some((start, marker_name(&cap)));
             }
         }
+        if let Some((mark_start, name)) = mark {
+            chunks.push(region(mark_start, code.len(), name, false));
+        }
+
+        chunks.sort_by_key(|c| c.start_byte);
+        chunks
     }
 
-    fn extract_java_chunks(&self, code: &str, chunks: &mut Vec<CodeChunk>) {
-        // Extract classes
-        for cap in self.java_class_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "class_declaration".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
+    /// Find the byte ranges of all decorators, including their argument lists
+    ///
+    /// Arguments may span several lines; their parentheses are matched while
+    /// ignoring string literals and comments.
+    fn decorator_spans(code: &str, language: Language) -> Vec<(usize, usize)> {
+        DECORATOR
+            .find_iter(code)
+            .map(|m| {
+                let start = declaration_start(&m);
+                let mut end = m.end();
+                if code[end..].starts_with('(') {
+                    let mut depth = 0;
+                    for (pos, byte) in CodeBytes::new(&code[end..], language) {
+                        match byte {
+                            b'(' => depth += 1,
+                            b')' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    end += pos + 1;
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
                 }
+                (start, end)
+            })
+            .collect()
+    }
+
+    /// Collect the decorators separated from `start` only by whitespace
+    fn decorators_before(code: &str, spans: &[(usize, usize)], start: usize) -> Vec<String> {
+        let mut cursor = start;
+        let mut decorators = Vec::new();
+
+        for &(span_start, span_end) in spans.iter().rev().filter(|(_, end)| *end <= start) {
+            if !code[span_end..cursor].trim().is_empty() {
+                break;
             }
+            decorators.push(code[span_start..span_end].to_string());
+            cursor = span_start;
         }
 
-        // Extract methods
-        for cap in self.java_method_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "method_declaration".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
-                }
+        decorators.reverse();
+        decorators
+    }
+
+    /// Split code into chunks of `window_lines` lines, skipping blank windows
+    fn window_chunks(code: &str, window_lines: usize) -> Vec<CodeChunk> {
+        let mut chunks = Vec::new();
+        let mut window_start = 0;
+        let mut lines_in_window = 0;
+
+        for (idx, _) in code.match_indices('\n').chain(std::iter::once((code.len(), ""))) {
+            lines_in_window += 1;
+            if lines_in_window < window_lines && idx < code.len() {
+                continue;
+            }
+
+            let text = code[window_start..idx].trim_end();
+            if !text.trim().is_empty() {
+                chunks.push(Self::make_chunk(code, window_start, text, "window", None, None));
             }
+            window_start = (idx + 1).min(code.len());
+            lines_in_window = 0;
         }
+
+        chunks
     }
 
-    fn extract_c_chunks(&self, code: &str, chunks: &mut Vec<CodeChunk>) {
-        // Extract functions
-        for cap in self.c_function_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "function_definition".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
+    /// Split code into chunks of at most `window` bytes by offset alone
+    ///
+    /// Each chunk ends after the last top-level `;` or `}` inside its window,
+    /// or at the last character boundary inside it when there is none; a
+    /// character wider than the window gets a chunk of its own. Line numbers
+    /// are not computed, keeping this linear in the size of the code.
+    fn byte_window_chunks(code: &str, window: usize, language: Language) -> Vec<CodeChunk> {
+        let mut chunks = Vec::new();
+        let mut push = |start: usize, end: usize| {
+            let text = &code[start..end];
+            if !text.trim().is_empty() {
+                chunks.push(CodeChunk {
+                    code: text.to_string(),
+                    node_type: "byte_window".to_string(),
+                    start_byte: start,
+                    end_byte: end,
+                    start_line: UNKNOWN_LINE,
+                    end_line: UNKNOWN_LINE,
+                    start_column: UNKNOWN_LINE,
+                    end_column: UNKNOWN_LINE,
+                    ..Default::default()
+                });
+            }
+        };
+
+        let mut start = 0;
+        let mut cut = None;
+        let mut depth = 0i32;
+        for (pos, byte) in CodeBytes::new(code, language) {
+            match byte {
+                b'{' | b'(' | b'[' => depth += 1,
+                b'}' | b')' | b']' => depth -= 1,
+                _ => {}
+            }
+            if depth <= 0 && matches!(byte, b';' | b'}') {
+                cut = Some(pos + 1);
+            }
+            if pos < start {
+                continue;
+            }
+            if pos + 1 - start >= window {
+                let mut end = cut.filter(|&c| c > start).unwrap_or(pos + 1);
+                while !code.is_char_boundary(end) {
+                    end -= 1;
+                }
+                if end == start {
+                    end = pos + 1;
+                    while !code.is_char_boundary(end) {
+                        end += 1;
+                    }
                 }
+                push(start, end);
+                start = end;
+                cut = None;
             }
         }
+        if start < code.len() {
+            push(start, code.len());
+        }
+
+        chunks
     }
 
-    fn extract_cpp_chunks(&self, code: &str, chunks: &mut Vec<CodeChunk>) {
-        // Extract classes
-        for cap in self.cpp_class_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "class_specifier".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
+    /// Extract members declared directly in a container block's body
+    fn extract_members(
+        &self,
+        code: &str,
+        language: Language,
+        container: &CodeChunk,
+        member: &DeclarationPattern,
+    ) -> Vec<CodeChunk> {
+        let mut members = Vec::new();
+        let body_start = match container.code.find('{') {
+            Some(pos) => pos + 1,
+            None => return members,
+        };
+        let body = &container.code[body_start..];
+
+        for cap in member.regex.captures_iter(body) {
+            let (Some(m), Some(name)) = (cap.get(0), cap.get(1)) else {
+                continue;
+            };
+            if JS_CONTROL_KEYWORDS.contains(&name.as_str()) {
+                continue;
+            }
+
+            // Only direct members: anything nested deeper belongs to a member body
+            let depth = CodeBytes::new(&body[..m.start()], language).fold(0i32, |depth, (_, b)| {
+                match b {
+                    b'{' => depth + 1,
+                    b'}' => depth - 1,
+                    _ => depth,
+                }
+            });
+            if depth != 0 {
+                continue;
+            }
+
+            let offset = container.start_byte + body_start;
+            let start = offset + declaration_start(&m);
+            if let Some(code_text) = self.extract_declaration(code, start, language, member.block) {
+                let mut chunk = Self::make_chunk(
+                    code,
+                    start,
+                    &code_text,
+                    member.node_type,
+                    Some(&container.node_type),
+                    Some(name.as_str()),
+                );
+                if self.config.debug_spans {
+                    chunk.match_span = Some((start, offset + m.end()));
                 }
+                members.push(chunk);
             }
         }
 
-        // Extract functions (C-style)
-        for cap in self.c_function_regex.captures_iter(code) {
-            if let Some(m) = cap.get(0) {
-                let start = m.start();
-                if let Some(code_text) = self.extract_block(code, start) {
-                    chunks.push(CodeChunk {
-                        code: code_text.clone(),
-                        node_type: "function_definition".to_string(),
-                        start_byte: start,
-                        end_byte: start + code_text.len(),
-                        start_line: code[..start].lines().count(),
-                        end_line: code[..start + code_text.len()].lines().count(),
-                        parent_type: None,
-                    });
+        members
+    }
+
+    /// Extract the member signatures of an interface body
+    ///
+    /// Members end at a `;` or `,` outside brackets, braces, parentheses and
+    /// type arguments, or at a line break where neither the line nor the next
+    /// one continues the type (e.g. a union written one `| variant` per line),
+    /// so object-typed members can span several lines. Named members become
+    /// `"method_signature"` chunks when the name is followed by `(` or `<` and
+    /// `"property_signature"` chunks otherwise; index, call and construct
+    /// signatures are skipped.
+    fn signature_members(code: &str, language: Language, container: &CodeChunk) -> Vec<CodeChunk> {
+        let mut members = Vec::new();
+        let Some((open, close)) = block_end(&container.code, language) else {
+            return members;
+        };
+        let body = &container.code[open + 1..close];
+        let mut push = |from: usize, to: usize| {
+            let text = body[from..to].trim_end();
+            let Some(cap) = SIGNATURE_NAME.captures(text) else {
+                return;
+            };
+            let node_type = match cap.get(2) {
+                Some(_) => "method_signature",
+                None => "property_signature",
+            };
+            members.push(Self::make_chunk(
+                code,
+                container.start_byte + open + 1 + from,
+                text,
+                node_type,
+                Some(&container.node_type),
+                cap.get(1).map(|n| n.as_str()),
+            ));
+        };
+
+        let mut start = None;
+        let (mut depth, mut type_args) = (0usize, 0usize);
+        let mut prev = b';';
+        let mut next_pos = 0;
+        for (pos, byte) in CodeBytes::new(body, language) {
+            if ends_string_literal(&body[next_pos..pos]) {
+                prev = b'"';
+            }
+            next_pos = pos + 1;
+            let Some(from) = start.or_else(|| {
+                let separator = byte.is_ascii_whitespace() || matches!(byte, b';' | b',');
+                (!separator).then_some(pos)
+            }) else {
+                continue;
+            };
+            start = Some(from);
+
+            let top_level = depth == 0 && type_args == 0;
+            let mut end = None;
+            match byte {
+                b'(' | b'[' | b'{' => depth += 1,
+                b')' | b']' | b'}' => depth = depth.saturating_sub(1),
+                b'<' if (prev.is_ascii_alphanumeric() || prev == b'_')
+                    && body.as_bytes()[pos - 1] == prev =>
+                {
+                    type_args += 1
+                }
+                b'>' if type_args > 0 && prev != b'=' => type_args -= 1,
+                b';' if top_level => end = Some(pos + 1),
+                b',' if top_level => end = Some(pos),
+                b'\n' if top_level && !CONTINUING_TYPE_ENDS.contains(&prev) => {
+                    let next = body[pos..].trim_start().bytes().next();
+                    if !matches!(next, Some(b) if CONTINUING_TYPE_STARTS.contains(&b)) {
+                        end = Some(pos);
+                    }
                 }
+                _ => {}
+            }
+            if !byte.is_ascii_whitespace() {
+                prev = byte;
             }
+            if let Some(to) = end {
+                push(from, to);
+                start = None;
+            }
+        }
+        if let Some(from) = start {
+            push(from, body.len());
         }
+
+        members
     }
 
-    /// Extract a Python code block by indentation
-    fn extract_python_block(&self, code: &str, start: usize) -> Option<String> {
-        let lines: Vec<&str> = code.lines().collect();
-        let start_line_idx = code[..start].lines().count();
+    /// Extract the block of a declaration starting at `start`
+    fn extract_declaration(
+        &self,
+        code: &str,
+        start: usize,
+        language: Language,
+        block: BlockStyle,
+    ) -> Option<String> {
+        match block {
+            BlockStyle::Braces => self.extract_block(code, start, language),
+            BlockStyle::Indentation => self.extract_python_block(code, start),
+        }
+    }
 
-        if start_line_idx >= lines.len() {
-            return None;
+    /// Build a chunk for a block extracted at `start`
+    fn make_chunk(
+        code: &str,
+        start: usize,
+        code_text: &str,
+        node_type: &str,
+        parent_type: Option<&str>,
+        name: Option<&str>,
+    ) -> CodeChunk {
+        let end = start + code_text.len();
+        let (start_line, start_column) = line_column(code, start);
+        let (end_line, end_column) = line_column(code, end);
+        CodeChunk {
+            code: code_text.to_string(),
+            node_type: node_type.to_string(),
+            language: Language::default(),
+            start_byte: start,
+            end_byte: end,
+            start_line,
+            end_line,
+            start_column,
+            end_column,
+            parent_type: parent_type.map(str::to_string),
+            name: name.map(str::to_string),
+            decorators: Vec::new(),
+            dirty: false,
+            match_span: None,
         }
+    }
 
-        // Get the base indentation from the def/class line
-        let base_line = lines[start_line_idx];
+    /// Extract a Python code block by indentation
+    ///
+    /// The block runs from `start` to the end of the last line indented deeper
+    /// than the line containing `start`.
+    fn extract_python_block(&self, code: &str, start: usize) -> Option<String> {
+        let line_start = code[..start].rfind('\n').map_or(0, |i| i + 1);
+        let base_line = code[line_start..].split('\n').next()?;
         let base_indent = base_line.len() - base_line.trim_start().len();
 
-        let mut end_line_idx = start_line_idx + 1;
+        let mut pos = line_start + base_line.len();
+        let mut end = line_start + base_line.trim_end().len();
 
         // Find the end of the indented block
-        while end_line_idx < lines.len() {
-            let line = lines[end_line_idx];
-            let trimmed = line.trim();
+        while pos < code.len() {
+            let next_start = pos + 1;
+            let line = code[next_start..].split('\n').next().unwrap_or("");
+            pos = next_start + line.len();
 
             // Skip empty lines and comments
+            let trimmed = line.trim();
             if trimmed.is_empty() || trimmed.starts_with('#') {
-                end_line_idx += 1;
                 continue;
             }
 
-            let line_indent = line.len() - line.trim_start().len();
-
             // If indentation is less than or equal to base, we've reached the end
+            let line_indent = line.len() - line.trim_start().len();
             if line_indent <= base_indent {
                 break;
             }
 
-            end_line_idx += 1;
+            end = next_start + line.trim_end().len();
         }
 
-        let block_lines = &lines[start_line_idx..end_line_idx];
-        Some(block_lines.join("\n"))
+        Some(code[start..end].to_string())
     }
 
     /// Extract a code block by finding matching braces
-    fn extract_block(&self, code: &str, start: usize) -> Option<String> {
-        let bytes = code.as_bytes();
+    ///
+    /// Braces inside string literals and comments are ignored.
+    fn extract_block(&self, code: &str, start: usize, language: Language) -> Option<String> {
+        let (_, close) = block_end(&code[start..], language)?;
+        Some(code[start..=start + close].to_string())
+    }
+
+    /// Extract chunks and check syntax in one call
+    ///
+    /// This is the preferred entry point when both are needed: it works on
+    /// `code` directly instead of copying it into a [`LiteTree`], and scans the
+    /// brackets once for both the validity flag and the error.
+    pub fn analyze(&self, code: &str, language: Language) -> FileAnalysis {
+        let first_error = self.validate_syntax_detailed(code, language).err();
+        let (chunks, _) = self.extract_chunks_bounded(code, language, usize::MAX);
+        FileAnalysis {
+            chunks,
+            is_valid: first_error.is_none(),
+            first_error,
+        }
+    }
+
+    /// Validate syntax by checking for balanced braces/parens/brackets
+    pub fn validate_syntax(&self, code: &str, language: Language) -> bool {
+        self.validate_syntax_detailed(code, language).is_ok()
+    }
+
+    /// Validate syntax, reporting the first problem found
+    ///
+    /// Brackets inside strings and comments are ignored. A bracket error
+    /// before the start of an unterminated string, template or comment is
+    /// reported first; otherwise the unterminated literal is, and finally the
+    /// innermost bracket left open.
+    pub fn validate_syntax_detailed(
+        &self,
+        code: &str,
+        language: Language,
+    ) -> std::result::Result<(), SyntaxError> {
+        let mut scanner = CodeBytes::new(code, language);
+        let mut open: Vec<(usize, u8)> = Vec::new();
+        let mut bracket_error = None;
+
+        for (pos, byte) in scanner.by_ref() {
+            let expected = match byte {
+                b'(' | b'{' | b'[' => {
+                    open.push((pos, byte));
+                    continue;
+                }
+                b')' => b'(',
+                b'}' => b'{',
+                b']' => b'[',
+                _ => continue,
+            };
+            let kind = match open.pop() {
+                Some((_, opener)) if opener == expected => continue,
+                Some((_, opener)) => SyntaxErrorKind::MismatchedClose {
+                    expected: closing_bracket(opener),
+                    found: byte as char,
+                },
+                None => SyntaxErrorKind::UnexpectedClose {
+                    found: byte as char,
+                },
+            };
+            bracket_error = Some(SyntaxError::at(code, pos, kind));
+            break;
+        }
 
-        // Find the opening brace
-        let mut brace_start = start;
-        while brace_start < bytes.len() && bytes[brace_start] != b'{' {
-            brace_start += 1;
+        let unterminated = scanner.unterminated();
+        match (bracket_error, unterminated) {
+            (Some(error), Some((start, _))) if error.byte < start => Err(error),
+            (_, Some((start, delimiter))) => Err(SyntaxError::at(
+                code,
+                start,
+                SyntaxErrorKind::UnterminatedLiteral { delimiter },
+            )),
+            (Some(error), None) => Err(error),
+            (None, None) => match open.pop() {
+                Some((pos, opener)) => Err(SyntaxError::at(
+                    code,
+                    pos,
+                    SyntaxErrorKind::Unclosed {
+                        delimiter: opener as char,
+                    },
+                )),
+                None => Ok(()),
+            },
         }
+    }
+
+    /// Widen a chunk of `code` so it starts and ends on whole statements
+    ///
+    /// The start moves back to the beginning of the statement it falls in,
+    /// including the indentation before it when the statement begins its
+    /// line, and the end moves forward to the `;` or block-closing `}` that
+    /// ends the statement it falls in. Python and markup chunks are widened to
+    /// whole lines. The chunk never shrinks; its code, byte range, lines and
+    /// columns are updated and all other fields kept.
+    pub fn snap_to_statements(code: &str, chunk: &CodeChunk, language: Language) -> CodeChunk {
+        let from = chunk.start_byte.min(code.len());
+        let to = chunk.end_byte.clamp(from, code.len());
+        let (start, end) = match language {
+            Language::Python | Language::Html => {
+                let end = code[to..].find('\n').map_or(code.len(), |i| to + i);
+                (from, end)
+            }
+            _ => statement_bounds(code, from, to, language),
+        };
+
+        let line_start = code[..start].rfind('\n').map_or(0, |i| i + 1);
+        let start = if code[line_start..start].trim().is_empty() {
+            line_start
+        } else {
+            start
+        };
+
+        let (start_line, start_column) = line_column(code, start);
+        let (end_line, end_column) = line_column(code, end);
+        CodeChunk {
+            code: code[start..end].to_string(),
+            start_byte: start,
+            end_byte: end,
+            start_line,
+            end_line,
+            start_column,
+            end_column,
+            ..chunk.clone()
+        }
+    }
 
-        if brace_start >= bytes.len() {
-            return None;
+    /// Find a chunk by its name, qualified with the enclosing class for methods
+    ///
+    /// `ClassName.methodName` resolves to a method chunk lying inside a class
+    /// chunk named `ClassName`; a bare name resolves to a top-level chunk.
+    pub fn find_by_qualified_name<'a>(
+        chunks: &'a [CodeChunk],
+        qualified: &str,
+    ) -> Option<&'a CodeChunk> {
+        match qualified.rsplit_once('.') {
+            Some((class_name, member)) => chunks
+                .iter()
+                .filter(|c| {
+                    c.node_type == "class_declaration" && c.name.as_deref() == Some(class_name)
+                })
+                .find_map(|class| {
+                    chunks.iter().find(|c| {
+                        c.parent_type.as_deref() == Some("class_declaration")
+                            && c.name.as_deref() == Some(member)
+                            && c.start_byte >= class.start_byte
+                            && c.end_byte <= class.end_byte
+                    })
+                }),
+            None => chunks
+                .iter()
+                .find(|c| c.parent_type.is_none() && c.name.as_deref() == Some(qualified)),
         }
+    }
 
-        // Count braces to find matching closing brace
-        let mut depth = 0;
-        let mut pos = brace_start;
+    /// Find the innermost declaration containing a 1-based line number
+    ///
+    /// Only chunks whose node type is one of their language's
+    /// [`Language::supported_node_types`] count, so structural chunks such as
+    /// regions or module bodies never match. Returns `None` for lines outside
+    /// every declaration, and for line 0.
+    pub fn declaration_at_line(chunks: &[CodeChunk], line: usize) -> Option<&CodeChunk> {
+        let line = line.checked_sub(1)?;
+        chunks
+            .iter()
+            .filter(|c| c.language.supported_node_types().contains(&c.node_type.as_str()))
+            .filter(|c| c.start_line <= line && line <= c.end_line)
+            .min_by_key(|c| c.end_byte - c.start_byte)
+    }
 
-        while pos < bytes.len() {
-            match bytes[pos] {
-                b'{' => depth += 1,
-                b'}' => {
-                    depth -= 1;
-                    if depth == 0 {
-                        // Found matching brace
-                        return Some(code[start..=pos].to_string());
+    /// Classify the chunks of two versions of a file as added, removed,
+    /// modified or unchanged
+    ///
+    /// Named chunks are paired by `(node_type, name, parent_type)`, repeated
+    /// keys in source order; a pair is unchanged when it is
+    /// [`CodeChunk::semantically_equal`] and modified otherwise. Unnamed
+    /// chunks have nothing stable to pair on, so they only match a chunk of
+    /// the same type with the same [`CodeChunk::content_hash`].
+    pub fn diff_chunks(old: &[CodeChunk], new: &[CodeChunk]) -> ChunkDiff {
+        let mut paired = vec![false; new.len()];
+        let mut changes = Vec::new();
+
+        for (o, chunk) in old.iter().enumerate() {
+            let found = new.iter().enumerate().position(|(n, candidate)| {
+                !paired[n]
+                    && candidate.node_type == chunk.node_type
+                    && match chunk.name {
+                        Some(_) => {
+                            candidate.name == chunk.name
+                                && candidate.parent_type == chunk.parent_type
+                        }
+                        None => {
+                            candidate.name.is_none()
+                                && candidate.content_hash() == chunk.content_hash()
+                        }
+                    }
+            });
+            changes.push(match found {
+                Some(n) => {
+                    paired[n] = true;
+                    if chunk.semantically_equal(&new[n]) {
+                        ChunkChange::Unchanged { old: o, new: n }
+                    } else {
+                        ChunkChange::Modified { old: o, new: n }
                     }
                 }
-                _ => {}
-            }
-            pos += 1;
+                None => ChunkChange::Removed { old: o },
+            });
         }
 
-        None
+        changes.extend(
+            paired
+                .iter()
+                .enumerate()
+                .filter(|(_, &p)| !p)
+                .map(|(n, _)| ChunkChange::Added { new: n }),
+        );
+        ChunkDiff { changes }
     }
 
-    /// Validate syntax by checking for balanced braces/parens/brackets
-    pub fn validate_syntax(&self, code: &str, _language: Language) -> bool {
-        let mut paren_depth = 0;
-        let mut brace_depth = 0;
-        let mut bracket_depth = 0;
-
-        for ch in code.chars() {
-            match ch {
-                '(' => paren_depth += 1,
-                ')' => paren_depth -= 1,
-                '{' => brace_depth += 1,
-                '}' => brace_depth -= 1,
-                '[' => bracket_depth += 1,
-                ']' => bracket_depth -= 1,
-                _ => {}
-            }
-
-            // Check for negative depth (closing before opening)
-            if paren_depth < 0 || brace_depth < 0 || bracket_depth < 0 {
-                return false;
+    /// Find pairs of chunks whose code is at least `similarity_threshold`
+    /// similar, such as copy-pasted functions
+    ///
+    /// Each chunk's code is split into tokens, ignoring whitespace and
+    /// comments, and similarity is the Jaccard index of the two chunks' sets
+    /// of overlapping three-token shingles, from 0 to 1. Pairs where one
+    /// chunk contains the other, like a class and its methods, are skipped.
+    /// Chunks are compared pairwise, so only the first
+    /// [`MAX_DUPLICATE_CHUNKS`] are considered and any beyond are ignored.
+    /// Returns `(i, j, similarity)` with `i < j`, in index order.
+    pub fn find_near_duplicates(
+        chunks: &[CodeChunk],
+        similarity_threshold: f64,
+    ) -> Vec<(usize, usize, f64)> {
+        let chunks = &chunks[..chunks.len().min(MAX_DUPLICATE_CHUNKS)];
+        let tokens: Vec<Vec<&str>> =
+            chunks.iter().map(|c| code_tokens(&c.code, c.language)).collect();
+        let shingles: Vec<HashSet<&[&str]>> = tokens
+            .iter()
+            .map(|tokens| match tokens.len() {
+                0 => HashSet::new(),
+                n if n < SHINGLE_TOKENS => HashSet::from([tokens.as_slice()]),
+                _ => tokens.windows(SHINGLE_TOKENS).collect(),
+            })
+            .collect();
+
+        let mut pairs = Vec::new();
+        for (i, a) in chunks.iter().enumerate() {
+            for (j, b) in chunks.iter().enumerate().skip(i + 1) {
+                let nested = (a.start_byte <= b.start_byte && b.end_byte <= a.end_byte)
+                    || (b.start_byte <= a.start_byte && a.end_byte <= b.end_byte);
+                if nested || shingles[i].is_empty() || shingles[j].is_empty() {
+                    continue;
+                }
+                let shared = shingles[i].intersection(&shingles[j]).count();
+                let union = shingles[i].len() + shingles[j].len() - shared;
+                let similarity = shared as f64 / union as f64;
+                if similarity >= similarity_threshold {
+                    pairs.push((i, j, similarity));
+                }
             }
         }
-
-        // All should be balanced
-        paren_depth == 0 && brace_depth == 0 && bracket_depth == 0
+        pairs
     }
 
     /// Extract full file as a single chunk (fallback)
     pub fn extract_full_file(&self, code: &str) -> CodeChunk {
+        let (end_line, end_column) = line_column(code, code.len());
         CodeChunk {
             code: code.to_string(),
             node_type: "program".to_string(),
+            language: Language::default(),
             start_byte: 0,
             end_byte: code.len(),
             start_line: 0,
-            end_line: code.lines().count(),
+            end_line,
+            start_column: 0,
+            end_column,
             parent_type: None,
+            name: None,
+            decorators: Vec::new(),
+            dirty: false,
+            match_span: None,
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
+impl ChunkExtractor for Parser {
+    fn extract_chunks(&self, code: &str, language: Language) -> Vec<CodeChunk> {
+        let tree = LiteTree {
+            code: code.to_string(),
+            language,
+        };
+        Parser::extract_chunks(self, &tree, code)
+    }
+
+    fn validate_syntax(&self, code: &str, language: Language) -> Result<bool> {
+        Ok(Parser::validate_syntax(self, code, language))
+    }
+
+    fn extract_full_file(&self, code: &str) -> CodeChunk {
+        Parser::extract_full_file(self, code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supported_node_types_match_patterns() {
+        for &language in Parser::supported_languages() {
+            let patterns = patterns_for(language);
+            for pattern in patterns.declarations.iter().chain(&patterns.member) {
+                assert!(
+                    language.supported_node_types().contains(&pattern.node_type),
+                    "{} missing from {}",
+                    pattern.node_type,
+                    language.as_str()
+                );
+            }
+        }
+
+        for language in [Language::JavaScript, Language::TypeScript] {
+            assert!(Parser::supported_languages().contains(&language));
+            let types = language.supported_node_types();
+            assert!(types.contains(&"function_declaration"));
+            assert!(types.contains(&"class_declaration"));
+        }
+    }
+
+    #[test]
     fn test_parse_function() {
         let mut parser = Parser::new().unwrap();
         let code = r#"
@@ -577,9 +1695,856 @@ class Person {
 
         let tree = parser.parse(code, Language::JavaScript).unwrap();
         let chunks = parser.extract_chunks(&tree, code);
-        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks.len(), 2);
         assert_eq!(chunks[0].node_type, "class_declaration");
         assert!(chunks[0].code.contains("Person"));
+        assert_eq!(chunks[1].node_type, "method_definition");
+        assert_eq!(chunks[1].name.as_deref(), Some("constructor"));
+    }
+
+    #[test]
+    fn test_dispatch_by_language() {
+        let mut parser = Parser::new().unwrap();
+        let code = r#"def greet(name):
+    return "hi " + name
+
+function greet(name) {
+    return "hi " + name;
+}
+"#;
+
+        let js_tree = parser.parse(code, Language::JavaScript).unwrap();
+        let js_chunks = parser.extract_chunks(&js_tree, code);
+        assert_eq!(js_chunks.len(), 1);
+        assert_eq!(js_chunks[0].node_type, "function_declaration");
+        assert!(js_chunks[0].code.contains("function greet"));
+
+        let py_tree = parser.parse(code, Language::Python).unwrap();
+        let py_chunks = parser.extract_chunks(&py_tree, code);
+        assert_eq!(py_chunks.len(), 1);
+        assert_eq!(py_chunks[0].node_type, "function_definition");
+        assert!(py_chunks[0].code.contains("def greet"));
+    }
+
+    #[test]
+    fn test_js_identifier_names() {
+        let mut parser = Parser::new().unwrap();
+        let code = r#"function $init(){}
+
+export function grüßen(name) {
+    return name;
+}
+
+class $Store extends _Base {
+    $get(key) { return key; }
+}
+
+function 1bad() {}
+"#;
+
+        let tree = parser.parse(code, Language::TypeScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+        let names: Vec<_> = chunks.iter().filter_map(|c| c.name.as_deref()).collect();
+
+        assert_eq!(names, ["$init", "grüßen", "$Store", "$get"]);
+        assert_eq!(chunks[0].code, "function $init(){}");
+    }
+
+    #[test]
+    fn test_parse_rust_items() {
+        let mut parser = Parser::new().unwrap();
+        let code = r##"pub fn render(name: &str) -> String {
+    format!("{{{}}}", name)
+}
+
+pub struct Config<'a> {
+    name: &'a str,
+}
+
+enum Mode {
+    Fast,
+    Slow,
+}
+
+impl<'a> Config<'a> {
+    fn template(&self) -> &'static str {
+        r#"{ "open": "{", "brace": '}' }"#
+    }
+
+    fn close(&self) -> char {
+        '}'
+    }
+}
+"##;
+
+        let tree = parser.parse(code, Language::Rust).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+        let find = |node_type: &str, name: &str| {
+            chunks
+                .iter()
+                .find(|c| c.node_type == node_type && c.name.as_deref() == Some(name))
+                .unwrap_or_else(|| panic!("missing {} {}", node_type, name))
+        };
+
+        assert!(find("fn", "render").code.ends_with("name)\n}"));
+        assert!(find("struct", "Config").code.ends_with("str,\n}"));
+        assert!(find("enum", "Mode").code.contains("Slow"));
+
+        let template = find("fn", "template");
+        assert!(template.code.ends_with("\"#\n    }"));
+
+        let imp = find("impl", "Config");
+        assert!(imp.code.contains("fn close"));
+        assert!(imp.code.ends_with("'}'\n    }\n}"));
+    }
+
+    #[test]
+    fn test_chunk_columns() {
+        let mut parser = Parser::new().unwrap();
+        let code = "class Counter {\n    increment(by) {\n        return by;\n    }\n}\n";
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+
+        let method = chunks
+            .iter()
+            .find(|c| c.node_type == "method_definition")
+            .unwrap();
+        assert!(method.code.starts_with("increment"));
+        assert_eq!((method.start_line, method.start_column), (1, 4));
+        assert_eq!((method.end_line, method.end_column), (3, 5));
+
+        // Tabs count as a single column
+        let code = "class Counter:\n\tdef increment(self):\n\t\treturn 1\n";
+        let tree = parser.parse(code, Language::Python).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+
+        let method = chunks
+            .iter()
+            .find(|c| c.name.as_deref() == Some("increment"))
+            .unwrap();
+        assert_eq!(method.code, "def increment(self):\n\t\treturn 1");
+        assert_eq!((method.start_line, method.start_column), (1, 1));
+        assert_eq!((method.end_line, method.end_column), (2, 10));
+    }
+
+    #[test]
+    fn test_debug_spans() {
+        let code = "class Greeter {\n  greet(name) {\n    return `hi ${name}`;\n  }\n}\n";
+
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+        assert!(chunks.iter().all(|c| c.match_span.is_none()));
+
+        let mut parser = Parser::with_config(ParserConfig {
+            debug_spans: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+
+        assert_eq!(chunks.len(), 2);
+        let (class, method) = (&chunks[0], &chunks[1]);
+        let (start, end) = class.match_span.unwrap();
+        assert_eq!(&code[start..end], "class Greeter {");
+        assert_eq!(start, class.start_byte);
+        assert!(end < class.end_byte);
+        let (start, end) = method.match_span.unwrap();
+        assert_eq!(&code[start..end], "greet(name) {");
+        assert_eq!(start, method.start_byte);
+        assert!(end < method.end_byte);
+    }
+
+    #[test]
+    fn test_fallback_windows() {
+        let code = "const a = 1;\nconsole.log(a);\n\nawait run(a);\nprocess.exit(0);\n";
+
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        assert!(parser.extract_chunks(&tree, code).is_empty());
+
+        let mut parser = Parser::with_config(ParserConfig {
+            fallback_window_lines: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.node_type == "window"));
+        assert_eq!(chunks[0].code, "const a = 1;\nconsole.log(a);");
+        assert_eq!(chunks[1].code, "\nawait run(a);");
+        assert_eq!((chunks[1].start_line, chunks[1].end_line), (2, 3));
+        assert_eq!(chunks[2].code, "process.exit(0);");
+        assert_eq!(&code[chunks[2].start_byte..chunks[2].end_byte], chunks[2].code);
+    }
+
+    #[test]
+    fn test_decorators_attached() {
+        let mut parser = Parser::new().unwrap();
+        let code = r#"import { Component } from '@angular/core';
+
+@Injectable()
+@Component({
+  selector: 'app-root',
+  template: '<p>{{ title) }}</p>',
+})
+export class AppComponent {
+  @HostListener('window:resize', ['$event'])
+  onResize(event) {
+    this.width = event.target.innerWidth;
+  }
+}
+"#;
+        let tree = parser.parse(code, Language::TypeScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+
+        let class = chunks
+            .iter()
+            .find(|c| c.node_type == "class_declaration")
+            .unwrap();
+        assert_eq!(
+            class.decorators,
+            vec![
+                "@Injectable()".to_string(),
+                "@Component({\n  selector: 'app-root',\n  template: '<p>{{ title) }}</p>',\n})"
+                    .to_string(),
+            ]
+        );
+        assert!(class.code.starts_with("export class AppComponent {"));
+        assert!(class.code.ends_with("innerWidth;\n  }\n}"));
+
+        let method = chunks
+            .iter()
+            .find(|c| c.node_type == "method_definition")
+            .unwrap();
+        assert_eq!(
+            method.decorators,
+            vec!["@HostListener('window:resize', ['$event'])".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_module_body_chunks() {
+        let code = r#"import { load } from './load.js';
+
+const config = await load();
+
+function start(options) {
+    return run(options);
+}
+
+await start(config);
+"#;
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        assert_eq!(parser.extract_chunks(&tree, code).len(), 1);
+
+        let mut parser = Parser::with_config(ParserConfig {
+            emit_module_body: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].node_type, "function_declaration");
+
+        let bodies: Vec<&str> = chunks
+            .iter()
+            .filter(|c| c.node_type == "module_body")
+            .map(|c| c.code.as_str())
+            .collect();
+        assert_eq!(
+            bodies,
+            vec![
+                "import { load } from './load.js';\n\nconst config = await load();",
+                "await start(config);",
+            ]
+        );
+        assert_eq!(chunks[2].start_line, 8);
+        assert_eq!(&code[chunks[2].start_byte..chunks[2].end_byte], chunks[2].code);
+    }
+
+    #[test]
+    fn test_declaration_at_line() {
+        let code = concat!(
+            "class Cart {\n",
+            "  add(item) {\n",
+            "    this.items.push(item);\n",
+            "  }\n",
+            "}\n",
+            "\n",
+            "function total() {\n",
+            "  return 0;\n",
+            "}\n",
+        );
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+
+        let method = Parser::declaration_at_line(&chunks, 3).unwrap();
+        assert_eq!(method.node_type, "method_definition");
+        assert_eq!(method.name.as_deref(), Some("add"));
+
+        let class = Parser::declaration_at_line(&chunks, 1).unwrap();
+        assert_eq!(class.name.as_deref(), Some("Cart"));
+        let function = Parser::declaration_at_line(&chunks, 8).unwrap();
+        assert_eq!(function.name.as_deref(), Some("total"));
+
+        assert!(Parser::declaration_at_line(&chunks, 6).is_none());
+        assert!(Parser::declaration_at_line(&chunks, 0).is_none());
+    }
+
+    #[test]
+    fn test_find_near_duplicates() {
+        let code = concat!(
+            "function totalPrice(items) {\n",
+            "  let total = 0;\n",
+            "  for (const item of items) {\n",
+            "    total += item.price * item.quantity;\n",
+            "  }\n",
+            "  return Math.round(total * 100) / 100;\n",
+            "}\n",
+            "function totalCost(items) {\n",
+            "  // copied from totalPrice\n",
+            "  let total = 0;\n",
+            "  for (const item of items) {\n",
+            "    total += item.price * item.quantity;\n",
+            "  }\n",
+            "  return Math.round(total * 100) / 100;\n",
+            "}\n",
+            "function greet(name) {\n",
+            "  console.log(`Hello, ${name}!`);\n",
+            "}\n",
+        );
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+        assert_eq!(chunks.len(), 3);
+
+        let pairs = Parser::find_near_duplicates(&chunks, 0.8);
+        assert_eq!(pairs.len(), 1);
+        let (i, j, similarity) = pairs[0];
+        assert_eq!((i, j), (0, 1));
+        assert!(similarity > 0.8 && similarity < 1.0);
+
+        // The unrelated function shares almost nothing with either copy
+        let all = Parser::find_near_duplicates(&chunks, 0.0);
+        assert!(all.iter().filter(|p| p.1 == 2).all(|p| p.2 < 0.2));
+    }
+
+    #[test]
+    fn test_diff_chunks() {
+        let old_code = concat!(
+            "function keep() {\n  return 1;\n}\n",
+            "function drop() {\n  return 2;\n}\n",
+            "function change() {\n  return 3;\n}\n",
+        );
+        let new_code = concat!(
+            "function add() {\n  return 4;\n}\n",
+            "function keep() {\n  // moved down\n  return 1;\n}\n",
+            "function change() {\n  return 30;\n}\n",
+        );
+        let mut parser = Parser::new().unwrap();
+        let old_tree = parser.parse(old_code, Language::JavaScript).unwrap();
+        let new_tree = parser.parse(new_code, Language::JavaScript).unwrap();
+        let old = parser.extract_chunks(&old_tree, old_code);
+        let new = parser.extract_chunks(&new_tree, new_code);
+
+        let diff = Parser::diff_chunks(&old, &new);
+        let name = |chunks: &[CodeChunk], i: usize| chunks[i].name.clone().unwrap();
+        let mut summary: Vec<(&str, String)> = diff
+            .changes
+            .iter()
+            .map(|change| match *change {
+                ChunkChange::Added { new: n } => ("added", name(&new, n)),
+                ChunkChange::Removed { old: o } => ("removed", name(&old, o)),
+                ChunkChange::Modified { old: o, .. } => ("modified", name(&old, o)),
+                ChunkChange::Unchanged { old: o, .. } => ("unchanged", name(&old, o)),
+            })
+            .collect();
+        summary.sort();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("added", "add".to_string()),
+                ("modified", "change".to_string()),
+                ("removed", "drop".to_string()),
+                ("unchanged", "keep".to_string()),
+            ]
+        );
+        assert!(diff.has_changes());
+        assert!(!Parser::diff_chunks(&old, &old).has_changes());
+    }
+
+    #[test]
+    fn test_extract_chunks_ignoring_ranges() {
+        let code = concat!(
+            "function keep() {}\n",
+            "// <generated>\n",
+            "function generatedA() {}\n",
+            "function generatedB() {}\n",
+            "// </generated>\n",
+            "class Kept {\n",
+            "  run() {}\n",
+            "}\n",
+        );
+        let generated = code.find("// <generated>").unwrap();
+        let middle = code.find("function generatedB").unwrap();
+        let end = code.find("class Kept").unwrap();
+
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        // Overlapping and adjacent ranges covering the generated block
+        let ranges = [(middle, end), (generated, middle), (generated + 3, middle + 5)];
+        let chunks = parser.extract_chunks_ignoring(&tree, code, &ranges);
+        let names: Vec<&str> = chunks.iter().filter_map(|c| c.name.as_deref()).collect();
+        assert_eq!(names, vec!["keep", "Kept", "run"]);
+
+        let chunks = parser.extract_chunks_ignoring(&tree, code, &[]);
+        assert_eq!(chunks, parser.extract_chunks(&tree, code));
+    }
+
+    #[test]
+    fn test_top_level_variable_declarations() {
+        let code = concat!(
+            "const CONFIG = {\n",
+            "  port: 8080,\n",
+            "  hosts: ['a', 'b'],\n",
+            "};\n",
+            "\n",
+            "function run() {\n",
+            "  const local = CONFIG.port;\n",
+            "}\n",
+        );
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        assert_eq!(parser.extract_chunks(&tree, code).len(), 1);
+
+        let mut parser = Parser::with_config(ParserConfig {
+            emit_variables: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+        let variables: Vec<&CodeChunk> = chunks
+            .iter()
+            .filter(|c| c.node_type == "variable_declaration")
+            .collect();
+
+        assert_eq!(variables.len(), 1);
+        assert_eq!(variables[0].name.as_deref(), Some("CONFIG"));
+        assert_eq!(variables[0].code, code[..code.find(";\n").unwrap() + 1]);
+        assert_eq!((variables[0].start_line, variables[0].end_line), (0, 3));
+    }
+
+    #[test]
+    fn test_multiple_variable_declarators() {
+        let code = concat!(
+            "export let a = 1, b = [2, 3];\n",
+            "var m: Map<string, number> = new Map<string, number>(), n\n",
+            "const { x, y } = point\n",
+            "  .normalize()\n",
+            "let label = 'a' +\n",
+            "  'b'\n",
+            "const enum Mode { On, Off }\n",
+        );
+        let mut parser = Parser::with_config(ParserConfig {
+            emit_variables: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let tree = parser.parse(code, Language::TypeScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+        let variables: Vec<(Option<&str>, &str)> = chunks
+            .iter()
+            .filter(|c| c.node_type == "variable_declaration")
+            .map(|c| (c.name.as_deref(), c.code.as_str()))
+            .collect();
+
+        assert_eq!(
+            variables,
+            vec![
+                (Some("a"), "export let a = 1"),
+                (Some("b"), "b = [2, 3];"),
+                (Some("m"), "var m: Map<string, number> = new Map<string, number>()"),
+                (Some("n"), "n"),
+                (None, "const { x, y } = point\n  .normalize()"),
+                (Some("label"), "let label = 'a' +\n  'b'"),
+            ]
+        );
+        for chunk in &chunks {
+            assert_eq!(&code[chunk.start_byte..chunk.end_byte], chunk.code);
+        }
+    }
+
+    #[test]
+    fn test_chunks_in_source_order() {
+        let code = concat!(
+            "class Store {\n",
+            "  load() {}\n",
+            "}\n",
+            "function helper() {}\n",
+            "class Cache {\n",
+            "  get() {}\n",
+            "}\n",
+        );
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+        let names: Vec<&str> = chunks.iter().filter_map(|c| c.name.as_deref()).collect();
+        assert_eq!(names, vec!["Store", "load", "helper", "Cache", "get"]);
+    }
+
+    #[test]
+    fn test_interface_member_signatures() {
+        let code = concat!(
+            "export interface Repository<T> extends Base {\n",
+            "  /** Display name; shown in lists */\n",
+            "  name: string;\n",
+            "  find(id: string, options?: Map<string, T>): Promise<T>;\n",
+            "  readonly settings?: {\n",
+            "    cache: boolean;\n",
+            "  },\n",
+            "  mode:\n",
+            "    | 'read'\n",
+            "    | 'write'\n",
+            "  [key: string]: unknown;\n",
+            "}\n",
+        );
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(code, Language::TypeScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+
+        assert_eq!(chunks[0].node_type, "interface_declaration");
+        assert_eq!(chunks[0].name.as_deref(), Some("Repository"));
+        let members: Vec<(&str, Option<&str>, &str)> = chunks[1..]
+            .iter()
+            .map(|c| (c.node_type.as_str(), c.name.as_deref(), c.code.as_str()))
+            .collect();
+        assert_eq!(
+            members,
+            vec![
+                ("property_signature", Some("name"), "name: string;"),
+                (
+                    "method_signature",
+                    Some("find"),
+                    "find(id: string, options?: Map<string, T>): Promise<T>;"
+                ),
+                (
+                    "property_signature",
+                    Some("settings"),
+                    "readonly settings?: {\n    cache: boolean;\n  }"
+                ),
+                (
+                    "property_signature",
+                    Some("mode"),
+                    "mode:\n    | 'read'\n    | 'write'"
+                ),
+            ]
+        );
+        for chunk in &chunks[1..] {
+            assert_eq!(chunk.parent_type.as_deref(), Some("interface_declaration"));
+            assert_eq!(&code[chunk.start_byte..chunk.end_byte], chunk.code);
+        }
+    }
+
+    #[test]
+    fn test_typescript_namespaces() {
+        let code = concat!(
+            "namespace Validation {\n",
+            "  export function isZip(s: string) {\n",
+            "    return s.length === 5;\n",
+            "  }\n",
+            "}\n",
+            "\n",
+            "declare module \"left-pad\" {\n",
+            "  namespace Inner {\n",
+            "    class Pad {\n",
+            "      run() {}\n",
+            "    }\n",
+            "  }\n",
+            "}\n",
+            "\n",
+            "function topLevel() {}\n",
+        );
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(code, Language::TypeScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+        let find = |name: &str| chunks.iter().find(|c| c.name.as_deref() == Some(name)).unwrap();
+
+        let namespace = find("Validation");
+        assert_eq!(namespace.node_type, "namespace");
+        assert_eq!(namespace.parent_type, None);
+        let function = find("isZip");
+        assert_eq!(function.node_type, "function_declaration");
+        assert_eq!(function.parent_type.as_deref(), Some("namespace"));
+
+        let module = find("left-pad");
+        assert_eq!(module.node_type, "module");
+        assert_eq!(find("Inner").parent_type.as_deref(), Some("module"));
+        assert_eq!(find("Pad").parent_type.as_deref(), Some("namespace"));
+        assert_eq!(find("run").parent_type.as_deref(), Some("class_declaration"));
+        assert_eq!(find("topLevel").parent_type, None);
+    }
+
+    #[test]
+    fn test_snap_to_statements() {
+        let code = r#"function total(items) {
+    const sum = items
+        .map((item) => item.price)
+        .reduce((a, b) => a + b, 0);
+    return sum;
+}
+"#;
+        let start = code.find(".map").unwrap();
+        let end = code.find("item.price").unwrap();
+        let (start_line, start_column) = line_column(code, start);
+        let chunk = CodeChunk {
+            code: code[start..end].to_string(),
+            start_byte: start,
+            end_byte: end,
+            start_line,
+            end_line: start_line,
+            start_column,
+            end_column: start_column + end - start,
+            node_type: "window".to_string(),
+            ..Default::default()
+        };
+
+        let snapped = Parser::snap_to_statements(code, &chunk, Language::JavaScript);
+        assert_eq!(
+            snapped.code,
+            concat!(
+                "    const sum = items\n",
+                "        .map((item) => item.price)\n",
+                "        .reduce((a, b) => a + b, 0);"
+            )
+        );
+        assert_eq!(&code[snapped.start_byte..snapped.end_byte], snapped.code);
+        assert_eq!((snapped.start_line, snapped.start_column), (1, 0));
+        assert_eq!((snapped.end_line, snapped.end_column), (3, 36));
+        assert_eq!(snapped.node_type, "window");
+
+        let whole = Parser::snap_to_statements(code, &snapped, Language::JavaScript);
+        assert_eq!(whole, snapped);
+    }
+
+    #[test]
+    fn test_region_chunks() {
+        let code = r#"import { api } from "./api";
+
+// #region Handlers
+function onLoad() {
+    api.load();
+}
+
+  // #region Helpers
+  function log(message) {}
+  // #endregion
+
+// #endregion
+function after() {}
+"#;
+        let mut parser = Parser::with_config(ParserConfig {
+            emit_regions: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+        let regions: Vec<_> = chunks.iter().filter(|c| c.node_type == "region").collect();
+
+        assert_eq!(regions.len(), 2);
+        let outer = regions[0];
+        assert_eq!(outer.name.as_deref(), Some("Handlers"));
+        assert_eq!(outer.parent_type, None);
+        assert_eq!(outer.start_byte, code.find("// #region Handlers").unwrap());
+        assert_eq!(outer.end_byte, code.rfind("// #endregion").unwrap() + "// #endregion".len());
+        assert_eq!((outer.start_line, outer.end_line), (2, 11));
+        assert_eq!(&code[outer.start_byte..outer.end_byte], outer.code);
+
+        let inner = regions[1];
+        assert_eq!(inner.name.as_deref(), Some("Helpers"));
+        assert_eq!(inner.parent_type.as_deref(), Some("region"));
+        assert_eq!((inner.start_line, inner.end_line), (7, 9));
+        assert_eq!(chunks.iter().filter(|c| c.node_type == "function_declaration").count(), 3);
+    }
+
+    #[test]
+    fn test_mark_regions() {
+        let code = "// MARK: - Setup\nfunction a() {}\n\n// MARK: Teardown\nfunction b() {}\n";
+        let parser = Parser::with_config(ParserConfig {
+            emit_regions: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let chunks = ChunkExtractor::extract_chunks(&parser, code, Language::JavaScript);
+        let regions: Vec<_> = chunks.iter().filter(|c| c.node_type == "region").collect();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].name.as_deref(), Some("Setup"));
+        assert_eq!(regions[0].code, "// MARK: - Setup\nfunction a() {}");
+        assert_eq!(regions[1].name.as_deref(), Some("Teardown"));
+        assert_eq!(regions[1].code, "// MARK: Teardown\nfunction b() {}");
+    }
+
+    #[test]
+    fn test_minified_file_byte_windows() {
+        let code = "function f(){return \"}\"};var x=[1,2];".repeat(20_000);
+        let parser = Parser::with_config(ParserConfig {
+            max_line_length: Some(1000),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let started = std::time::Instant::now();
+        let chunks = ChunkExtractor::extract_chunks(&parser, &code, Language::JavaScript);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        assert!(chunks.len() >= code.len() / 1000);
+        let mut expected_start = 0;
+        for chunk in &chunks {
+            assert_eq!(chunk.node_type, "byte_window");
+            assert_eq!(chunk.start_byte, expected_start);
+            assert!(chunk.end_byte - chunk.start_byte <= 1000);
+            assert_eq!(&code[chunk.start_byte..chunk.end_byte], chunk.code);
+            assert!(chunk.code.ends_with(';'));
+            assert_eq!((chunk.start_line, chunk.end_line), (UNKNOWN_LINE, UNKNOWN_LINE));
+            expected_start = chunk.end_byte;
+        }
+        assert_eq!(expected_start, code.len());
+
+        // Files with short lines are parsed as usual
+        let normal =
+            ChunkExtractor::extract_chunks(&parser, "function f() {}\n", Language::JavaScript);
+        assert_eq!(normal[0].node_type, "function_declaration");
+    }
+
+    #[test]
+    fn test_byte_windows_split_at_char_boundaries() {
+        let code = "var a€=1;var b漢=2;var c€漢=3;".repeat(3);
+        for window in 1..=12 {
+            let parser = Parser::with_config(ParserConfig {
+                max_line_length: Some(window),
+                ..Default::default()
+            })
+            .unwrap();
+            let chunks = ChunkExtractor::extract_chunks(&parser, &code, Language::JavaScript);
+
+            // Whitespace-only windows are dropped
+            let mut expected_start = 0;
+            for chunk in &chunks {
+                assert_eq!(code[expected_start..chunk.start_byte].trim(), "");
+                assert_eq!(&code[chunk.start_byte..chunk.end_byte], chunk.code);
+                assert!(chunk.code.len() <= window.max(3));
+                expected_start = chunk.end_byte;
+            }
+            assert_eq!(expected_start, code.len());
+        }
+    }
+
+    #[test]
+    fn test_find_by_qualified_name() {
+        let mut parser = Parser::new().unwrap();
+        let code = r#"
+class Person {
+    greet() {
+        if (this.name) {
+            return "Hi " + this.name;
+        }
+    }
+}
+
+function helper() {
+    return 1;
+}
+"#;
+
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+
+        let greet = Parser::find_by_qualified_name(&chunks, "Person.greet").unwrap();
+        assert_eq!(greet.node_type, "method_definition");
+        assert!(greet.code.trim_start().starts_with("greet()"));
+
+        let helper = Parser::find_by_qualified_name(&chunks, "helper").unwrap();
+        assert_eq!(helper.node_type, "function_declaration");
+
+        assert!(Parser::find_by_qualified_name(&chunks, "Person.missing").is_none());
+        assert!(Parser::find_by_qualified_name(&chunks, "greet").is_none());
+    }
+
+    #[test]
+    fn test_count_declarations_matches_extraction() {
+        let mut parser = Parser::new().unwrap();
+        let code = r#"fn a() -> u32 {
+    1
+}
+
+pub struct B {
+    x: u32,
+}
+
+impl B {
+    fn get(&self) -> u32 {
+        self.x
+    }
+}
+"#;
+        let tree = parser.parse(code, Language::Rust).unwrap();
+
+        let count = parser.count_declarations(code, Language::Rust);
+        assert_eq!(count, parser.extract_chunks(&tree, code).len());
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn test_count_declarations_skips_class_members() {
+        let mut parser = Parser::new().unwrap();
+        let code = r#"class Counter {
+    increment() {
+        this.count += 1;
+    }
+}
+
+function reset(counter) {
+    counter.count = 0;
+}
+"#;
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+        let chunks = parser.extract_chunks(&tree, code);
+        assert!(chunks.iter().any(|c| c.node_type == "method_definition"));
+
+        // The class and the function, but not the method
+        let count = parser.count_declarations(code, Language::JavaScript);
+        assert_eq!(count, 2);
+        assert_eq!(count, chunks.len() - 1);
+    }
+
+    #[test]
+    fn test_extract_chunks_bounded() {
+        let parser = Parser::new().unwrap();
+        let code = r#"function a() { return 1; }
+function b() { return 2; }
+function c() { return 3; }
+"#;
+
+        let (chunks, truncated) = parser.extract_chunks_bounded(code, Language::JavaScript, 30);
+        assert!(truncated);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].code, "function b() { return 2; }");
+
+        let (chunks, truncated) =
+            parser.extract_chunks_bounded(code, Language::JavaScript, 10_000);
+        assert!(!truncated);
+        assert_eq!(chunks.len(), 3);
     }
 
     #[test]
@@ -591,12 +2556,78 @@ class Person {
         assert!(!parser.validate_syntax("function f() return 42; }", Language::JavaScript));
     }
 
+    #[test]
+    fn test_analyze_matches_separate_calls() {
+        let code = r#"import { a } from "./a";
+
+function first() {
+    return "}";
+}
+
+class Second {
+    run() { return first(); }
+}
+"#;
+        let mut parser = Parser::new().unwrap();
+        let tree = parser.parse(code, Language::JavaScript).unwrap();
+
+        let analysis = parser.analyze(code, Language::JavaScript);
+        assert_eq!(analysis.chunks, parser.extract_chunks(&tree, code));
+        assert!(analysis.is_valid);
+        assert_eq!(analysis.first_error, None);
+
+        let broken = parser.analyze("function f() {", Language::JavaScript);
+        assert!(!broken.is_valid);
+        assert_eq!(broken.chunks, Vec::new());
+        assert_eq!(
+            broken.first_error.map(|e| e.kind),
+            Some(SyntaxErrorKind::Unclosed { delimiter: '{' })
+        );
+    }
+
+    #[test]
+    fn test_unterminated_string_reported() {
+        let parser = Parser::new().unwrap();
+        let code = "function f() {\n  return \"oops;\n}\n";
+
+        let err = parser
+            .validate_syntax_detailed(code, Language::JavaScript)
+            .unwrap_err();
+        assert_eq!(err.kind, SyntaxErrorKind::UnterminatedLiteral { delimiter: "\"" });
+        assert_eq!((err.byte, err.line, err.column), (24, 1, 9));
+        assert!(!parser.validate_syntax(code, Language::JavaScript));
+    }
+
+    #[test]
+    fn test_unterminated_block_comment_reported() {
+        let parser = Parser::new().unwrap();
+        let code = "fn main() {}\n  /* never closed {\nfn other() {}\n";
+
+        let err = parser
+            .validate_syntax_detailed(code, Language::Rust)
+            .unwrap_err();
+        assert_eq!(err.kind, SyntaxErrorKind::UnterminatedLiteral { delimiter: "/*" });
+        assert_eq!((err.byte, err.line, err.column), (15, 1, 2));
+        assert_eq!(err.to_string(), "unterminated /* literal starting at 2:3");
+
+        let err = parser
+            .validate_syntax_detailed("let x = (1, 2];", Language::JavaScript)
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            SyntaxErrorKind::MismatchedClose {
+                expected: ')',
+                found: ']'
+            }
+        );
+    }
+
     #[test]
     fn test_extract_block() {
         let parser = Parser::new().unwrap();
         let code = "function test() { return { a: 1 }; }";
 
-        let block = parser.extract_block(code, 0);
+        let block = parser.extract_block(code, 0, Language::JavaScript);
         assert!(block.is_some());
         assert_eq!(block.unwrap(), code);
     }
@@ -0,0 +1,156 @@
+//! Language selection for files in a repository
+//!
+//! [`LanguageResolver`] decides which [`Language`] a file is parsed as: the
+//! first override rule whose glob matches the path wins, and paths matching
+//! no rule fall back to [`Language::from_path`].
+
+use crate::models::{AgentBoosterError, Language, Result};
+use regex::Regex;
+use std::path::Path;
+
+/// An override rule: paths matching the glob are parsed as `language`
+#[derive(Debug, Clone)]
+struct Override {
+    glob: String,
+    regex: Regex,
+    /// Whether the glob has no `/` and is matched against the file name only
+    file_name_only: bool,
+    language: Language,
+}
+
+/// Maps file paths to languages, with glob overrides applied in order
+///
+/// Globs use `/` as the separator on every platform and support `*` (any
+/// characters except `/`), `**` (any characters, including `/`; `**/` also
+/// matches no directory at all), `?` (one character except `/`) and
+/// `{a,b}` alternatives. A glob without `/`, such as `*.config.ts`, is
+/// matched against the file name alone; any other glob must match the whole
+/// path, minus a leading `./`.
+#[derive(Debug, Clone, Default)]
+pub struct LanguageResolver {
+    overrides: Vec<Override>,
+}
+
+impl LanguageResolver {
+    /// Create a resolver using extension detection only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an override rule, checked after the ones already added
+    ///
+    /// Fails with [`AgentBoosterError::ConfigError`] if the glob has
+    /// unbalanced braces.
+    pub fn with_override(mut self, glob: &str, language: Language) -> Result<Self> {
+        self.overrides.push(Override {
+            glob: glob.to_string(),
+            regex: glob_regex(glob)?,
+            file_name_only: !glob.contains('/'),
+            language,
+        });
+        Ok(self)
+    }
+
+    /// Get the language a file should be parsed as
+    ///
+    /// Fails with [`AgentBoosterError::UnsupportedLanguage`] when no rule
+    /// matches and the extension is not recognized.
+    pub fn resolve(&self, path: &Path) -> Result<Language> {
+        let full = path.to_string_lossy().replace('\\', "/");
+        let full = full.strip_prefix("./").unwrap_or(&full);
+        let file_name = full.rsplit('/').next().unwrap_or(full);
+
+        self.overrides
+            .iter()
+            .find(|rule| {
+                let candidate = if rule.file_name_only { file_name } else { full };
+                rule.regex.is_match(candidate)
+            })
+            .map_or_else(|| Language::from_path(path), |rule| Ok(rule.language))
+    }
+
+    /// Get the override globs in the order they are checked
+    pub fn globs(&self) -> impl Iterator<Item = &str> {
+        self.overrides.iter().map(|rule| rule.glob.as_str())
+    }
+}
+
+/// Translate a glob into an anchored regex
+fn glob_regex(glob: &str) -> Result<Regex> {
+    let invalid = || AgentBoosterError::ConfigError(format!("invalid glob '{}'", glob));
+    let mut pattern = String::from("^");
+    let mut depth = 0usize;
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '{' => {
+                depth += 1;
+                pattern.push_str("(?:");
+            }
+            '}' => {
+                depth = depth.checked_sub(1).ok_or_else(invalid)?;
+                pattern.push(')');
+            }
+            ',' if depth > 0 => pattern.push('|'),
+            _ => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    if depth > 0 {
+        return Err(invalid());
+    }
+    pattern.push('$');
+    Regex::new(&pattern).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_forces_language() {
+        let resolver = LanguageResolver::new()
+            .with_override("*.config.ts", Language::JavaScript)
+            .unwrap()
+            .with_override("scripts/**/*.{py,txt}", Language::Python)
+            .unwrap();
+
+        let resolve = |path: &str| resolver.resolve(Path::new(path)).unwrap();
+        assert_eq!(resolve("web/vite.config.ts"), Language::JavaScript);
+        assert_eq!(resolve("./scripts/build.txt"), Language::Python);
+        assert_eq!(resolve("scripts/ci/deploy.py"), Language::Python);
+        assert_eq!(
+            resolver.globs().collect::<Vec<_>>(),
+            vec!["*.config.ts", "scripts/**/*.{py,txt}"]
+        );
+    }
+
+    #[test]
+    fn test_unmatched_path_uses_extension() {
+        let resolver = LanguageResolver::new()
+            .with_override("*.config.ts", Language::JavaScript)
+            .unwrap();
+
+        let resolve = |path: &str| resolver.resolve(Path::new(path));
+        assert_eq!(resolve("src/app.ts").unwrap(), Language::TypeScript);
+        assert_eq!(resolve("src/lib.rs").unwrap(), Language::Rust);
+        assert!(matches!(
+            resolve("notes/todo.txt"),
+            Err(AgentBoosterError::UnsupportedLanguage(_))
+        ));
+        assert!(LanguageResolver::new()
+            .with_override("*.{ts", Language::JavaScript)
+            .is_err());
+    }
+}
@@ -88,7 +88,7 @@ impl SimilarityMatcher {
     }
 
     /// Normalize code for comparison (remove whitespace, comments, etc.)
-    fn normalize_code(code: &str) -> String {
+    pub(crate) fn normalize_code(code: &str) -> String {
         code.lines()
             .map(|line| {
                 // Remove leading/trailing whitespace
@@ -187,6 +187,7 @@ mod tests {
             end_line: 0,
             node_type: "function_declaration".to_string(),
             parent_type: None,
+            ..Default::default()
         }
     }
 
@@ -12,6 +12,7 @@ fn create_search_result(code: &str, similarity: f32, start: usize, end: usize) -
             end_line: 0,
             node_type: "function_declaration".to_string(),
             parent_type: None,
+            ..Default::default()
         },
         similarity,
         chunk_index: 0,
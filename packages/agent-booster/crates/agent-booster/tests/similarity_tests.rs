@@ -10,6 +10,7 @@ fn create_chunk(code: &str, node_type: &str) -> CodeChunk {
         end_line: code.lines().count().saturating_sub(1),
         node_type: node_type.to_string(),
         parent_type: None,
+        ..Default::default()
     }
 }
 